@@ -0,0 +1,314 @@
+//
+//  Description  :    Canonical XML (C14N) output of a parsed tree, for signing and comparing XML files
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::io::Write;
+use document::{XMLDocument, NodeId, NodeKind};
+use namespaces::{XML_NAMESPACE, split_name, declared_prefix};
+
+//Which form of canonical XML to write
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct CanonicalOptions {
+    //Exclusive canonicalization only declares the namespaces an element uses, so a subtree reads the same wherever it is placed
+    pub exclusive: bool,
+    pub with_comments: bool,
+    //Prefixes which exclusive canonicalization declares as inclusive canonicalization would, with '#default' for the default namespace
+    pub inclusive_prefixes: Vec<String>,
+}
+
+impl Default for CanonicalOptions {
+    fn default() -> CanonicalOptions {
+        CanonicalOptions::new()
+    }
+}
+
+impl CanonicalOptions {
+    pub fn new() -> CanonicalOptions {
+        CanonicalOptions {
+            exclusive: false,
+            with_comments: false,
+            inclusive_prefixes: Vec::new(),
+        }
+    }
+}
+
+//Binds a prefix, replacing any earlier binding of it
+fn declare<'a>(namespaces: &mut Vec<(&'a str, &'a str)>, prefix: &'a str, uri: &'a str) {
+    match namespaces.iter().position(|&(declared, _)| declared == prefix) {
+        Some(index) => namespaces[index] = (prefix, uri),
+        None => namespaces.push((prefix, uri)),
+    }
+}
+
+fn lookup<'a>(namespaces: &[(&'a str, &'a str)], prefix: &str) -> Option<&'a str> {
+    if prefix == "xml" {
+        return Some(XML_NAMESPACE);
+    }
+
+    namespaces.iter().find(|&&(declared, _)| declared == prefix).map(|&(_, uri)| uri)
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('\r', "&#xD;")
+}
+
+//Whitespace left in an attribute value after it was read is written as character references, so it reads back the same
+fn escape_attribute(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('"', "&quot;").replace('\t', "&#x9;").replace('\n', "&#xA;").replace('\r', "&#xD;")
+}
+
+//Writes a node and everything below it. 'in_scope' holds every namespace bound above the node,
+//and 'rendered' the namespaces already declared by the elements written around it.
+fn write_canonical_node<'a>(document: &'a XMLDocument, node: NodeId, options: &CanonicalOptions, in_scope: &[(&'a str, &'a str)], rendered: &[(&'a str, &'a str)], inherited_attributes: &[(&'a str, &'a str)], output: &mut String) {
+    let name = document.name(node);
+
+    match document.kind(node) {
+        NodeKind::Element => {},
+        NodeKind::Text => {
+            output.push_str(&escape_text(document.data(node)));
+            return;
+        },
+        NodeKind::Comment => {
+            if options.with_comments {
+                output.push_str(&format!("<!--{}-->", document.data(node)));
+            }
+            return;
+        },
+        NodeKind::ProcessingInstruction => {
+            let data = document.data(node);
+            if !data.is_empty() {
+                output.push_str(&format!("<?{} {}?>", name, data));
+            }
+            else {
+                output.push_str(&format!("<?{}?>", name));
+            }
+            return;
+        },
+    }
+
+    let mut in_scope = in_scope.to_vec();
+    let mut attributes: Vec<(&str, &str)> = Vec::new();
+
+    for (attribute_name, value) in document.attributes(node) {
+        match declared_prefix(attribute_name) {
+            Some(prefix) => declare(&mut in_scope, prefix, value),
+            None => attributes.push((attribute_name, value)),
+        }
+    }
+    attributes.extend(inherited_attributes.iter().cloned());
+
+    //Choose the prefixes which could need declaring here
+    let mut prefixes: Vec<&str> = Vec::new();
+    if options.exclusive {
+        prefixes.push(split_name(name).0);
+
+        for &(attribute_name, _) in &attributes {
+            let (prefix, _) = split_name(attribute_name);
+
+            //Attributes without a prefix are never in the default namespace
+            if !prefix.is_empty() {
+                prefixes.push(prefix);
+            }
+        }
+
+        for prefix in &options.inclusive_prefixes {
+            let prefix = if prefix == "#default" { "" } else { prefix.as_str() };
+
+            if let Some(&(declared, _)) = in_scope.iter().find(|&&(declared, _)| declared == prefix) {
+                prefixes.push(declared);
+            }
+        }
+    }
+    else {
+        prefixes.extend(in_scope.iter().map(|&(prefix, _)| prefix));
+    }
+
+    prefixes.sort();
+    prefixes.dedup();
+
+    //Declare every namespace whose binding differs from the one written around this element
+    let mut now_rendered = rendered.to_vec();
+    let mut declarations: Vec<(&str, &str)> = Vec::new();
+
+    for prefix in prefixes {
+        if prefix == "xml" {
+            continue;
+        }
+
+        let uri = lookup(&in_scope, prefix).unwrap_or("");
+
+        //A prefix can only be unbound by leaving the element which declared it
+        if !prefix.is_empty() && uri.is_empty() {
+            continue;
+        }
+
+        if lookup(rendered, prefix).unwrap_or("") != uri {
+            declarations.push((prefix, uri));
+            declare(&mut now_rendered, prefix, uri);
+        }
+    }
+
+    //Attributes are sorted by namespace URI, then local name
+    let mut sorted_attributes: Vec<(&str, &str, &str, &str)> = attributes.iter()
+        .map(|&(attribute_name, value)| {
+            let (prefix, local_name) = split_name(attribute_name);
+            let uri = if !prefix.is_empty() { lookup(&in_scope, prefix).unwrap_or("") } else { "" };
+
+            (uri, local_name, attribute_name, value)
+        })
+        .collect();
+    sorted_attributes.sort();
+
+    output.push('<');
+    output.push_str(name);
+
+    for (prefix, uri) in declarations {
+        if !prefix.is_empty() {
+            output.push_str(&format!(" xmlns:{}=\"{}\"", prefix, escape_attribute(uri)));
+        }
+        else {
+            output.push_str(&format!(" xmlns=\"{}\"", escape_attribute(uri)));
+        }
+    }
+
+    for (_, _, attribute_name, value) in sorted_attributes {
+        output.push_str(&format!(" {}=\"{}\"", attribute_name, escape_attribute(value)));
+    }
+
+    output.push('>');
+
+    for &child in document.children(node) {
+        write_canonical_node(document, child, options, &in_scope, &now_rendered, &[], output);
+    }
+
+    //Empty elements are always written with a closing tag
+    output.push_str(&format!("</{}>", name));
+}
+
+//Converts a node and everything below it to canonical XML. The root of a document converts the whole document,
+//with comments and processing instructions outside the outermost element on lines of their own.
+pub fn canonicalize(document: &XMLDocument, node: NodeId, options: &CanonicalOptions) -> String {
+    let mut output = String::new();
+
+    if node == document.root() {
+        let mut after_element = false;
+
+        for &child in document.children(node) {
+            let is_element = document.is_element(child);
+
+            //Whitespace outside the outermost element is not part of the canonical form
+            if document.kind(child) == NodeKind::Text || (document.kind(child) == NodeKind::Comment && !options.with_comments) {
+                continue;
+            }
+
+            if after_element {
+                output.push('\n');
+            }
+
+            write_canonical_node(document, child, options, &[], &[], &[], &mut output);
+
+            if is_element {
+                after_element = true;
+            }
+            else if !after_element {
+                output.push('\n');
+            }
+        }
+
+        return output;
+    }
+
+    //A subtree keeps the namespaces bound above it, and inclusive canonicalization also keeps inherited 'xml:' attributes
+    let mut ancestors: Vec<NodeId> = Vec::new();
+    let mut ancestor = document.parent(node);
+
+    while let Some(current) = ancestor {
+        ancestors.push(current);
+        ancestor = document.parent(current);
+    }
+
+    let mut in_scope: Vec<(&str, &str)> = Vec::new();
+    for &current in ancestors.iter().rev() {
+        for (name, value) in document.attributes(current) {
+            if let Some(prefix) = declared_prefix(name) {
+                declare(&mut in_scope, prefix, value);
+            }
+        }
+    }
+
+    let mut inherited_attributes: Vec<(&str, &str)> = Vec::new();
+    if !options.exclusive {
+        for &current in &ancestors {
+            for (name, value) in document.attributes(current) {
+                if name.starts_with("xml:") && document.attribute(node, name).is_none() && !inherited_attributes.iter().any(|&(inherited, _)| inherited == name) {
+                    inherited_attributes.push((name, value));
+                }
+            }
+        }
+    }
+
+    write_canonical_node(document, node, options, &in_scope, &[], &inherited_attributes, &mut output);
+
+    output
+}
+
+//Writes the canonical form of a node, which is always UTF-8 with '\n' line endings and no byte order mark
+pub fn write_canonical<W: Write>(document: &XMLDocument, node: NodeId, mut writer: W, options: &CanonicalOptions) -> Result<(), String> {
+    match writer.write_all(canonicalize(document, node, options).as_bytes()).and_then(|_| writer.flush()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write to file: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_keeping_whitespace;
+
+    //The canonical form of the first element below the outermost one, or of the whole document
+    fn canonical(xml: &str, subtree: bool, options: &CanonicalOptions) -> String {
+        let document = parse_keeping_whitespace(xml).unwrap();
+        let mut node = document.root();
+
+        if subtree {
+            node = document.child_elements(document.child_elements(node)[0])[0];
+        }
+
+        canonicalize(&document, node, options)
+    }
+
+    #[test]
+    fn sorts_attributes_and_writes_empty_elements_in_full() {
+        let xml = "<?page?><a b=\"2\" xmlns:z=\"urn:z\" a=\"1\" z:c=\"3\" xmlns=\"urn:d\"><e/><!--c--></a>";
+
+        assert_eq!(canonical(xml, false, &CanonicalOptions::new()), "<?page?>\n<a xmlns=\"urn:d\" xmlns:z=\"urn:z\" a=\"1\" b=\"2\" z:c=\"3\"><e></e></a>");
+
+        let mut options = CanonicalOptions::new();
+        options.with_comments = true;
+        assert_eq!(canonical("<a><!--c--></a>", false, &options), "<a><!--c--></a>");
+    }
+
+    #[test]
+    fn exclusive_subtrees_only_declare_the_namespaces_they_use() {
+        let xml = "<a xmlns:x=\"urn:x\" xmlns:y=\"urn:y\" xml:lang=\"en\"><x:b/></a>";
+
+        assert_eq!(canonical(xml, true, &CanonicalOptions::new()), "<x:b xmlns:x=\"urn:x\" xmlns:y=\"urn:y\" xml:lang=\"en\"></x:b>");
+
+        let mut options = CanonicalOptions::new();
+        options.exclusive = true;
+        assert_eq!(canonical(xml, true, &options), "<x:b xmlns:x=\"urn:x\"></x:b>");
+
+        options.inclusive_prefixes = vec!["y".to_owned()];
+        assert_eq!(canonical(xml, true, &options), "<x:b xmlns:x=\"urn:x\" xmlns:y=\"urn:y\"></x:b>");
+    }
+
+    #[test]
+    fn keeps_whitespace_and_escapes_it_in_attributes() {
+        let xml = "<?xml version=\"1.0\"?>\r\n<a x=\"p&#10;q\" y=\"r\r\n s\">\r\n  <b> t </b>\n</a>\n";
+
+        assert_eq!(canonical(xml, false, &CanonicalOptions::new()), "<a x=\"p&#xA;q\" y=\"r  s\">\n  <b> t </b>\n</a>");
+    }
+}
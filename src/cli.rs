@@ -0,0 +1,379 @@
+//
+//  Description  :    Command line interface for the XML/CSV parser
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::collections::HashMap;
+use std::time::Instant;
+use xmlparser::{xml_to_csv, xml_to_csv_streaming, xml_to_csv_mapped, csv_to_xml_with_xsd, csv_to_xml_streaming, describe_csv, xml_to_xml, xml_to_xml_lossless, xml_to_canonical, xml_query, validate_xml, validate_xml_relax_ng, validate_xml_with_dtd, infer_xsd, read_file, CSVHeader, OutputOptions};
+use xmlparser::canonical::CanonicalOptions;
+use xmlparser::csv_sniffer::{sniff_csv, SNIFF_SAMPLE_LINES};
+use xmlparser::encoding::Encoding;
+use xmlparser::tokenizer::Tokenizer;
+use xmlparser::xml_stream::TermReader;
+use xmlparser::namespaces::NamespaceMap;
+
+const USAGE: &str = "Usage:
+    xmlparser xml2csv <input.xml> <output.csv> [--stream [--record=NAME] | [--mapping=FILE] [--schema=FILE | --dtd[=FILE]]] [OUTPUT OPTIONS]
+    xmlparser csv2xml <input.csv> <output.xml> [--stream | --xsd=FILE] [--header | --no-header | --columns=NAME,NAME,...] [--encoding=NAME] [OUTPUT OPTIONS]
+    xmlparser format <input.xml> <output.xml> [--lossless | OUTPUT OPTIONS]
+    xmlparser canonical <input.xml> <output.xml> [--exclusive [--inclusive-prefixes=PREFIX,...]] [--with-comments] [--select=EXPRESSION [--namespaces=PREFIX=URI,...]]
+    xmlparser sniff <input.csv> [--lines=N] [--encoding=NAME]
+    xmlparser describe <input.csv> [--header | --no-header | --columns=NAME,NAME,...] [--encoding=NAME]
+    xmlparser query <input.xml> <xpath | css:selector> [--namespaces=PREFIX=URI,...]
+    xmlparser validate <input.xml> [--schema=FILE | --dtd[=FILE]]
+    xmlparser infer-xsd <output.xsd> <sample.xml>... [OUTPUT OPTIONS]
+    xmlparser bench <input.xml> [--iterations=N]
+
+Streaming reads one record at a time, where records are elements named by --record or
+the children of the outermost element. The columns are taken from the first record, and a later
+record with a column the first did not have is an error, as its data would be lost. So is a record
+which repeats an element, as each column holds one value.
+Streaming CSV to XML writes each row as it is read, generating column names from the first row.
+
+Formatting rewrites an XML file with the XML layout options, such as --indent=4 to re-indent it
+or --compact to minify it. --lossless keeps the file exactly as it was written instead, including
+its declaration, comments, quoting and whitespace, which checks that it is well formed.
+
+Canonical XML sorts namespace declarations and attributes and writes empty elements in full,
+for signing and comparing files. --select writes only the first node an XPath or CSS selector
+chooses, with prefixes bound by --namespaces. Exclusive canonicalization only declares the
+namespaces each element uses, and --inclusive-prefixes lists prefixes to declare anyway,
+with #default for the default namespace.
+
+A mapping file picks the records with 'records = XPATH', then names one column per line
+with 'Name = XPATH', relative to each record. Anywhere an XPath is accepted, a CSS selector
+can be given instead with a 'css:' prefix, such as 'css:CD > TITLE'.
+
+Validation checks an XML file against an XSD, a RELAX NG compact schema (a --schema file ending
+in .rnc) or a DTD, and lists every problem with its line and column.
+Without --schema, the file is checked against the DTD its <!DOCTYPE> names, or the file given by --dtd.
+Giving --schema or --dtd to xml2csv refuses to convert a file which does not match. Schemas may
+declare elements, attributes, groups, and simple and complex types, but not import or include others.
+RELAX NG schemas may use XML Schema datatypes, but not include other schemas or nest grammars.
+Entities and attribute defaults of a file's DTD are always applied when it is read, from its internal
+subset. External DTD and entity files are only read when validating, and only from the file's directory.
+
+Inferring an XSD reads sample files and writes a schema which all of them match, with the nesting
+and number of each element, whether elements and attributes are optional, and which values are
+integers, decimals, booleans, dates or date-times. Elements found in varying orders are allowed in any order.
+Describing a CSV file infers the type of each column: integer, decimal, boolean, date, datetime or
+string, with the share of its values which have the type and the number of null cells (blank or 'null').
+
+Converting CSV to XML with --xsd also writes a schema for the XML, typing each column from its values and
+allowing blank cells in the columns which had any. It cannot be combined with --stream.
+
+Names in namespaces can be matched as '{URI}local', with a prefix bound by --namespaces or by an
+'xmlns:prefix = URI' line in a mapping file, or in any namespace as '*:local' (CSS: '*|local').
+
+Output options:
+    --out-encoding=NAME     Encoding of the written file, add '-bom' to write a byte order mark (default UTF-8)
+    --line-ending=NAME      LF, CRLF or CR (default LF)
+    --namespaces=P=URI,...  Prefixes for namespaced names in expressions, column titles and CSV headers
+    --expanded-names        Title columns of namespaced elements {URI}local, or with a prefix from --namespaces
+
+XML layout options:
+    --indent=N|tabs         Indent each level by N spaces or by a tab (default 2)
+    --compact               Write each outermost element on a single line
+    --max-width=N           Put attributes on their own lines when a tag would be longer than N characters
+    --self-closing          Write empty elements as <name/>
+    --keep-blank-lines      Keep blank lines between the nodes of the input file
+
+Encodings: UTF-8, UTF-16LE, UTF-16BE, windows-1252, ISO-8859-1";
+
+//Splits command line arguments into positional arguments and '--name=value' options
+fn parse_args(args: &[String]) -> (Vec<String>, HashMap<String, String>) {
+    let mut positional: Vec<String> = Vec::new();
+    let mut options: HashMap<String, String> = HashMap::new();
+
+    for arg in args {
+        if let Some(option) = arg.strip_prefix("--") {
+            let mut parts = option.splitn(2, '=');
+            let name = parts.next().unwrap_or("").to_owned();
+            let value = parts.next().unwrap_or("").to_owned();
+            options.insert(name, value);
+        }
+        else {
+            positional.push(arg.clone());
+        }
+    }
+
+    (positional, options)
+}
+
+//Reads a numeric option, falling back to a default if it was not given
+fn numeric_option(options: &HashMap<String, String>, name: &str, default: usize) -> Result<usize, String> {
+    match options.get(name) {
+        Some(value) => match value.parse::<usize>() {
+            Ok(n) => Ok(n),
+            Err(_) => Err(format!("Expected a number for --{}, found: {}", name, value)),
+        },
+        None => Ok(default),
+    }
+}
+
+//Reads where CSV column names come from, leaving it to be guessed if no option was given
+fn header_option(options: &HashMap<String, String>) -> Option<CSVHeader> {
+    if let Some(columns) = options.get("columns") {
+        Some(CSVHeader::Supplied(columns.split(',').map(|name| name.to_owned()).collect()))
+    }
+    else if options.contains_key("no-header") {
+        Some(CSVHeader::Generated)
+    }
+    else if options.contains_key("header") {
+        Some(CSVHeader::FirstLine)
+    }
+    else {
+        None
+    }
+}
+
+//Reads a forced input encoding, leaving it to be detected if no option was given
+fn encoding_option(options: &HashMap<String, String>) -> Result<Option<Encoding>, String> {
+    match options.get("encoding") {
+        Some(name) => match Encoding::from_name(name) {
+            Some(encoding) => Ok(Some(encoding)),
+            None => Err(format!("Unknown encoding: {}", name)),
+        },
+        None => Ok(None),
+    }
+}
+
+//Reads the output encoding, line endings and XML layout, keeping the defaults for any option not given
+fn output_options(options: &HashMap<String, String>) -> Result<OutputOptions, String> {
+    let mut output_options = OutputOptions::new();
+
+    if let Some(name) = options.get("out-encoding") {
+        output_options.set_encoding(name)?;
+    }
+    if let Some(name) = options.get("line-ending") {
+        output_options.set_line_ending(name)?;
+    }
+    if let Some(name) = options.get("indent") {
+        output_options.set_indent(name)?;
+    }
+    if options.contains_key("max-width") {
+        output_options.max_line_width = Some(numeric_option(options, "max-width", 0)?);
+    }
+
+    output_options.compact = output_options.compact || options.contains_key("compact");
+    output_options.self_closing = options.contains_key("self-closing");
+    output_options.preserve_blank_lines = options.contains_key("keep-blank-lines");
+    output_options.expanded_names = options.contains_key("expanded-names");
+
+    if let Some(bindings) = options.get("namespaces") {
+        output_options.namespaces = NamespaceMap::parse(bindings)?;
+    }
+
+    Ok(output_options)
+}
+
+//Reads which form of canonical XML to write
+fn canonical_options(options: &HashMap<String, String>) -> CanonicalOptions {
+    let mut canonical_options = CanonicalOptions::new();
+
+    canonical_options.exclusive = options.contains_key("exclusive");
+    canonical_options.with_comments = options.contains_key("with-comments");
+
+    if let Some(prefixes) = options.get("inclusive-prefixes") {
+        canonical_options.inclusive_prefixes = prefixes.split(',').map(|prefix| prefix.trim().to_owned()).filter(|prefix| !prefix.is_empty()).collect();
+    }
+
+    canonical_options
+}
+
+//Prints the guessed dialect of a CSV file
+fn sniff(input_file: &str, options: &HashMap<String, String>) -> Result<(), String> {
+    let sample_lines = numeric_option(options, "lines", SNIFF_SAMPLE_LINES)?;
+    let file_contents = read_file(input_file, encoding_option(options)?)?;
+    let dialect = sniff_csv(&file_contents, sample_lines)?;
+
+    println!("{}", dialect);
+
+    Ok(())
+}
+
+//Prints the inferred type of each column of a CSV file as a table
+fn describe(input_file: &str, options: &HashMap<String, String>) -> Result<(), String> {
+    let column_types = describe_csv(input_file, header_option(options), encoding_option(options)?)?;
+
+    let width = column_types.iter().map(|column| column.name.chars().count()).max().unwrap_or(0).max("Column".len());
+
+    println!("{:<width$}  {:<8}  {:>10}  Nulls", "Column", "Type", "Confidence", width = width);
+
+    for column in &column_types {
+        let confidence = if column.nulls == column.rows { "-".to_owned() } else { format!("{:.1}%", column.confidence * 100.0) };
+        println!("{:<width$}  {:<8}  {:>10}  {} of {}", column.name, column.value_type.name(), confidence, column.nulls, column.rows, width = width);
+    }
+
+    Ok(())
+}
+
+//Checks an XML file against the XSD or RELAX NG schema given by --schema or a DTD, printing every problem found
+fn validate(input_file: &str, options: &HashMap<String, String>) -> Result<(), String> {
+    let (errors, against) = match options.get("schema") {
+        Some(schema_file) if schema_file.to_lowercase().ends_with(".rnc") => (validate_xml_relax_ng(input_file, schema_file)?, format!("the schema {}", schema_file)),
+        Some(schema_file) => (validate_xml(input_file, schema_file)?, format!("the schema {}", schema_file)),
+        None => match options.get("dtd").map(|dtd_file| dtd_file.as_str()) {
+            Some(dtd_file) if !dtd_file.is_empty() => (validate_xml_with_dtd(input_file, Some(dtd_file))?, format!("the DTD {}", dtd_file)),
+            _ => (validate_xml_with_dtd(input_file, None)?, "its DTD".to_owned()),
+        },
+    };
+
+    for error in &errors {
+        println!("{}: {}", input_file, error);
+    }
+
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(format!("{} does not match {}", input_file, against)),
+        n => Err(format!("{} does not match {} in {} places", input_file, against, n)),
+    }
+}
+
+//Times a lexer over a file held in memory, returning the number of terms it read
+fn time_lexer<F: Fn() -> Result<usize, String>>(label: &str, file_size: usize, iterations: usize, lex: F) -> Result<usize, String> {
+    let start = Instant::now();
+    let mut terms: usize = 0;
+
+    for _ in 0..iterations {
+        terms = lex()?;
+    }
+
+    let elapsed = start.elapsed();
+    let seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
+    let megabytes = (file_size * iterations) as f64 / (1024.0 * 1024.0);
+
+    println!("{}: {} terms, {:.3} ms per pass, {:.1} MB/s", label, terms, seconds * 1000.0 / iterations as f64, megabytes / seconds);
+
+    Ok(terms)
+}
+
+//Compares the throughput of the character lexer and the zero-copy tokenizer on a file
+fn bench(input_file: &str, options: &HashMap<String, String>) -> Result<(), String> {
+    let iterations = numeric_option(options, "iterations", 100)?;
+    let file_contents = read_file(input_file, None)?;
+
+    if iterations == 0 {
+        return Err("Expected at least one iteration".to_owned());
+    }
+
+    let lexer_terms = time_lexer("lexer", file_contents.len(), iterations, || {
+        let mut count: usize = 0;
+        for term in TermReader::new(file_contents.chars().map(Ok)) {
+            term?;
+            count += 1;
+        }
+        Ok(count)
+    })?;
+
+    let tokenizer_terms = time_lexer("tokenizer", file_contents.len(), iterations, || {
+        let mut count: usize = 0;
+        for token in Tokenizer::new(&file_contents) {
+            token?;
+            count += 1;
+        }
+        Ok(count)
+    })?;
+
+    if lexer_terms != tokenizer_terms {
+        return Err(format!("The lexers disagree: {} terms against {}", lexer_terms, tokenizer_terms));
+    }
+
+    Ok(())
+}
+
+//Runs a command given on the command line
+pub fn run(args: &[String]) -> Result<(), String> {
+    let (positional, options) = parse_args(args);
+
+    match positional.iter().map(|s| s.as_str()).collect::<Vec<&str>>()[..] {
+        ["xml2csv", input_file, output_file] => {
+            if options.contains_key("schema") || options.contains_key("dtd") {
+                //Validating reads the whole document, which streaming is there to avoid
+                if options.contains_key("stream") {
+                    return Err("--schema and --dtd cannot be used with --stream".to_owned());
+                }
+
+                validate(input_file, &options)?;
+            }
+
+            if let Some(mapping_file) = options.get("mapping") {
+                xml_to_csv_mapped(input_file.to_owned(), output_file.to_owned(), mapping_file.clone(), &output_options(&options)?)?;
+            }
+            else if options.contains_key("stream") {
+                let records = xml_to_csv_streaming(input_file.to_owned(), output_file.to_owned(), options.get("record").cloned(), &output_options(&options)?)?;
+                eprintln!("Converted {} records", records);
+            }
+            else {
+                xml_to_csv(input_file.to_owned(), output_file.to_owned(), &output_options(&options)?)?;
+            }
+            eprintln!("CSV File written successfully");
+        },
+        ["csv2xml", input_file, output_file] => {
+            let schema_file = options.get("xsd").cloned();
+
+            if options.contains_key("stream") {
+                //The schema is made from the whole converted document, which streaming never holds
+                if schema_file.is_some() {
+                    return Err("--xsd cannot be used with --stream".to_owned());
+                }
+
+                let records = csv_to_xml_streaming(input_file.to_owned(), output_file.to_owned(), header_option(&options), encoding_option(&options)?, &output_options(&options)?)?;
+                eprintln!("Converted {} records", records);
+            }
+            else {
+                csv_to_xml_with_xsd(input_file.to_owned(), output_file.to_owned(), schema_file.clone(), header_option(&options), encoding_option(&options)?, &output_options(&options)?)?;
+            }
+            eprintln!("XML File written successfully");
+
+            if schema_file.is_some() {
+                eprintln!("XSD File written successfully");
+            }
+        },
+        ["format", input_file, output_file] => {
+            if options.contains_key("lossless") {
+                xml_to_xml_lossless(input_file.to_owned(), output_file.to_owned())?;
+            }
+            else {
+                xml_to_xml(input_file.to_owned(), output_file.to_owned(), &output_options(&options)?)?;
+            }
+            eprintln!("XML File written successfully");
+        },
+        ["canonical", input_file, output_file] => {
+            let namespaces = match options.get("namespaces") {
+                Some(bindings) => NamespaceMap::parse(bindings)?,
+                None => NamespaceMap::new(),
+            };
+
+            xml_to_canonical(input_file.to_owned(), output_file.to_owned(), options.get("select").cloned(), &namespaces, &canonical_options(&options))?;
+            eprintln!("XML File written successfully");
+        },
+        ["sniff", input_file] => sniff(input_file, &options)?,
+        ["describe", input_file] => describe(input_file, &options)?,
+        ["query", input_file, expression] => {
+            let namespaces = match options.get("namespaces") {
+                Some(bindings) => NamespaceMap::parse(bindings)?,
+                None => NamespaceMap::new(),
+            };
+
+            for value in xml_query(input_file, expression, &namespaces)? {
+                println!("{}", value);
+            }
+        },
+        ["validate", input_file] => {
+            validate(input_file, &options)?;
+            println!("{} is valid", input_file);
+        },
+        ["bench", input_file] => bench(input_file, &options)?,
+        ["infer-xsd", output_file, ref input_files @ ..] if !input_files.is_empty() => {
+            let input_files: Vec<String> = input_files.iter().map(|input_file| input_file.to_string()).collect();
+            infer_xsd(&input_files, output_file.to_owned(), &output_options(&options)?)?;
+            eprintln!("XSD File written successfully");
+        },
+        _ => return Err(USAGE.to_owned()),
+    }
+
+    Ok(())
+}
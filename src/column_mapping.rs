@@ -0,0 +1,167 @@
+//
+//  Description  :    Mapping files which choose the records and named columns of an XML to CSV conversion
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use document::XMLDocument;
+use xpath::{XPathItem, item_string};
+use selection::Selection;
+use namespaces::{NamespaceMap, declared_prefix};
+use csv_escape;
+
+//Which nodes are records, and the column each relative expression fills, by XPath or by CSS selector with a 'css:' prefix. A mapping file looks like:
+//
+//    # Comments start with '#'
+//    records = /CATALOG/CD
+//    Title = TITLE
+//    Price = PRICE/text()
+//    Id = @id
+//    Artist = css:ARTIST
+//
+//The first line names the records, and every following line is a column in the order it should be written.
+//Lines such as 'xmlns:atom = http://www.w3.org/2005/Atom' bind a prefix for the expressions after them, and may come before the records.
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct ColumnMapping {
+    records: Selection,
+    columns: Vec<(String, Selection)>,
+}
+
+impl ColumnMapping {
+    //Reads the text of a mapping file
+    pub fn parse(text: &str) -> Result<ColumnMapping, String> {
+        ColumnMapping::parse_with_namespaces(text, &NamespaceMap::new())
+    }
+
+    //Reads the text of a mapping file, starting with the prefixes of a namespace map
+    pub fn parse_with_namespaces(text: &str, namespaces: &NamespaceMap) -> Result<ColumnMapping, String> {
+        let mut namespaces = namespaces.clone();
+        let mut records: Option<Selection> = None;
+        let mut columns: Vec<(String, Selection)> = Vec::new();
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim();
+            let expression = match parts.next() {
+                Some(expression) => expression.trim(),
+                None => return Err(format!("Expected 'name = expression' on line {} of mapping", line_index + 1)),
+            };
+
+            if let Some(prefix) = declared_prefix(name) {
+                if prefix.is_empty() || expression.is_empty() {
+                    return Err(format!("Expected 'xmlns:prefix = uri' on line {} of mapping", line_index + 1));
+                }
+
+                namespaces.insert(prefix, expression);
+                continue;
+            }
+
+            let path = match Selection::parse_with_namespaces(expression, &namespaces) {
+                Ok(path) => path,
+                Err(e) => return Err(format!("Invalid expression on line {} of mapping: {}", line_index + 1, e)),
+            };
+
+            if records.is_none() {
+                if name != "records" {
+                    return Err(format!("Expected the mapping to start with 'records = ...', found: {}", name));
+                }
+
+                records = Some(path);
+            }
+            else {
+                if name.is_empty() {
+                    return Err(format!("Missing column name on line {} of mapping", line_index + 1));
+                }
+
+                columns.push((name.to_owned(), path));
+            }
+        }
+
+        let records = match records {
+            Some(records) => records,
+            None => return Err("Mapping does not say which nodes are records".to_owned()),
+        };
+
+        if columns.is_empty() {
+            return Err("Mapping does not have any columns".to_owned());
+        }
+
+        Ok(ColumnMapping {
+            records,
+            columns,
+        })
+    }
+
+    pub fn column_names(&self) -> Vec<&str> {
+        self.columns.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    //Finds the entries of each record. A column which selects several values takes the first, and one which selects nothing is left empty.
+    pub fn rows(&self, document: &XMLDocument) -> Vec<Vec<String>> {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+
+        for record in self.records.evaluate(document, document.root()) {
+            //Only elements have anything below them to fill columns from
+            let node = match record {
+                XPathItem::Node(node) => node,
+                _ => continue,
+            };
+
+            rows.push(self.columns.iter()
+                .map(|(_, path)| match path.evaluate(document, node).first() {
+                    Some(item) => item_string(document, item),
+                    None => String::new(),
+                })
+                .collect());
+        }
+
+        rows
+    }
+}
+
+//Converts an XML tree into a CSV file string, with the records and columns chosen by a mapping
+pub fn mapped_csv_formatter(document: &XMLDocument, mapping: &ColumnMapping) -> String {
+    let mut csv_string = String::new();
+
+    //Push 'column' titles
+    let titles: Vec<String> = mapping.column_names().iter().map(|name| csv_escape(name)).collect();
+    csv_string.push_str(&titles.join(","));
+    csv_string.push('\n');
+
+    //Push row data
+    for row in mapping.rows(document) {
+        let entries: Vec<String> = row.iter().map(|entry| csv_escape(entry)).collect();
+        csv_string.push_str(&entries.join(","));
+        csv_string.push('\n');
+    }
+
+    csv_string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {lexer, parser};
+
+    #[test]
+    fn writes_a_column_for_each_mapped_expression() {
+        let document = parser(&lexer("<CATALOG><CD id=\"7\"><TITLE>Empire, Live</TITLE></CD><CD><ARTIST>Bob</ARTIST></CD></CATALOG>".to_owned()).unwrap()).unwrap();
+        let mapping = ColumnMapping::parse("# CDs\nrecords = /CATALOG/CD\nTitle = TITLE\nId = @id\nArtist = css:ARTIST\n").unwrap();
+
+        assert_eq!(mapped_csv_formatter(&document, &mapping), "Title,Id,Artist\n\"Empire, Live\",7,\n,,Bob\n");
+    }
+
+    #[test]
+    fn mappings_must_start_with_records_and_have_columns() {
+        assert!(ColumnMapping::parse("Title = TITLE\n").is_err());
+        assert!(ColumnMapping::parse("records = //CD\n").is_err());
+        assert!(ColumnMapping::parse("records = //CD\nTitle TITLE\n").is_err());
+    }
+}
@@ -0,0 +1,653 @@
+//
+//  Description  :    CSS selectors for selecting elements of a parsed XML tree
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use document::{XMLDocument, NodeId};
+use namespaces::{NamespaceMap, NameTest};
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+enum AttributeOperator {
+    Equals,
+    //'~=', one of the space separated words
+    Includes,
+    //'|=', the value or the value followed by '-'
+    DashMatch,
+    Prefix,
+    Suffix,
+    Substring,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+struct AttributeTest {
+    name: NameTest,
+    operator: Option<(AttributeOperator, String)>,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum Pseudo {
+    //The position 'a'n+'b' among siblings, counting from the first or the last
+    NthChild(i64, i64),
+    NthLastChild(i64, i64),
+    NthOfType(i64, i64),
+    NthLastOfType(i64, i64),
+    OnlyChild,
+    OnlyOfType,
+    Empty,
+    Not(Box<Compound>),
+    Scope,
+}
+
+//A run of tests on one element, such as 'game[platform=PC]:first-child'
+#[derive(Debug)]
+#[derive(Clone)]
+struct Compound {
+    name: Option<NameTest>,
+    attributes: Vec<AttributeTest>,
+    pseudos: Vec<Pseudo>,
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+enum Combinator {
+    Descendant,
+    Child,
+    Adjacent,
+    Sibling,
+}
+
+//Compounds joined by combinators, such as 'CATALOG > CD TITLE'
+#[derive(Debug)]
+#[derive(Clone)]
+struct Complex {
+    compounds: Vec<Compound>,
+    combinators: Vec<Combinator>,
+}
+
+//A compiled list of CSS selectors
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct CSSSelector {
+    selectors: Vec<Complex>,
+}
+
+//Reads a selector one character at a time
+struct SelectorReader<'a> {
+    selector: &'a str,
+    position: usize,
+    namespaces: &'a NamespaceMap,
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.' || c == ':'
+}
+
+impl<'a> SelectorReader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.selector[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    //Skips whitespace, returning whether there was any
+    fn skip_whitespace(&mut self) -> bool {
+        let rest = self.rest();
+        let skipped = rest.len() - rest.trim_start().len();
+        self.position += skipped;
+        skipped > 0
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn error(&self, expected: &str) -> String {
+        format!("Expected {} at position {} of {}", expected, self.position, self.selector)
+    }
+
+    //Reads a name. Names inside a compound stop at ':' and '.', which start other tests.
+    fn name(&mut self, allow_separators: bool) -> Result<String, String> {
+        let rest = self.rest();
+        let end = rest.find(|c: char| !is_name_char(c) || (!allow_separators && (c == ':' || c == '.'))).unwrap_or(rest.len());
+
+        if end == 0 {
+            return Err(self.error("a name"));
+        }
+
+        self.position += end;
+        Ok(rest[..end].to_owned())
+    }
+
+    //Checks for the '|' between a namespace prefix and a name, which is not the start of a '|=' operator
+    fn eat_namespace_separator(&mut self) -> bool {
+        !self.rest().starts_with("|=") && self.eat("|")
+    }
+
+    //Reads an element or attribute name with an optional namespace, written 'prefix|name', '*|name' for any namespace,
+    //'|name' for no namespace or '{uri}name'. A name of '*' matches any element, so gives no test.
+    fn qualified_name(&mut self, allow_separators: bool) -> Result<Option<NameTest>, String> {
+        let uri = if self.eat("{") {
+            let end = match self.rest().find('}') {
+                Some(end) => end,
+                None => return Err(self.error("'}'")),
+            };
+
+            let uri = self.rest()[..end].to_owned();
+            self.position += end + 1;
+            Some(uri)
+        }
+        else if self.eat("*") {
+            if !self.eat_namespace_separator() {
+                return Ok(None);
+            }
+            if self.eat("*") {
+                return Ok(None);
+            }
+
+            return Ok(Some(NameTest::LocalName(self.name(allow_separators)?)));
+        }
+        else if self.eat_namespace_separator() {
+            Some(String::new())
+        }
+        else {
+            let name = self.name(allow_separators)?;
+
+            if !self.eat_namespace_separator() {
+                return Ok(Some(NameTest::Written(name)));
+            }
+
+            match self.namespaces.get(&name) {
+                Some(uri) => Some(uri.to_owned()),
+                None => return Err(format!("Unknown namespace prefix {} in {}", name, self.selector)),
+            }
+        };
+
+        let uri = uri.unwrap_or_default();
+
+        if self.eat("*") {
+            Ok(Some(NameTest::AnyInNamespace(uri)))
+        }
+        else {
+            Ok(Some(NameTest::Expanded(uri, self.name(allow_separators)?)))
+        }
+    }
+
+    //Reads a quoted string, or a bare word up to the closing bracket
+    fn value(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+
+        let rest = self.rest();
+        match rest.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => {
+                match rest[1..].find(quote) {
+                    Some(end) => {
+                        self.position += end + 2;
+                        Ok(rest[1..end + 1].to_owned())
+                    },
+                    None => Err(format!("Unterminated string in {}", self.selector)),
+                }
+            },
+            _ => {
+                let end = rest.find(|c: char| c == ']' || c.is_whitespace()).unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(self.error("a value"));
+                }
+                self.position += end;
+                Ok(rest[..end].to_owned())
+            },
+        }
+    }
+
+    fn attribute(&mut self) -> Result<AttributeTest, String> {
+        self.skip_whitespace();
+        let name = match self.qualified_name(true)? {
+            Some(name) => name,
+            None => return Err(self.error("an attribute name")),
+        };
+        self.skip_whitespace();
+
+        let operator = if self.eat("=") {
+            Some(AttributeOperator::Equals)
+        }
+        else {
+            let operators = [("~=", AttributeOperator::Includes), ("|=", AttributeOperator::DashMatch), ("^=", AttributeOperator::Prefix),
+                ("$=", AttributeOperator::Suffix), ("*=", AttributeOperator::Substring)];
+            operators.iter().find(|&&(token, _)| self.rest().starts_with(token)).map(|&(token, operator)| {
+                self.position += token.len();
+                operator
+            })
+        };
+
+        let operator = match operator {
+            Some(operator) => Some((operator, self.value()?)),
+            None => None,
+        };
+
+        self.skip_whitespace();
+        if !self.eat("]") {
+            return Err(self.error("']'"));
+        }
+
+        Ok(AttributeTest {
+            name,
+            operator,
+        })
+    }
+
+    //Reads the argument of ':nth-child(...)', such as 'odd', '3' or '2n+1'
+    fn nth(&mut self) -> Result<(i64, i64), String> {
+        let end = match self.rest().find(')') {
+            Some(end) => end,
+            None => return Err(self.error("')'")),
+        };
+
+        let argument: String = self.rest()[..end].chars().filter(|c| !c.is_whitespace()).collect();
+        self.position += end + 1;
+
+        let invalid = || format!("Invalid position '{}' in {}", argument, self.selector);
+
+        match argument.as_str() {
+            "odd" => return Ok((2, 1)),
+            "even" => return Ok((2, 0)),
+            _ => {},
+        }
+
+        match argument.find('n') {
+            Some(n) => {
+                let a = match &argument[..n] {
+                    "" | "+" => 1,
+                    "-" => -1,
+                    a => a.parse::<i64>().map_err(|_| invalid())?,
+                };
+                let b = match &argument[n + 1..] {
+                    "" => 0,
+                    b => b.trim_start_matches('+').parse::<i64>().map_err(|_| invalid())?,
+                };
+                Ok((a, b))
+            },
+            None => Ok((0, argument.parse::<i64>().map_err(|_| invalid())?)),
+        }
+    }
+
+    fn pseudo(&mut self) -> Result<Pseudo, String> {
+        let name = self.name(false)?;
+
+        Ok(match name.as_str() {
+            "first-child" => Pseudo::NthChild(0, 1),
+            "last-child" => Pseudo::NthLastChild(0, 1),
+            "first-of-type" => Pseudo::NthOfType(0, 1),
+            "last-of-type" => Pseudo::NthLastOfType(0, 1),
+            "only-child" => Pseudo::OnlyChild,
+            "only-of-type" => Pseudo::OnlyOfType,
+            "empty" => Pseudo::Empty,
+            "scope" => Pseudo::Scope,
+            "nth-child" | "nth-last-child" | "nth-of-type" | "nth-last-of-type" => {
+                if !self.eat("(") {
+                    return Err(self.error("'('"));
+                }
+                let (a, b) = self.nth()?;
+                match name.as_str() {
+                    "nth-child" => Pseudo::NthChild(a, b),
+                    "nth-last-child" => Pseudo::NthLastChild(a, b),
+                    "nth-of-type" => Pseudo::NthOfType(a, b),
+                    _ => Pseudo::NthLastOfType(a, b),
+                }
+            },
+            "not" => {
+                if !self.eat("(") {
+                    return Err(self.error("'('"));
+                }
+                self.skip_whitespace();
+                let compound = self.compound()?;
+                self.skip_whitespace();
+                if !self.eat(")") {
+                    return Err(self.error("')'"));
+                }
+                Pseudo::Not(Box::new(compound))
+            },
+            _ => return Err(format!("Unsupported pseudo-class :{} in {}", name, self.selector)),
+        })
+    }
+
+    fn compound(&mut self) -> Result<Compound, String> {
+        let mut compound = Compound {
+            name: None,
+            attributes: Vec::new(),
+            pseudos: Vec::new(),
+        };
+
+        let starts_name = match self.peek() {
+            Some(c) => c == '*' || c == '|' || c == '{' || (is_name_char(c) && c != ':' && c != '.'),
+            None => false,
+        };
+
+        if starts_name {
+            compound.name = self.qualified_name(false)?;
+        }
+
+        loop {
+            if self.eat("[") {
+                compound.attributes.push(self.attribute()?);
+            }
+            else if self.eat("#") {
+                let id = self.name(false)?;
+                compound.attributes.push(AttributeTest { name: NameTest::Written("id".to_owned()), operator: Some((AttributeOperator::Equals, id)) });
+            }
+            else if self.eat(".") {
+                let class = self.name(false)?;
+                compound.attributes.push(AttributeTest { name: NameTest::Written("class".to_owned()), operator: Some((AttributeOperator::Includes, class)) });
+            }
+            else if self.eat(":") {
+                compound.pseudos.push(self.pseudo()?);
+            }
+            else {
+                break;
+            }
+        }
+
+        Ok(compound)
+    }
+
+    fn combinator(&mut self) -> Option<Combinator> {
+        let whitespace = self.skip_whitespace();
+
+        let combinator = if self.eat(">") {
+            Combinator::Child
+        }
+        else if self.eat("+") {
+            Combinator::Adjacent
+        }
+        else if self.eat("~") {
+            Combinator::Sibling
+        }
+        else if whitespace && self.peek().is_some_and(|c| c != ',') {
+            return Some(Combinator::Descendant);
+        }
+        else {
+            return None;
+        };
+
+        self.skip_whitespace();
+        Some(combinator)
+    }
+
+    fn complex(&mut self) -> Result<Complex, String> {
+        let mut complex = Complex {
+            compounds: Vec::new(),
+            combinators: Vec::new(),
+        };
+
+        self.skip_whitespace();
+
+        //A selector starting with a combinator, such as '> TITLE', is relative to the node being searched
+        match self.peek() {
+            Some('>') | Some('+') | Some('~') => {
+                complex.compounds.push(Compound { name: None, attributes: Vec::new(), pseudos: vec![Pseudo::Scope] });
+                complex.combinators.push(self.combinator().unwrap());
+            },
+            _ => {},
+        }
+
+        loop {
+            let start = self.position;
+            let compound = self.compound()?;
+            if self.position == start {
+                return Err(self.error("a selector"));
+            }
+            complex.compounds.push(compound);
+
+            match self.combinator() {
+                Some(combinator) => complex.combinators.push(combinator),
+                None => break,
+            }
+        }
+
+        Ok(complex)
+    }
+}
+
+//Checks whether the 1-based 'position' is a*n+b for some n of at least 0
+fn nth_matches(a: i64, b: i64, position: i64) -> bool {
+    if a == 0 {
+        position == b
+    }
+    else {
+        (position - b) % a == 0 && (position - b) / a >= 0
+    }
+}
+
+fn attribute_matches(document: &XMLDocument, node: NodeId, test: &AttributeTest) -> bool {
+    //Element-only documents keep their fields as children, so '[platform=PC]' also looks at child text
+    let mut values: Vec<String> = document.attributes(node).iter()
+        .filter(|&&(name, _)| test.name.matches_attribute(document, node, name))
+        .map(|&(_, value)| value.to_owned())
+        .collect();
+
+    if values.is_empty() {
+        values = document.children(node).iter()
+            .filter(|&&child| test.name.matches(document, child))
+            .map(|&child| document.text(child).trim().to_owned())
+            .collect();
+    }
+
+    let (operator, expected) = match test.operator {
+        Some((operator, ref expected)) => (operator, expected),
+        None => return !values.is_empty(),
+    };
+
+    values.iter().any(|value| match operator {
+        AttributeOperator::Equals => value == expected,
+        AttributeOperator::Includes => value.split_whitespace().any(|word| word == expected),
+        AttributeOperator::DashMatch => value == expected || value.starts_with(&format!("{}-", expected)),
+        AttributeOperator::Prefix => !expected.is_empty() && value.starts_with(expected.as_str()),
+        AttributeOperator::Suffix => !expected.is_empty() && value.ends_with(expected.as_str()),
+        AttributeOperator::Substring => !expected.is_empty() && value.contains(expected.as_str()),
+    })
+}
+
+fn pseudo_matches(document: &XMLDocument, node: NodeId, pseudo: &Pseudo, scope: NodeId) -> bool {
+    let siblings: Vec<NodeId> = match document.parent(node) {
+        Some(parent) => document.child_elements(parent),
+        None => vec![node],
+    };
+    //Elements are of the same type if they have the same namespace and local name
+    let of_type: Vec<NodeId> = siblings.iter().cloned()
+        .filter(|&sibling| document.namespace(sibling) == document.namespace(node) && document.local_name(sibling) == document.local_name(node))
+        .collect();
+
+    let position = |nodes: &Vec<NodeId>| nodes.iter().position(|&n| n == node).unwrap_or(0) as i64 + 1;
+    let last_position = |nodes: &Vec<NodeId>| nodes.len() as i64 - position(nodes) + 1;
+
+    match *pseudo {
+        Pseudo::NthChild(a, b) => nth_matches(a, b, position(&siblings)),
+        Pseudo::NthLastChild(a, b) => nth_matches(a, b, last_position(&siblings)),
+        Pseudo::NthOfType(a, b) => nth_matches(a, b, position(&of_type)),
+        Pseudo::NthLastOfType(a, b) => nth_matches(a, b, last_position(&of_type)),
+        Pseudo::OnlyChild => siblings.len() == 1,
+        Pseudo::OnlyOfType => of_type.len() == 1,
+        Pseudo::Empty => document.child_elements(node).is_empty() && document.text(node).is_empty(),
+        Pseudo::Not(ref compound) => !compound_matches(document, node, compound, scope),
+        Pseudo::Scope => node == scope,
+    }
+}
+
+fn compound_matches(document: &XMLDocument, node: NodeId, compound: &Compound, scope: NodeId) -> bool {
+    //The document itself is not an element, so can only be the scope being searched
+    if node == document.root() {
+        return compound.pseudos.iter().any(|pseudo| matches!(*pseudo, Pseudo::Scope)) && node == scope;
+    }
+
+    if !document.is_element(node) {
+        return false;
+    }
+
+    if let Some(ref name) = compound.name {
+        if !name.matches(document, node) {
+            return false;
+        }
+    }
+
+    compound.attributes.iter().all(|test| attribute_matches(document, node, test))
+        && compound.pseudos.iter().all(|pseudo| pseudo_matches(document, node, pseudo, scope))
+}
+
+//Finds the closest element before 'node' with the same parent, passing over comments
+fn previous_element(document: &XMLDocument, node: NodeId) -> Option<NodeId> {
+    let mut sibling = document.previous_sibling(node);
+
+    while let Some(current) = sibling {
+        if document.is_element(current) {
+            return Some(current);
+        }
+        sibling = document.previous_sibling(current);
+    }
+
+    None
+}
+
+//Matches the compounds of a selector right to left, from 'index' down to the first
+fn complex_matches(document: &XMLDocument, node: NodeId, complex: &Complex, index: usize, scope: NodeId) -> bool {
+    if !compound_matches(document, node, &complex.compounds[index], scope) {
+        return false;
+    }
+
+    if index == 0 {
+        return true;
+    }
+
+    match complex.combinators[index - 1] {
+        Combinator::Child => match document.parent(node) {
+            Some(parent) => complex_matches(document, parent, complex, index - 1, scope),
+            None => false,
+        },
+        Combinator::Descendant => {
+            let mut ancestor = document.parent(node);
+            while let Some(current) = ancestor {
+                if complex_matches(document, current, complex, index - 1, scope) {
+                    return true;
+                }
+                ancestor = document.parent(current);
+            }
+            false
+        },
+        Combinator::Adjacent => match previous_element(document, node) {
+            Some(sibling) => complex_matches(document, sibling, complex, index - 1, scope),
+            None => false,
+        },
+        Combinator::Sibling => {
+            let mut sibling = previous_element(document, node);
+            while let Some(current) = sibling {
+                if complex_matches(document, current, complex, index - 1, scope) {
+                    return true;
+                }
+                sibling = previous_element(document, current);
+            }
+            false
+        },
+    }
+}
+
+impl CSSSelector {
+    //Compiles a list of selectors such as 'CD > TITLE', 'game[platform=PC]' or 'CD:nth-child(odd)'
+    pub fn parse(selector: &str) -> Result<CSSSelector, String> {
+        CSSSelector::parse_with_namespaces(selector, &NamespaceMap::new())
+    }
+
+    //Compiles selectors whose names may use the prefixes of a namespace map, such as 'atom|entry > atom|title'
+    pub fn parse_with_namespaces(selector: &str, namespaces: &NamespaceMap) -> Result<CSSSelector, String> {
+        let mut reader = SelectorReader {
+            selector,
+            position: 0,
+            namespaces,
+        };
+
+        let mut selectors: Vec<Complex> = Vec::new();
+
+        loop {
+            selectors.push(reader.complex()?);
+            reader.skip_whitespace();
+
+            if !reader.eat(",") {
+                break;
+            }
+        }
+
+        if !reader.rest().is_empty() {
+            return Err(format!("Unexpected '{}' in {}", reader.rest(), selector));
+        }
+
+        Ok(CSSSelector {
+            selectors,
+        })
+    }
+
+    pub fn matches(&self, document: &XMLDocument, node: NodeId, scope: NodeId) -> bool {
+        self.selectors.iter().any(|complex| complex_matches(document, node, complex, complex.compounds.len() - 1, scope))
+    }
+
+    //Finds the elements below 'scope' which match, in document order
+    pub fn select(&self, document: &XMLDocument, scope: NodeId) -> Vec<NodeId> {
+        document.descendants(scope).filter(|&node| self.matches(document, node, scope)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {lexer, parser};
+
+    const GAMES: &str = "<games><game platform=\"PC\"><title>Doom</title><tags>fps retro</tags></game><game platform=\"PC-98\"><title>Rusty</title></game><game platform=\"SNES\"><title>Zelda</title><extra/></game></games>";
+
+    //The text of every element the selector finds in the games list
+    fn select(selector: &str) -> Result<Vec<String>, String> {
+        let document = parser(&lexer(GAMES.to_owned()).unwrap()).unwrap();
+        let selector = CSSSelector::parse(selector)?;
+
+        Ok(selector.select(&document, document.root()).into_iter().map(|node| document.text(node)).collect())
+    }
+
+    #[test]
+    fn selects_by_combinators() {
+        assert_eq!(select("games > game > title").unwrap(), vec!["Doom", "Rusty", "Zelda"]);
+        assert_eq!(select("games title").unwrap(), vec!["Doom", "Rusty", "Zelda"]);
+        assert_eq!(select("title + tags").unwrap(), vec!["fps retro"]);
+        assert_eq!(select("tags, extra").unwrap(), vec!["fps retro", ""]);
+    }
+
+    #[test]
+    fn selects_by_attributes() {
+        assert_eq!(select("game[platform=PC] title").unwrap(), vec!["Doom"]);
+        assert_eq!(select("game[platform|=PC] title").unwrap(), vec!["Doom", "Rusty"]);
+        assert_eq!(select("game[platform^=\"S\"] title").unwrap(), vec!["Zelda"]);
+        assert_eq!(select("game[nope]").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn selects_by_pseudo_classes() {
+        assert_eq!(select("game:nth-child(2) > title").unwrap(), vec!["Rusty"]);
+        assert_eq!(select("game:nth-child(odd) > title").unwrap(), vec!["Doom", "Zelda"]);
+        assert_eq!(select("game:last-child > title").unwrap(), vec!["Zelda"]);
+        assert_eq!(select("game > :only-child").unwrap(), vec!["Rusty"]);
+        assert_eq!(select("game > :not(title)").unwrap(), vec!["fps retro", ""]);
+        assert_eq!(select(":empty").unwrap(), vec![""]);
+    }
+
+    #[test]
+    fn malformed_selectors_are_an_error() {
+        assert!(select("game[platform").is_err());
+        assert!(select("game:unknown").is_err());
+        assert!(select("game >").is_err());
+    }
+}
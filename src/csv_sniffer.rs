@@ -0,0 +1,319 @@
+//
+//  Description  :    Guesses the dialect (delimiter, quoting, header, line endings) of a CSV file
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::fmt;
+use csv_lexer;
+
+//The number of lines sampled when guessing a dialect
+pub const SNIFF_SAMPLE_LINES: usize = 20;
+
+const CANDIDATE_DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+const CANDIDATE_QUOTES: [char; 2] = ['"', '\''];
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum LineEnding {
+    LF,
+    CRLF,
+    CR,
+}
+
+impl LineEnding {
+    //Finds a line ending from its name, such as 'CRLF'
+    pub fn from_name(name: &str) -> Option<LineEnding> {
+        match name.trim().to_uppercase().as_str() {
+            "LF" => Some(LineEnding::LF),
+            "CRLF" => Some(LineEnding::CRLF),
+            "CR" => Some(LineEnding::CR),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LineEnding::LF => "\n",
+            LineEnding::CRLF => "\r\n",
+            LineEnding::CR => "\r",
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct CSVDialect {
+    pub delimiter: char,
+    pub quote: Option<char>,
+    pub has_header: bool,
+    pub line_ending: LineEnding,
+}
+
+impl CSVDialect {
+    pub fn new() -> CSVDialect {
+        CSVDialect {
+            delimiter: ',',
+            quote: Some('"'),
+            has_header: true,
+            line_ending: LineEnding::LF,
+        }
+    }
+}
+
+impl Default for CSVDialect {
+    fn default() -> CSVDialect {
+        CSVDialect::new()
+    }
+}
+
+impl fmt::Display for CSVDialect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let delimiter = match self.delimiter {
+            ',' => "comma".to_owned(),
+            ';' => "semicolon".to_owned(),
+            '\t' => "tab".to_owned(),
+            '|' => "pipe".to_owned(),
+            c => format!("'{}'", c),
+        };
+
+        let quote = match self.quote {
+            Some(c) => format!("{}", c),
+            None => "none".to_owned(),
+        };
+
+        write!(f, "delimiter: {}, quote: {}, header: {}, line endings: {:?}",
+            delimiter, quote, if self.has_header { "yes" } else { "no" }, self.line_ending)
+    }
+}
+
+//Finds the text of the first 'sample_lines' lines of a file
+fn sample_text(file_contents: &str, sample_lines: usize) -> &str {
+    let mut lines: usize = 0;
+
+    for (index, c) in file_contents.char_indices() {
+        if c == '\n' {
+            lines += 1;
+
+            if lines >= sample_lines {
+                return &file_contents[..index + 1];
+            }
+        }
+    }
+
+    file_contents
+}
+
+//Finds the most common line ending in a sample
+fn sniff_line_ending(sample: &str) -> LineEnding {
+    let crlf = sample.matches("\r\n").count();
+    let lf = sample.matches('\n').count() - crlf;
+    let cr = sample.matches('\r').count() - crlf;
+
+    if crlf > 0 && crlf >= lf && crlf >= cr {
+        LineEnding::CRLF
+    }
+    else if cr > lf {
+        LineEnding::CR
+    }
+    else {
+        LineEnding::LF
+    }
+}
+
+//Finds the quote character which most often opens an entry in a sample
+fn sniff_quote(sample: &str) -> Option<char> {
+    let mut best: Option<(char, usize)> = None;
+
+    for quote in CANDIDATE_QUOTES.iter() {
+        let mut count: usize = 0;
+        let mut previous_char = '\n';
+
+        for c in sample.chars() {
+            //Only count quotes which start an entry
+            if c == *quote && (previous_char == '\n' || previous_char == '\r' || CANDIDATE_DELIMITERS.contains(&previous_char)) {
+                count += 1;
+            }
+
+            previous_char = c;
+        }
+
+        if count > best.map_or(0, |(_, n)| n) {
+            best = Some((*quote, count));
+        }
+    }
+
+    best.map(|(quote, _)| quote)
+}
+
+//Checks whether an entry looks like a number
+fn is_numeric(entry: &str) -> bool {
+    entry.trim().parse::<f64>().is_ok()
+}
+
+//Guesses whether the first record is a row of column titles
+fn sniff_header(records: &[Vec<String>]) -> bool {
+    if records.len() < 2 {
+        return true;
+    }
+
+    let header = &records[0];
+    let mut votes: i32 = 0;
+
+    for (column, title) in header.iter().enumerate() {
+        let title = title.trim();
+        let values: Vec<&str> = records[1..].iter()
+            .filter_map(|record| record.get(column))
+            .map(|entry| entry.trim())
+            .filter(|entry| !entry.is_empty())
+            .collect();
+
+        if values.is_empty() {
+            continue;
+        }
+
+        if values.iter().all(|entry| is_numeric(entry)) {
+            //A text title above a column of numbers is a strong hint
+            votes += if is_numeric(title) { -1 } else { 1 };
+        }
+        else if values.contains(&title) {
+            //Titles rarely repeat in their own column
+            votes -= 1;
+        }
+        else if values.iter().all(|entry| entry.len() == values[0].len()) && title.len() != values[0].len() {
+            //Fixed width columns with a differently sized first entry
+            votes += 1;
+        }
+    }
+
+    //Column titles are expected to be unique and non-empty
+    for (index, title) in header.iter().enumerate() {
+        if title.trim().is_empty() || header[..index].contains(title) {
+            votes -= 1;
+        }
+    }
+
+    votes >= 0
+}
+
+//Guesses the dialect of a CSV file from its first 'sample_lines' lines
+pub fn sniff_csv(file_contents: &str, sample_lines: usize) -> Result<CSVDialect, String> {
+    let sample = sample_text(file_contents, sample_lines);
+
+    if sample.trim().is_empty() {
+        return Err("No entries in CSV file".to_owned());
+    }
+
+    let mut dialect = CSVDialect::new();
+    dialect.line_ending = sniff_line_ending(sample);
+
+    //A sample without quotes keeps the usual '"', as later lines may still quote entries
+    if let Some(quote) = sniff_quote(sample) {
+        dialect.quote = Some(quote);
+    }
+
+    //Pick the delimiter which splits the sample into the most consistent number of entries
+    let mut best_score: (f64, usize) = (0.0, 1);
+    let mut best_records: Option<Vec<Vec<String>>> = None;
+
+    for delimiter in CANDIDATE_DELIMITERS.iter() {
+        let mut candidate = dialect.clone();
+        candidate.delimiter = *delimiter;
+
+        let records = match csv_lexer(sample, &candidate, Some(sample_lines)) {
+            Ok(records) => records,
+            Err(_) => continue,
+        };
+
+        if records.is_empty() {
+            continue;
+        }
+
+        //Find the most common number of entries per record
+        let mut counts: Vec<(usize, usize)> = Vec::new();
+        for record in &records {
+            match counts.iter_mut().find(|&&mut (width, _)| width == record.len()) {
+                Some(count) => count.1 += 1,
+                None => counts.push((record.len(), 1)),
+            }
+        }
+
+        let mut width: usize = 0;
+        let mut frequency: usize = 0;
+        for &(count_width, count_frequency) in &counts {
+            if count_frequency > frequency || (count_frequency == frequency && count_width > width) {
+                width = count_width;
+                frequency = count_frequency;
+            }
+        }
+
+        let consistency = frequency as f64 / records.len() as f64;
+
+        if width > 1 && (consistency > best_score.0 || (consistency == best_score.0 && width > best_score.1)) {
+            best_score = (consistency, width);
+            dialect.delimiter = *delimiter;
+            best_records = Some(records);
+        }
+    }
+
+    //Files with a single column keep the default delimiter
+    let records = match best_records {
+        Some(records) => records,
+        None => csv_lexer(sample, &dialect, Some(sample_lines))?,
+    };
+
+    dialect.has_header = sniff_header(&records);
+
+    Ok(dialect)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_semicolons_and_crlf_line_endings() {
+        let dialect = sniff_csv("name;year;score\r\nHalo;2001;9.5\r\nMyst;1993;8.0\r\n", SNIFF_SAMPLE_LINES).unwrap();
+
+        assert_eq!(dialect.delimiter, ';');
+        assert_eq!(dialect.line_ending, LineEnding::CRLF);
+        assert!(dialect.has_header);
+    }
+
+    #[test]
+    fn sniffs_single_quotes_around_delimiters() {
+        let dialect = sniff_csv("'a|b'|c\n'd|e'|f\n'g|h'|i\n", SNIFF_SAMPLE_LINES).unwrap();
+
+        assert_eq!(dialect.delimiter, '|');
+        assert_eq!(dialect.quote, Some('\''));
+    }
+
+    #[test]
+    fn numbers_in_the_first_line_are_not_a_header() {
+        let dialect = sniff_csv("1\t2\t3\n4\t5\t6\n7\t8\t9\n", SNIFF_SAMPLE_LINES).unwrap();
+
+        assert_eq!(dialect.delimiter, '\t');
+        assert!(!dialect.has_header);
+    }
+
+    #[test]
+    fn quotes_after_the_sample_are_still_read() {
+        let mut file_contents = "name,score\n".to_owned();
+        for index in 0..SNIFF_SAMPLE_LINES {
+            file_contents.push_str(&format!("player{},{}\n", index, index));
+        }
+        file_contents.push_str("\"Smith, John\",7\n");
+
+        let dialect = sniff_csv(&file_contents, SNIFF_SAMPLE_LINES).unwrap();
+        let records = csv_lexer(&file_contents, &dialect, None).unwrap();
+
+        assert_eq!(dialect.quote, Some('"'));
+        assert_eq!(records.last().unwrap(), &vec!["Smith, John".to_owned(), "7".to_owned()]);
+    }
+
+    #[test]
+    fn blank_files_have_no_dialect() {
+        assert!(sniff_csv(" \n\n", SNIFF_SAMPLE_LINES).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//
+//  Description  :    Streaming conversion of CSV files to XML, one record at a time
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::io::Read;
+use std::io::Write;
+use std::io::BufReader;
+use csv_sniffer::{sniff_csv, SNIFF_SAMPLE_LINES};
+use encoding::{Encoding, CharReader};
+use xml_writer::{element_start, element_end};
+use namespaces::qualify_names;
+use {CSVHeader, CSVRecordReader, EncodedWriter, OutputOptions, xml_declaration};
+
+//Writes one record as an 'element' with a child for each column
+fn write_element<W: Write>(writer: &mut EncodedWriter<W>, keymap: &[String], row: &[String], row_index: usize) -> Result<(), String> {
+    let mut element = element_start("element", &[], "", 1, !keymap.is_empty(), writer.options());
+
+    for key_index in 0..keymap.len() {
+        if row.len() <= key_index {
+            return Err(format!("Expected key {} for row {}", key_index, row_index));
+        }
+
+        element.push_str(&element_start(&keymap[key_index], &[], &row[key_index], 2, false, writer.options()));
+    }
+
+    if !keymap.is_empty() {
+        element.push_str(&element_end("element", 1, writer.options()));
+    }
+
+    writer.write_xml(&element)
+}
+
+//Converts CSV records read from 'reader' into XML elements written straight to 'writer', holding one record at a time.
+//Generated column names are taken from the width of the first record, as later rows are not known yet.
+pub fn csv_to_xml_stream<R: Read, W: Write>(reader: R, writer: W, header: Option<CSVHeader>, encoding: Option<Encoding>, options: &OutputOptions) -> Result<usize, String> {
+    let reader = BufReader::new(reader);
+    let mut chars = match encoding {
+        Some(encoding) => CharReader::new(reader, encoding),
+        None => CharReader::with_detected_encoding(reader)?,
+    };
+
+    //Guess the dialect from the first lines, which are then read again ahead of the rest of the file
+    let mut sample = String::new();
+    let mut sample_lines: usize = 0;
+
+    while sample_lines < SNIFF_SAMPLE_LINES {
+        match chars.next() {
+            Some(c) => {
+                let c = c?;
+                sample.push(c);
+
+                if c == '\n' {
+                    sample_lines += 1;
+                }
+            },
+            None => break,
+        }
+    }
+
+    let dialect = match sniff_csv(&sample, SNIFF_SAMPLE_LINES) {
+        Ok(dialect) => dialect,
+        Err(e) => return Err(format!("Could not parse CSV file: {}", e)),
+    };
+
+    let header = match header {
+        Some(header) => header,
+        None if dialect.has_header => CSVHeader::FirstLine,
+        None => CSVHeader::Generated,
+    };
+
+    let mut records = CSVRecordReader::new(sample.chars().map(Ok).chain(chars), &dialect);
+
+    let mut keymap: Vec<String> = match header {
+        CSVHeader::FirstLine => match records.next() {
+            Some(record) => record?,
+            None => return Err("No entries in CSV file".to_owned()),
+        },
+        CSVHeader::Generated => Vec::new(),
+        CSVHeader::Supplied(names) => names.iter().map(|name| name.trim().to_owned()).collect(),
+    };
+
+    //Columns titled '{uri}local' or 'prefix:local' become elements in that namespace, declared on the outermost element
+    let (qualified_names, declarations) = qualify_names(&keymap, &options.namespaces);
+    keymap = qualified_names;
+
+    let declarations: Vec<(&str, &str)> = declarations.iter().map(|(name, uri)| (name.as_str(), uri.as_str())).collect();
+
+    let mut writer = EncodedWriter::new(writer, options)?;
+    writer.write_xml(&xml_declaration(options))?;
+    writer.write_xml(&element_start("root2", &declarations, "", 0, true, options))?;
+
+    let mut rows: usize = 0;
+
+    for record in records {
+        let row = record?;
+
+        //Name the columns 'col1', 'col2', ... from the first record
+        if rows == 0 && keymap.is_empty() {
+            keymap = (0..row.len()).map(|key_index| format!("col{}", key_index + 1)).collect();
+        }
+
+        write_element(&mut writer, &keymap, &row, rows)?;
+        rows += 1;
+    }
+
+    if rows == 0 {
+        return Err("No entries in CSV file".to_owned());
+    }
+
+    writer.write_xml(&element_end("root2", 0, options))?;
+    writer.flush()?;
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_to_xml(csv: &str, header: Option<CSVHeader>) -> Result<String, String> {
+        let mut output: Vec<u8> = Vec::new();
+        csv_to_xml_stream(csv.as_bytes(), &mut output, header, None, &OutputOptions::new())?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn writes_each_row_as_an_element() {
+        let xml = stream_to_xml("name,year\nHalo,2001\nMyst,1993\n", None).unwrap();
+
+        assert!(xml.starts_with("<?xml"), "{}", xml);
+        assert!(xml.contains("<name>Halo</name>"), "{}", xml);
+        assert!(xml.contains("<year>1993</year>"), "{}", xml);
+        assert!(xml.trim_end().ends_with("</root2>"), "{}", xml);
+    }
+
+    #[test]
+    fn generated_names_come_from_the_first_row() {
+        let xml = stream_to_xml("1,2\n3,4\n", Some(CSVHeader::Generated)).unwrap();
+
+        assert!(xml.contains("<col1>1</col1>"), "{}", xml);
+        assert!(xml.contains("<col2>4</col2>"), "{}", xml);
+    }
+
+    #[test]
+    fn a_short_row_is_an_error() {
+        assert!(stream_to_xml("a,b\n1,2\n3\n", None).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+//
+//  Description  :    Inference of the type of each column of CSV data, with how sure it is and how many cells are null
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::fmt;
+use xsd_inference::SimpleType;
+
+//The share of a column's values which must have a type for the column to be given it, rather than being left as strings
+pub const MIN_CONFIDENCE: f64 = 0.5;
+
+//The types a column can be given besides string, from the most to the least specific
+const CANDIDATE_TYPES: [SimpleType; 5] = [SimpleType::Boolean, SimpleType::Integer, SimpleType::Decimal, SimpleType::Date, SimpleType::DateTime];
+
+//The inferred type of a column of CSV data
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct ColumnType {
+    pub name: String,
+    pub value_type: SimpleType,
+    //The share of the values which are not null that have the type, from 0 to 1
+    pub confidence: f64,
+    pub nulls: usize,
+    pub rows: usize,
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.nulls == self.rows {
+            return write!(f, "{}: {} (no values, {} null)", self.name, self.value_type.name(), self.nulls);
+        }
+
+        write!(f, "{}: {} ({:.1}% of values, {} of {} null)", self.name, self.value_type.name(), self.confidence * 100.0, self.nulls, self.rows)
+    }
+}
+
+//Whether a cell holds no value, by being blank or the word 'null' in any case
+pub fn is_null(value: &str) -> bool {
+    let value = value.trim();
+    value.is_empty() || value.eq_ignore_ascii_case("null")
+}
+
+//Infers the type of each named column from the rows of a CSV file, where rows missing a cell count it as null.
+//A column takes the most specific type held by the most of its values, if that is more than MIN_CONFIDENCE of them.
+pub fn infer_column_types(names: &[String], rows: &[Vec<String>]) -> Vec<ColumnType> {
+    let mut column_types: Vec<ColumnType> = Vec::new();
+
+    for (index, name) in names.iter().enumerate() {
+        let mut nulls: usize = 0;
+        let mut counts: [usize; 5] = [0; 5];
+
+        for row in rows {
+            let value = row.get(index).map_or("", |value| value.as_str());
+
+            if is_null(value) {
+                nulls += 1;
+                continue;
+            }
+
+            let value_type = SimpleType::of(value);
+            for (count, &candidate) in counts.iter_mut().zip(CANDIDATE_TYPES.iter()) {
+                if value_type.is_within(candidate) {
+                    *count += 1;
+                }
+            }
+        }
+
+        //Ties go to the more specific type, which comes first
+        let mut best: Option<(SimpleType, usize)> = None;
+        for (&count, &candidate) in counts.iter().zip(CANDIDATE_TYPES.iter()) {
+            if count > best.map_or(0, |(_, best_count)| best_count) {
+                best = Some((candidate, count));
+            }
+        }
+
+        let values = rows.len() - nulls;
+        let (value_type, confidence) = match best {
+            Some((value_type, count)) if count as f64 > values as f64 * MIN_CONFIDENCE => (value_type, count as f64 / values as f64),
+            _ if values == 0 => (SimpleType::String, 0.0),
+            _ => (SimpleType::String, 1.0),
+        };
+
+        column_types.push(ColumnType {
+            name: name.clone(),
+            value_type,
+            confidence,
+            nulls,
+            rows: rows.len(),
+        });
+    }
+
+    column_types
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Infers the types of columns given as rows of text split on ','
+    fn infer(names: &str, rows: &[&str]) -> Vec<ColumnType> {
+        let names: Vec<String> = names.split(',').map(|name| name.to_owned()).collect();
+        let rows: Vec<Vec<String>> = rows.iter().map(|row| row.split(',').map(|cell| cell.to_owned()).collect()).collect();
+
+        infer_column_types(&names, &rows)
+    }
+
+    #[test]
+    fn columns_take_the_most_specific_type_of_their_values() {
+        let types = infer("id,price,paid,day,at,name", &[
+            "1,2,true,2024-01-31,2024-01-31T10:00:00,a",
+            "2,2.5,false,2024-02-01,2024-02-01T11:30:00Z,b",
+        ]);
+        let names: Vec<&str> = types.iter().map(|column| column.value_type.name()).collect();
+
+        assert_eq!(names, vec!["integer", "decimal", "boolean", "date", "datetime", "string"]);
+        assert!(types.iter().all(|column| column.confidence == 1.0 && column.nulls == 0));
+    }
+
+    #[test]
+    fn nulls_are_counted_and_do_not_lower_confidence() {
+        let types = infer("count", &["1", "NULL", " ", "x", "3", "4"]);
+
+        assert_eq!(types[0].value_type, SimpleType::Integer);
+        assert_eq!(types[0].nulls, 2);
+        assert_eq!(types[0].confidence, 0.75);
+        assert_eq!(types[0].to_string(), "count: integer (75.0% of values, 2 of 6 null)");
+    }
+
+    #[test]
+    fn columns_without_a_clear_type_are_strings() {
+        let types = infer("mixed,empty", &["1,", "x,", "y,"]);
+
+        assert_eq!(types[0].value_type, SimpleType::String);
+        assert_eq!(types[1].value_type, SimpleType::String);
+        assert_eq!(types[1].to_string(), "empty: string (no values, 3 null)");
+    }
+}
@@ -0,0 +1,763 @@
+//
+//  Description  :    An XML tree stored as an arena of nodes addressed by id, with interned names
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::fmt;
+use std::collections::HashMap;
+use namespaces::{XML_NAMESPACE, split_name, declared_prefix};
+
+//The position of a node within its document
+#[derive(Debug)]
+#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, Copy)]
+pub struct NodeId(usize);
+
+//The position of an interned name within its document
+#[derive(Debug)]
+#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, Copy)]
+pub struct NameId(usize);
+
+//What a node holds. Text and comments keep their text as data, and processing instructions their target as the name.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum NodeKind {
+    Element,
+    Text,
+    Comment,
+    ProcessingInstruction,
+}
+
+//Where a node starts in the file it was read from, counting lines and columns from 1
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct XMLNode {
+    kind: NodeKind,
+    name: NameId,
+    local_name: NameId,
+    namespace: Option<NameId>,
+    data: String,
+    attributes: Vec<(NameId, String)>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    blank_line_before: bool,
+    position: Option<Position>,
+}
+
+//An XML tree. Every document starts with a synthetic 'root' node which holds the outermost elements.
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct XMLDocument {
+    nodes: Vec<XMLNode>,
+    names: Vec<String>,
+    name_ids: HashMap<String, NameId>,
+}
+
+impl Default for XMLDocument {
+    fn default() -> XMLDocument {
+        XMLDocument::new()
+    }
+}
+
+impl XMLDocument {
+    pub fn new() -> XMLDocument {
+        let mut document = XMLDocument {
+            nodes: Vec::new(),
+            names: Vec::new(),
+            name_ids: HashMap::new(),
+        };
+
+        let name = document.intern("root");
+        document.nodes.push(XMLNode {
+            kind: NodeKind::Element,
+            name,
+            local_name: name,
+            namespace: None,
+            data: String::new(),
+            attributes: Vec::new(),
+            parent: None,
+            children: Vec::new(),
+            blank_line_before: false,
+            position: None,
+        });
+
+        document
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    //Finds the id of a name, adding it to the document if it has not been seen before
+    pub fn intern(&mut self, name: &str) -> NameId {
+        if let Some(id) = self.name_ids.get(name) {
+            return *id;
+        }
+
+        let id = NameId(self.names.len());
+        self.names.push(name.to_owned());
+        self.name_ids.insert(name.to_owned(), id);
+
+        id
+    }
+
+    //Finds the id of a name without adding it
+    pub fn name_id(&self, name: &str) -> Option<NameId> {
+        self.name_ids.get(name).cloned()
+    }
+
+    pub fn name_str(&self, name: NameId) -> &str {
+        &self.names[name.0]
+    }
+
+    fn create_node(&mut self, kind: NodeKind, name: &str, data: &str) -> NodeId {
+        let local_name = self.intern(split_name(name).1);
+        let name = self.intern(name);
+        let id = NodeId(self.nodes.len());
+
+        self.nodes.push(XMLNode {
+            kind,
+            name,
+            local_name,
+            namespace: None,
+            data: data.to_owned(),
+            attributes: Vec::new(),
+            parent: None,
+            children: Vec::new(),
+            blank_line_before: false,
+            position: None,
+        });
+
+        id
+    }
+
+    //Creates an element which is not yet part of the tree, ready to be inserted
+    pub fn create_element(&mut self, name: &str) -> NodeId {
+        self.create_node(NodeKind::Element, name, "")
+    }
+
+    pub fn create_text(&mut self, text: &str) -> NodeId {
+        self.create_node(NodeKind::Text, "#text", text)
+    }
+
+    pub fn create_comment(&mut self, text: &str) -> NodeId {
+        self.create_node(NodeKind::Comment, "#comment", text)
+    }
+
+    pub fn create_processing_instruction(&mut self, target: &str, data: &str) -> NodeId {
+        self.create_node(NodeKind::ProcessingInstruction, target, data)
+    }
+
+    fn append_node(&mut self, parent: NodeId, node: NodeId) -> NodeId {
+        self.nodes[node.0].parent = Some(parent);
+        self.nodes[parent.0].children.push(node);
+
+        node
+    }
+
+    //Adds a new element as the last child of 'parent', in whichever namespace its prefix is bound to there
+    pub fn append_element(&mut self, parent: NodeId, name: &str) -> NodeId {
+        let node = self.create_element(name);
+        self.append_node(parent, node);
+        self.resolve_namespace(node);
+
+        node
+    }
+
+    pub fn append_text(&mut self, parent: NodeId, text: &str) -> NodeId {
+        let node = self.create_text(text);
+        self.append_node(parent, node)
+    }
+
+    pub fn append_comment(&mut self, parent: NodeId, text: &str) -> NodeId {
+        let node = self.create_comment(text);
+        self.append_node(parent, node)
+    }
+
+    pub fn append_processing_instruction(&mut self, parent: NodeId, target: &str, data: &str) -> NodeId {
+        let node = self.create_processing_instruction(target, data);
+        self.append_node(parent, node)
+    }
+
+    //Adds text to the end of an element, joining it onto the last child if that is text already
+    pub(crate) fn push_data(&mut self, node: NodeId, data: &str) {
+        if data.is_empty() {
+            return;
+        }
+
+        match self.nodes[node.0].children.last().cloned() {
+            Some(last) if self.kind(last) == NodeKind::Text => self.nodes[last.0].data.push_str(data),
+            _ => {
+                self.append_text(node, data);
+            },
+        }
+    }
+
+    pub(crate) fn push_attribute(&mut self, node: NodeId, name: &str, value: String) {
+        let name = self.intern(name);
+        self.nodes[node.0].attributes.push((name, value));
+    }
+
+    //Checks whether 'node' is 'ancestor' or somewhere below it
+    pub fn is_inside(&self, node: NodeId, ancestor: NodeId) -> bool {
+        let mut current = Some(node);
+
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.parent(id);
+        }
+
+        false
+    }
+
+    //Takes a node and everything below it out of the tree. It can be inserted again elsewhere.
+    pub fn remove(&mut self, node: NodeId) -> Result<(), String> {
+        if node == self.root() {
+            return Err("Cannot remove the root of a document".to_owned());
+        }
+
+        if let Some(parent) = self.nodes[node.0].parent.take() {
+            self.nodes[parent.0].children.retain(|&child| child != node);
+        }
+
+        Ok(())
+    }
+
+    //Moves a node to position 'index' among the children of 'parent', taking it from wherever it was
+    pub fn insert_child(&mut self, parent: NodeId, index: usize, node: NodeId) -> Result<(), String> {
+        if self.is_inside(parent, node) {
+            return Err(format!("Cannot move {} inside itself", self.name(node)));
+        }
+
+        //Check the position before taking the node out, so that a failed move leaves the tree as it was
+        let mut child_count = self.nodes[parent.0].children.len();
+        if self.parent(node) == Some(parent) {
+            child_count -= 1;
+        }
+
+        if index > child_count {
+            return Err(format!("Cannot insert at position {} of {}, which has {} children", index, self.name(parent), child_count));
+        }
+
+        self.remove(node)?;
+        self.nodes[parent.0].children.insert(index, node);
+        self.nodes[node.0].parent = Some(parent);
+
+        //Prefixes may be bound differently in the new place
+        self.resolve_namespaces(node);
+
+        Ok(())
+    }
+
+    //Moves a node to the end of the children of 'parent'
+    pub fn append_child(&mut self, parent: NodeId, node: NodeId) -> Result<(), String> {
+        if self.is_inside(parent, node) {
+            return Err(format!("Cannot move {} inside itself", self.name(node)));
+        }
+
+        //Count the children after taking the node out, in case it is already one of them
+        self.remove(node)?;
+
+        let index = self.nodes[parent.0].children.len();
+        self.insert_child(parent, index, node)
+    }
+
+    //Moves a node to just before 'sibling'
+    pub fn insert_before(&mut self, sibling: NodeId, node: NodeId) -> Result<(), String> {
+        self.insert_beside(sibling, node, 0)
+    }
+
+    //Moves a node to just after 'sibling'
+    pub fn insert_after(&mut self, sibling: NodeId, node: NodeId) -> Result<(), String> {
+        self.insert_beside(sibling, node, 1)
+    }
+
+    fn insert_beside(&mut self, sibling: NodeId, node: NodeId, offset: usize) -> Result<(), String> {
+        let parent = match self.parent(sibling) {
+            Some(parent) => parent,
+            None => return Err(format!("Cannot insert beside {}, which is not in the tree", self.name(sibling))),
+        };
+
+        if sibling == node {
+            return Ok(());
+        }
+        if self.is_inside(parent, node) {
+            return Err(format!("Cannot move {} inside itself", self.name(node)));
+        }
+        self.remove(node)?;
+
+        let index = self.child_index(sibling).unwrap_or(0) + offset;
+        self.insert_child(parent, index, node)
+    }
+
+    pub fn rename(&mut self, node: NodeId, name: &str) {
+        let local_name = self.intern(split_name(name).1);
+        let name = self.intern(name);
+
+        self.nodes[node.0].name = name;
+        self.nodes[node.0].local_name = local_name;
+        self.resolve_namespace(node);
+    }
+
+    //Replaces the text directly inside a node. An element loses all its text children and gets one holding 'text' first.
+    pub fn set_text(&mut self, node: NodeId, text: &str) {
+        if !self.is_element(node) {
+            self.nodes[node.0].data = text.to_owned();
+            return;
+        }
+
+        let children = ::std::mem::take(&mut self.nodes[node.0].children);
+        let (texts, mut others): (Vec<NodeId>, Vec<NodeId>) = children.into_iter().partition(|&child| self.kind(child) == NodeKind::Text);
+        for child in texts {
+            self.nodes[child.0].parent = None;
+        }
+
+        if !text.is_empty() {
+            let child = self.create_text(text);
+            self.nodes[child.0].parent = Some(node);
+            others.insert(0, child);
+        }
+        self.nodes[node.0].children = others;
+    }
+
+    //Marks whether a blank line came before a node in the file it was read from, which can be kept when it is written again
+    pub fn set_blank_line_before(&mut self, node: NodeId, blank_line: bool) {
+        self.nodes[node.0].blank_line_before = blank_line;
+    }
+
+    //Records where a node was found in the file it was read from
+    pub fn set_position(&mut self, node: NodeId, position: Position) {
+        self.nodes[node.0].position = Some(position);
+    }
+
+    //Sets the value of an attribute, adding it after the others if the node does not have it yet
+    pub fn set_attribute(&mut self, node: NodeId, name: &str, value: &str) {
+        let name_id = self.intern(name);
+
+        {
+            let attributes = &mut self.nodes[node.0].attributes;
+
+            match attributes.iter_mut().find(|&&mut (attribute_name, _)| attribute_name == name_id) {
+                Some(attribute) => attribute.1 = value.to_owned(),
+                None => attributes.push((name_id, value.to_owned())),
+            }
+        }
+
+        if declared_prefix(name).is_some() {
+            self.resolve_namespaces(node);
+        }
+    }
+
+    //Removes an attribute, returning its value if the node had it
+    pub fn remove_attribute(&mut self, node: NodeId, name: &str) -> Option<String> {
+        let name_id = self.name_id(name)?;
+        let index = self.nodes[node.0].attributes.iter().position(|&(attribute_name, _)| attribute_name == name_id)?;
+        let value = self.nodes[node.0].attributes.remove(index).1;
+
+        if declared_prefix(name).is_some() {
+            self.resolve_namespaces(node);
+        }
+
+        Some(value)
+    }
+
+    //Finds the namespace a prefix is bound to at a node, where an empty prefix finds the default namespace
+    pub fn lookup_namespace(&self, node: NodeId, prefix: &str) -> Option<&str> {
+        if prefix == "xml" {
+            return Some(XML_NAMESPACE);
+        }
+
+        let mut current = Some(node);
+
+        while let Some(id) = current {
+            for &(name, ref uri) in &self.nodes[id.0].attributes {
+                if declared_prefix(self.name_str(name)) == Some(prefix) {
+                    //'xmlns=""' takes elements out of the default namespace
+                    return if uri.is_empty() { None } else { Some(uri) };
+                }
+            }
+
+            current = self.parent(id);
+        }
+
+        None
+    }
+
+    //Sets the namespace of an element from the declarations in scope of its prefix
+    pub fn resolve_namespace(&mut self, node: NodeId) {
+        if !self.is_element(node) {
+            return;
+        }
+
+        let namespace = {
+            let (prefix, _) = split_name(self.name(node));
+            self.lookup_namespace(node, prefix).map(|uri| uri.to_owned())
+        };
+
+        self.nodes[node.0].namespace = namespace.map(|uri| self.intern(&uri));
+    }
+
+    //Sets the namespaces of a node and everything below it, after its declarations or its place in the tree change
+    pub fn resolve_namespaces(&mut self, node: NodeId) {
+        let nodes: Vec<NodeId> = Some(node).into_iter().chain(self.descendants(node)).collect();
+
+        for id in nodes {
+            self.resolve_namespace(id);
+        }
+    }
+
+    //Copies a node and everything below it, returning the copy which is not yet part of the tree
+    pub fn clone_node(&mut self, node: NodeId) -> NodeId {
+        let copy = NodeId(self.nodes.len());
+        let mut original = self.nodes[node.0].clone();
+        let children = ::std::mem::take(&mut original.children);
+
+        original.parent = None;
+        self.nodes.push(original);
+
+        for child in children {
+            let child_copy = self.clone_node(child);
+            self.nodes[child_copy.0].parent = Some(copy);
+            self.nodes[copy.0].children.push(child_copy);
+        }
+
+        copy
+    }
+
+    //Copies a node and everything below it from another document, returning the copy which is not yet part of the tree
+    pub fn import_node(&mut self, other: &XMLDocument, node: NodeId) -> NodeId {
+        let copy = self.create_node(other.kind(node), other.name(node), other.data(node));
+        self.nodes[copy.0].blank_line_before = other.blank_line_before(node);
+        self.nodes[copy.0].position = other.position(node);
+
+        for (name, value) in other.attributes(node) {
+            self.set_attribute(copy, name, value);
+        }
+
+        //Keep the namespace the node had, as the declarations above it are not copied
+        let namespace = other.namespace(node).map(|uri| self.intern(uri));
+        self.nodes[copy.0].namespace = namespace;
+
+        for &child in other.children(node) {
+            let child_copy = self.import_node(other, child);
+            self.nodes[child_copy.0].parent = Some(copy);
+            self.nodes[copy.0].children.push(child_copy);
+        }
+
+        copy
+    }
+
+    //The number of nodes in the document, including the root and any taken out of the tree
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn kind(&self, node: NodeId) -> NodeKind {
+        self.nodes[node.0].kind
+    }
+
+    pub fn is_element(&self, node: NodeId) -> bool {
+        self.kind(node) == NodeKind::Element
+    }
+
+    pub fn name(&self, node: NodeId) -> &str {
+        self.name_str(self.nodes[node.0].name)
+    }
+
+    pub fn name_of(&self, node: NodeId) -> NameId {
+        self.nodes[node.0].name
+    }
+
+    //The text held by a text, comment or processing instruction node. Elements hold none of their own.
+    pub fn data(&self, node: NodeId) -> &str {
+        &self.nodes[node.0].data
+    }
+
+    //The text directly inside an element, joined in document order, or the data of any other node
+    pub fn text(&self, node: NodeId) -> String {
+        if !self.is_element(node) {
+            return self.data(node).to_owned();
+        }
+
+        self.children(node).iter()
+            .filter(|&&child| self.kind(child) == NodeKind::Text)
+            .map(|&child| self.data(child))
+            .collect()
+    }
+
+    //Whether an element has any text directly inside it which is not just whitespace
+    pub fn has_text(&self, node: NodeId) -> bool {
+        self.children(node).iter().any(|&child| self.kind(child) == NodeKind::Text && !self.data(child).trim().is_empty())
+    }
+
+    //The namespace URI of an element, if it is in one
+    pub fn namespace(&self, node: NodeId) -> Option<&str> {
+        self.nodes[node.0].namespace.map(|uri| self.name_str(uri))
+    }
+
+    //The name of a node without its prefix
+    pub fn local_name(&self, node: NodeId) -> &str {
+        self.name_str(self.nodes[node.0].local_name)
+    }
+
+    //The name of an element written as '{uri}local' if it is in a namespace, otherwise as its local name
+    pub fn expanded_name(&self, node: NodeId) -> String {
+        match self.namespace(node) {
+            Some(uri) => format!("{{{}}}{}", uri, self.local_name(node)),
+            None => self.local_name(node).to_owned(),
+        }
+    }
+
+    //The namespace of an attribute. Attributes without a prefix are never in a namespace.
+    pub fn attribute_namespace(&self, node: NodeId, name: &str) -> Option<&str> {
+        match split_name(name) {
+            ("", _) => None,
+            ("xmlns", _) => None,
+            (prefix, _) => self.lookup_namespace(node, prefix),
+        }
+    }
+
+    //Where a node was in the file it was read from, if it was read with positions
+    pub fn position(&self, node: NodeId) -> Option<Position> {
+        self.nodes[node.0].position
+    }
+
+    pub fn blank_line_before(&self, node: NodeId) -> bool {
+        self.nodes[node.0].blank_line_before
+    }
+
+    pub fn attributes(&self, node: NodeId) -> Vec<(&str, &str)> {
+        self.nodes[node.0].attributes.iter()
+            .map(|&(name, ref value)| (self.name_str(name), value.as_str()))
+            .collect()
+    }
+
+    pub fn attribute(&self, node: NodeId, name: &str) -> Option<&str> {
+        let name = self.name_id(name)?;
+
+        self.nodes[node.0].attributes.iter()
+            .find(|&&(attribute_name, _)| attribute_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node.0].parent
+    }
+
+    //Every node directly inside 'node', including text, comments and processing instructions
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        &self.nodes[node.0].children
+    }
+
+    //The elements directly inside 'node'
+    pub fn child_elements(&self, node: NodeId) -> Vec<NodeId> {
+        self.children(node).iter().cloned().filter(|&child| self.is_element(child)).collect()
+    }
+
+    //The nodes which share a parent with 'node', not including itself
+    pub fn siblings(&self, node: NodeId) -> Vec<NodeId> {
+        match self.parent(node) {
+            Some(parent) => self.children(parent).iter().cloned().filter(|&sibling| sibling != node).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    //The position of a node among its parent's children
+    pub fn child_index(&self, node: NodeId) -> Option<usize> {
+        let parent = self.parent(node)?;
+        self.children(parent).iter().position(|&child| child == node)
+    }
+
+    pub fn next_sibling(&self, node: NodeId) -> Option<NodeId> {
+        let parent = self.parent(node)?;
+        let index = self.child_index(node)?;
+        self.children(parent).get(index + 1).cloned()
+    }
+
+    pub fn previous_sibling(&self, node: NodeId) -> Option<NodeId> {
+        let parent = self.parent(node)?;
+        let index = self.child_index(node)?;
+
+        if index == 0 {
+            None
+        }
+        else {
+            self.children(parent).get(index - 1).cloned()
+        }
+    }
+
+    //Every node below 'node' in document order, not including itself
+    pub fn descendants<'a>(&'a self, node: NodeId) -> Descendants<'a> {
+        let mut stack: Vec<NodeId> = self.children(node).to_vec();
+        stack.reverse();
+
+        Descendants {
+            document: self,
+            stack,
+        }
+    }
+
+    //The names of the nodes from the root down to 'node'
+    pub fn path(&self, node: NodeId) -> Vec<&str> {
+        let mut path: Vec<&str> = Vec::new();
+        let mut current = Some(node);
+
+        while let Some(id) = current {
+            path.push(self.name(id));
+            current = self.parent(id);
+        }
+
+        path.reverse();
+        path
+    }
+}
+
+//Walks the nodes below a node in document order
+pub struct Descendants<'a> {
+    document: &'a XMLDocument,
+    stack: Vec<NodeId>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let node = self.stack.pop()?;
+
+        //Visit the children next, first child first
+        for &child in self.document.children(node).iter().rev() {
+            self.stack.push(child);
+        }
+
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {lexer, parser};
+
+    fn parse(xml: &str) -> XMLDocument {
+        parser(&lexer(xml.to_owned()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn elements_with_the_same_name_share_an_interned_name() {
+        let document = parse("<a><b/><b/></a>");
+        let a = document.child_elements(document.root())[0];
+        let b = document.child_elements(a);
+
+        assert_eq!(document.name_of(b[0]), document.name_of(b[1]));
+        assert_eq!(document.name_id("b"), Some(document.name_of(b[0])));
+        assert_eq!(document.path(b[1]), vec!["root", "a", "b"]);
+    }
+
+    #[test]
+    fn descendants_are_in_document_order() {
+        let document = parse("<a><b><c/></b><d/></a>");
+        let names: Vec<&str> = document.descendants(document.root()).map(|node| document.name(node)).collect();
+
+        assert_eq!(names, vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn text_is_kept_between_child_elements() {
+        let document = parse("<p>one <b>two</b> three</p>");
+        let p = document.child_elements(document.root())[0];
+        let kinds: Vec<NodeKind> = document.children(p).iter().map(|&child| document.kind(child)).collect();
+
+        assert_eq!(kinds, vec![NodeKind::Text, NodeKind::Element, NodeKind::Text]);
+        assert_eq!(document.text(p), "onethree");
+        assert!(document.has_text(p));
+    }
+
+    #[test]
+    fn setting_text_replaces_only_the_text_children() {
+        let mut document = parse("<p>one<b>two</b>three</p>");
+        let p = document.child_elements(document.root())[0];
+
+        document.set_text(p, "new");
+
+        assert_eq!(document.text(p), "new");
+        assert_eq!(document.children(p).len(), 2);
+        assert_eq!(document.name(document.child_elements(p)[0]), "b");
+    }
+
+    //The names of the child elements of 'node'
+    fn child_names(document: &XMLDocument, node: NodeId) -> Vec<&str> {
+        document.child_elements(node).into_iter().map(|child| document.name(child)).collect()
+    }
+
+    #[test]
+    fn nodes_move_between_parents() {
+        let mut document = parse("<a><b/><c/><d/></a>");
+        let a = document.child_elements(document.root())[0];
+        let children = document.child_elements(a);
+
+        document.insert_before(children[0], children[2]).unwrap();
+        assert_eq!(child_names(&document, a), vec!["d", "b", "c"]);
+
+        document.append_child(children[0], children[1]).unwrap();
+        assert_eq!(child_names(&document, a), vec!["d", "b"]);
+        assert_eq!(document.parent(children[1]), Some(children[0]));
+
+        document.remove(children[2]).unwrap();
+        assert_eq!(child_names(&document, a), vec!["b"]);
+        assert_eq!(document.parent(children[2]), None);
+    }
+
+    #[test]
+    fn failed_moves_leave_the_tree_as_it_was() {
+        let mut document = parse("<a><b><c/></b></a>");
+        let a = document.child_elements(document.root())[0];
+        let b = document.child_elements(a)[0];
+        let c = document.child_elements(b)[0];
+
+        assert!(document.append_child(c, b).is_err());
+        assert!(document.insert_child(a, 2, c).is_err());
+        assert!(document.remove(document.root()).is_err());
+        assert_eq!(document.parent(c), Some(b));
+        assert_eq!(child_names(&document, a), vec!["b"]);
+    }
+
+    #[test]
+    fn clones_are_deep_and_detached() {
+        let mut document = parse("<a><b x=\"1\"><c>text</c></b></a>");
+        let a = document.child_elements(document.root())[0];
+        let b = document.child_elements(a)[0];
+
+        let copy = document.clone_node(b);
+        assert_eq!(document.parent(copy), None);
+
+        document.set_attribute(copy, "x", "2");
+        document.rename(copy, "e");
+        document.append_child(a, copy).unwrap();
+
+        assert_eq!(child_names(&document, a), vec!["b", "e"]);
+        assert_eq!(document.attribute(b, "x"), Some("1"));
+        assert_eq!(document.attribute(copy, "x"), Some("2"));
+        assert_eq!(document.text(document.child_elements(copy)[0]), "text");
+        assert_eq!(document.remove_attribute(copy, "x"), Some("2".to_owned()));
+        assert_eq!(document.attribute(copy, "x"), None);
+    }
+}
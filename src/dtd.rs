@@ -0,0 +1,1425 @@
+//
+//  Description  :    Document type definitions: their entities, attribute defaults and element content models
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use document::{XMLDocument, NodeId};
+use encoding::{detect_encoding, decode};
+use namespaces::declared_prefix;
+use validation::{ValidationError, ContentProblem, sort_by_position, match_repeated, check_content_model};
+use {decode_entities, declaration_depth};
+
+//The most characters one entity reference may expand to, so that entities referring to each other many times cannot fill memory
+const MAX_EXPANSION: usize = 1 << 20;
+
+//How deeply entities and parameter entities may refer to each other
+const MAX_DEPTH: usize = 32;
+
+//The '<!DOCTYPE>' of a file, naming its root element and where its declarations are
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Doctype {
+    pub name: String,
+    pub public_id: Option<String>,
+    pub system_id: Option<String>,
+    //The declarations between '[' and ']', empty if there are none
+    pub internal_subset: String,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum Entity {
+    //Replacement text, with character references already replaced
+    Internal(String),
+    //A local file holding the replacement text
+    External(PathBuf),
+    //An entity which is not read, such as an image, a file on another computer, or any file when external files are not read
+    Unparsed,
+}
+
+//A part of an element content model, such as '(name, address+)?', and how many times it may occur
+#[derive(Debug)]
+#[derive(Clone)]
+struct ContentParticle {
+    term: ContentTerm,
+    min_occurs: usize,
+    max_occurs: Option<usize>,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum ContentTerm {
+    Name(String),
+    Sequence(Vec<ContentParticle>),
+    Choice(Vec<ContentParticle>),
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum ContentSpec {
+    Empty,
+    Any,
+    //Text mixed with any of the named elements
+    Mixed(Vec<String>),
+    Children(ContentParticle),
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+enum AttributeType {
+    CData,
+    Id,
+    IdRef,
+    IdRefs,
+    Entity,
+    Entities,
+    NmToken,
+    NmTokens,
+    //One of a list of values, which is also how notation attributes are checked
+    Enumeration(Vec<String>),
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum AttributeDefault {
+    Required,
+    Implied,
+    Fixed(String),
+    Value(String),
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+struct AttributeDefinition {
+    name: String,
+    kind: AttributeType,
+    default: AttributeDefault,
+}
+
+//The declarations of a DTD, from the internal subset of a document and any external files it names
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Dtd {
+    root_name: Option<String>,
+    elements: HashMap<String, ContentSpec>,
+    attributes: HashMap<String, Vec<AttributeDefinition>>,
+    entities: HashMap<String, Entity>,
+    parameter_entities: HashMap<String, Entity>,
+    //External DTD files which were named but do not exist
+    missing_files: Vec<PathBuf>,
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '.' || c == '-' || c == '_' || c == ':'
+}
+
+fn is_name(text: &str) -> bool {
+    match text.chars().next() {
+        Some(c) if c.is_alphabetic() || c == '_' || c == ':' => text.chars().all(is_name_char),
+        _ => false,
+    }
+}
+
+//Whether a system identifier names a file on this computer rather than on a server
+fn is_local(system_id: &str) -> bool {
+    !system_id.contains("://") || system_id.starts_with("file://")
+}
+
+//Finds the file a system identifier names, relative to the directory of the file naming it. Only files inside
+//'directory', the directory of the document, are read, so a document cannot name any other file on this computer.
+fn resolve_system_id(system_id: &str, base_directory: &Path, directory: &Path) -> Result<PathBuf, String> {
+    let path = base_directory.join(system_id.trim_start_matches("file://"));
+
+    match (path.canonicalize(), directory.canonicalize()) {
+        (Ok(file), Ok(directory)) if file.starts_with(&directory) => Ok(path),
+        //A file which does not exist is never read
+        (Err(_), _) => Ok(path),
+        _ => Err(format!("{} is outside the directory of the document, so is not read", system_id)),
+    }
+}
+
+//Reads a DTD or external entity file, without the text declaration which may start it
+fn read_external(path: &Path) -> Result<String, String> {
+    let mut bytes: Vec<u8> = Vec::new();
+
+    let result = File::open(path).and_then(|mut file| file.read_to_end(&mut bytes));
+    if let Err(e) = result {
+        return Err(format!("Could not read {}: {}", path.display(), e));
+    }
+
+    let text = decode(&bytes, detect_encoding(&bytes))?;
+    let text = text.trim_start_matches('\u{feff}');
+
+    if text.starts_with("<?xml") {
+        if let Some(end) = text.find("?>") {
+            return Ok(text[end + 2..].to_owned());
+        }
+    }
+
+    Ok(text.to_owned())
+}
+
+//Replaces character references such as '&#169;' in an entity value, leaving other references to be replaced where the entity is used
+fn replace_character_references(text: &str) -> String {
+    let mut replaced = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("&#") {
+        replaced.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        match rest.find(';') {
+            Some(end) => {
+                let decoded = decode_entities(&rest[..end + 1]);
+                replaced.push_str(&decoded);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                replaced.push_str(rest);
+                rest = "";
+            },
+        }
+    }
+
+    replaced.push_str(rest);
+    replaced
+}
+
+//Reads the words, names and quoted values of a declaration
+struct Scanner {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Scanner {
+    fn new(text: &str) -> Scanner {
+        Scanner {
+            chars: text.chars().collect(),
+            position: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.chars.len()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(offset, c)| self.chars.get(self.position + offset) == Some(&c))
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        if self.starts_with(text) {
+            self.position += text.chars().count();
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn skip_space(&mut self) -> bool {
+        let start = self.position;
+
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.position += 1;
+        }
+
+        self.position > start
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        self.skip_space();
+
+        let start = self.position;
+        while self.peek().is_some_and(|c| is_name_char(c) || c == '#') {
+            self.position += 1;
+        }
+
+        if self.position == start {
+            return Err(match self.peek() {
+                Some(c) => format!("Expected a name but found '{}'", c),
+                None => "Expected a name but found the end of the declaration".to_owned(),
+            });
+        }
+
+        Ok(self.chars[start..self.position].iter().collect())
+    }
+
+    fn quoted(&mut self) -> Result<String, String> {
+        self.skip_space();
+
+        let quote = match self.peek() {
+            Some(c) if c == '"' || c == '\'' => c,
+            _ => return Err("Expected a quoted value".to_owned()),
+        };
+        self.position += 1;
+
+        let start = self.position;
+        while self.peek().is_some_and(|c| c != quote) {
+            self.position += 1;
+        }
+
+        if self.at_end() {
+            return Err("Unterminated quoted value".to_owned());
+        }
+
+        let value = self.chars[start..self.position].iter().collect();
+        self.position += 1;
+
+        Ok(value)
+    }
+
+    //Skips to just after the next occurrence of some text, returning what came before it
+    fn until(&mut self, end: &str) -> Result<String, String> {
+        let start = self.position;
+
+        while !self.at_end() && !self.starts_with(end) {
+            self.position += 1;
+        }
+
+        if self.at_end() {
+            return Err(format!("Expected '{}'", end));
+        }
+
+        let text = self.chars[start..self.position].iter().collect();
+        self.position += end.chars().count();
+
+        Ok(text)
+    }
+
+    //Reads a markup declaration up to its closing '>', which may not be inside a quoted value
+    fn declaration(&mut self) -> Result<String, String> {
+        let start = self.position;
+        let mut quote: Option<char> = None;
+
+        while let Some(c) = self.peek() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => {},
+                None if c == '"' || c == '\'' => quote = Some(c),
+                None if c == '>' => {
+                    let text = self.chars[start..self.position].iter().collect();
+                    self.position += 1;
+                    return Ok(text);
+                },
+                None => {},
+            }
+            self.position += 1;
+        }
+
+        Err("Unterminated declaration".to_owned())
+    }
+
+    //Reads how many times a particle may occur from a following '?', '*' or '+'
+    fn occurrence(&mut self) -> (usize, Option<usize>) {
+        if self.eat("?") {
+            (0, Some(1))
+        }
+        else if self.eat("*") {
+            (0, None)
+        }
+        else if self.eat("+") {
+            (1, None)
+        }
+        else {
+            (1, Some(1))
+        }
+    }
+
+    //Reads a content particle, such as 'title', '(a | b)*' or '(head, body)'
+    fn content_particle(&mut self) -> Result<ContentParticle, String> {
+        self.skip_space();
+
+        let term = if self.eat("(") {
+            let mut particles = vec![self.content_particle()?];
+            let mut separator: Option<char> = None;
+
+            loop {
+                self.skip_space();
+
+                match self.peek() {
+                    Some(')') => {
+                        self.position += 1;
+                        break;
+                    },
+                    Some(c) if (c == ',' || c == '|') && separator.is_none_or(|separator| separator == c) => {
+                        self.position += 1;
+                        separator = Some(c);
+                        particles.push(self.content_particle()?);
+                    },
+                    Some(c) => return Err(format!("Unexpected '{}' in a content model", c)),
+                    None => return Err("Expected ')' in a content model".to_owned()),
+                }
+            }
+
+            if separator == Some('|') { ContentTerm::Choice(particles) } else { ContentTerm::Sequence(particles) }
+        }
+        else {
+            ContentTerm::Name(self.name()?)
+        };
+
+        let (min_occurs, max_occurs) = self.occurrence();
+
+        Ok(ContentParticle {
+            term,
+            min_occurs,
+            max_occurs,
+        })
+    }
+}
+
+//Reads the declarations of a DTD into it, following parameter entities and external files
+struct DtdReader<'a> {
+    dtd: &'a mut Dtd,
+    depth: usize,
+    //The directory external files are read from, or None if they are not read
+    directory: Option<&'a Path>,
+}
+
+impl<'a> DtdReader<'a> {
+    //The text of a parameter entity, and the directory of the file it came from if it is external
+    fn parameter_text(&self, name: &str) -> Result<(String, Option<PathBuf>), String> {
+        match self.dtd.parameter_entities.get(name) {
+            Some(Entity::Internal(text)) => Ok((text.clone(), None)),
+            Some(Entity::External(path)) => Ok((read_external(path)?, path.parent().map(|directory| directory.to_path_buf()))),
+            Some(&Entity::Unparsed) => Ok((String::new(), None)),
+            None => Err(format!("Parameter entity %{}; is not declared", name)),
+        }
+    }
+
+    //Replaces parameter entity references such as '%common;', leaving quoted values as they are unless 'in_quotes' is set
+    fn replace_parameters(&mut self, text: &str, in_quotes: bool) -> Result<String, String> {
+        let mut replaced = String::new();
+        let mut chars = text.chars().peekable();
+        let mut quote: Option<char> = None;
+
+        while let Some(c) = chars.next() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                None if !in_quotes && (c == '"' || c == '\'') => quote = Some(c),
+                _ => {},
+            }
+
+            if c != '%' || quote.is_some() || !chars.peek().is_some_and(|&next| is_name_char(next)) {
+                replaced.push(c);
+                continue;
+            }
+
+            let name: String = chars.by_ref().take_while(|&c| c != ';').collect();
+
+            if self.depth >= MAX_DEPTH {
+                return Err(format!("Parameter entity %{}; refers to itself", name));
+            }
+
+            let text = self.parameter_text(&name)?.0;
+            self.depth += 1;
+            let text = self.replace_parameters(&text, in_quotes)?;
+            self.depth -= 1;
+
+            //Outside of values, parameter entities are read as if surrounded by spaces
+            if quote.is_none() {
+                replaced.push(' ');
+                replaced.push_str(&text);
+                replaced.push(' ');
+            }
+            else {
+                replaced.push_str(&text);
+            }
+        }
+
+        Ok(replaced)
+    }
+
+    //Reads the declarations of an internal subset or external file, whose relative system identifiers are found from 'base_directory'
+    fn read_subset(&mut self, text: &str, base_directory: Option<&Path>) -> Result<(), String> {
+        let mut scanner = Scanner::new(text);
+
+        loop {
+            scanner.skip_space();
+
+            if scanner.at_end() {
+                break;
+            }
+
+            if scanner.eat("<!--") {
+                scanner.until("-->")?;
+            }
+            else if scanner.eat("<?") {
+                scanner.until("?>")?;
+            }
+            else if scanner.eat("<![") {
+                self.conditional_section(&mut scanner, base_directory)?;
+            }
+            else if scanner.eat("%") {
+                let name = scanner.until(";")?;
+
+                if self.depth >= MAX_DEPTH {
+                    return Err(format!("Parameter entity %{}; refers to itself", name));
+                }
+
+                let (text, directory) = self.parameter_text(&name)?;
+
+                self.depth += 1;
+                self.read_subset(&text, directory.as_deref().or(base_directory))?;
+                self.depth -= 1;
+            }
+            else if scanner.eat("<!") {
+                let declaration = scanner.declaration()?;
+                self.declaration(&declaration, base_directory)?;
+            }
+            else {
+                let rest: String = scanner.chars[scanner.position..].iter().take(20).collect();
+                return Err(format!("Unexpected text in DTD: {}", rest));
+            }
+        }
+
+        Ok(())
+    }
+
+    //Reads '<![INCLUDE[ ... ]]>' or skips '<![IGNORE[ ... ]]>', after its '<!['
+    fn conditional_section(&mut self, scanner: &mut Scanner, base_directory: Option<&Path>) -> Result<(), String> {
+        scanner.skip_space();
+
+        let keyword = if scanner.eat("%") {
+            let name = scanner.until(";")?;
+            self.parameter_text(&name)?.0.trim().to_owned()
+        }
+        else {
+            scanner.name()?
+        };
+
+        scanner.skip_space();
+        if !scanner.eat("[") {
+            return Err(format!("Expected '[' after <![{}", keyword));
+        }
+
+        //Sections may be nested, so find the ']]>' which closes this one
+        let start = scanner.position;
+        let mut depth = 1;
+
+        while depth > 0 {
+            if scanner.at_end() {
+                return Err(format!("Unterminated <![{}[ section", keyword));
+            }
+
+            if scanner.eat("<![") {
+                depth += 1;
+            }
+            else if scanner.eat("]]>") {
+                depth -= 1;
+            }
+            else {
+                scanner.position += 1;
+            }
+        }
+
+        let section: String = scanner.chars[start..scanner.position - 3].iter().collect();
+
+        match keyword.as_str() {
+            "INCLUDE" => self.read_subset(&section, base_directory),
+            "IGNORE" => Ok(()),
+            other => Err(format!("Expected INCLUDE or IGNORE but found {}", other)),
+        }
+    }
+
+    fn declaration(&mut self, declaration: &str, base_directory: Option<&Path>) -> Result<(), String> {
+        let keyword: String = declaration.chars().take_while(|c| !c.is_whitespace()).collect();
+        let body = &declaration[keyword.len()..];
+
+        let result = match keyword.as_str() {
+            "ELEMENT" => {
+                let body = self.replace_parameters(body, false)?;
+                self.element(&body)
+            },
+            "ATTLIST" => {
+                let body = self.replace_parameters(body, false)?;
+                self.attribute_list(&body)
+            },
+            "ENTITY" => {
+                let body = self.replace_parameters(body, true)?;
+                self.entity(&body, base_directory)
+            },
+            "NOTATION" => Ok(()),
+            _ => Err("Unknown declaration".to_owned()),
+        };
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("{} in <!{}>", e, declaration.trim())),
+        }
+    }
+
+    fn element(&mut self, body: &str) -> Result<(), String> {
+        let mut scanner = Scanner::new(body);
+        let name = scanner.name()?;
+
+        scanner.skip_space();
+
+        let content = if scanner.eat("EMPTY") {
+            ContentSpec::Empty
+        }
+        else if scanner.eat("ANY") {
+            ContentSpec::Any
+        }
+        else if scanner.starts_with("(") && {
+            //Look past the '(' for '#PCDATA', then go back if it is not there
+            let start = scanner.position;
+            scanner.eat("(");
+            scanner.skip_space();
+            let mixed = scanner.eat("#PCDATA");
+            if !mixed {
+                scanner.position = start;
+            }
+            mixed
+        } {
+            let mut names: Vec<String> = Vec::new();
+            loop {
+                scanner.skip_space();
+
+                if scanner.eat(")") {
+                    break;
+                }
+                if !scanner.eat("|") {
+                    return Err("Expected '|' or ')' after #PCDATA".to_owned());
+                }
+                names.push(scanner.name()?);
+            }
+
+            if !scanner.eat("*") && !names.is_empty() {
+                return Err("Expected '*' after mixed content".to_owned());
+            }
+
+            ContentSpec::Mixed(names)
+        }
+        else {
+            ContentSpec::Children(scanner.content_particle()?)
+        };
+
+        scanner.skip_space();
+        if !scanner.at_end() {
+            return Err("Unexpected text after the content model".to_owned());
+        }
+
+        //An element may only be declared once
+        if self.dtd.elements.contains_key(&name) {
+            return Err(format!("Element {} is declared twice", name));
+        }
+        self.dtd.elements.insert(name, content);
+
+        Ok(())
+    }
+
+    fn attribute_list(&mut self, body: &str) -> Result<(), String> {
+        let mut scanner = Scanner::new(body);
+        let element = scanner.name()?;
+
+        loop {
+            scanner.skip_space();
+            if scanner.at_end() {
+                break;
+            }
+
+            let name = scanner.name()?;
+            scanner.skip_space();
+
+            let kind = if scanner.starts_with("(") || scanner.eat("NOTATION") {
+                scanner.skip_space();
+                if !scanner.eat("(") {
+                    return Err(format!("Expected '(' in the type of attribute {}", name));
+                }
+
+                let mut values: Vec<String> = Vec::new();
+                loop {
+                    values.push(scanner.name()?);
+                    scanner.skip_space();
+
+                    if scanner.eat(")") {
+                        break;
+                    }
+                    if !scanner.eat("|") {
+                        return Err(format!("Expected '|' or ')' in the values of attribute {}", name));
+                    }
+                }
+
+                AttributeType::Enumeration(values)
+            }
+            else {
+                match scanner.name()?.as_str() {
+                    "CDATA" => AttributeType::CData,
+                    "ID" => AttributeType::Id,
+                    "IDREF" => AttributeType::IdRef,
+                    "IDREFS" => AttributeType::IdRefs,
+                    "ENTITY" => AttributeType::Entity,
+                    "ENTITIES" => AttributeType::Entities,
+                    "NMTOKEN" => AttributeType::NmToken,
+                    "NMTOKENS" => AttributeType::NmTokens,
+                    other => return Err(format!("Unknown type {} of attribute {}", other, name)),
+                }
+            };
+
+            scanner.skip_space();
+
+            let default = if scanner.eat("#REQUIRED") {
+                AttributeDefault::Required
+            }
+            else if scanner.eat("#IMPLIED") {
+                AttributeDefault::Implied
+            }
+            else if scanner.eat("#FIXED") {
+                AttributeDefault::Fixed(scanner.quoted()?)
+            }
+            else {
+                AttributeDefault::Value(scanner.quoted()?)
+            };
+
+            //The first definition of an attribute is the one used
+            let definitions = self.dtd.attributes.entry(element.clone()).or_default();
+            if !definitions.iter().any(|definition| definition.name == name) {
+                definitions.push(AttributeDefinition {
+                    name,
+                    kind,
+                    default,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn entity(&mut self, body: &str, base_directory: Option<&Path>) -> Result<(), String> {
+        let mut scanner = Scanner::new(body);
+        scanner.skip_space();
+
+        let parameter = scanner.starts_with("%") && scanner.chars.get(scanner.position + 1).is_some_and(|c| c.is_whitespace());
+        if parameter {
+            scanner.eat("%");
+        }
+
+        let name = scanner.name()?;
+        scanner.skip_space();
+
+        let entity = if scanner.peek() == Some('"') || scanner.peek() == Some('\'') {
+            Entity::Internal(replace_character_references(&scanner.quoted()?))
+        }
+        else {
+            let system_id = match scanner.name()?.as_str() {
+                "SYSTEM" => scanner.quoted()?,
+                "PUBLIC" => {
+                    scanner.quoted()?;
+                    scanner.quoted()?
+                },
+                other => return Err(format!("Expected a value, SYSTEM or PUBLIC but found {}", other)),
+            };
+
+            scanner.skip_space();
+            let unparsed = scanner.eat("NDATA");
+
+            match self.directory {
+                Some(directory) if !unparsed && is_local(&system_id) => {
+                    Entity::External(resolve_system_id(&system_id, base_directory.unwrap_or(directory), directory)?)
+                },
+                _ => Entity::Unparsed,
+            }
+        };
+
+        //The first declaration of an entity is the one used, so the internal subset overrides external files
+        let entities = if parameter { &mut self.dtd.parameter_entities } else { &mut self.dtd.entities };
+        entities.entry(name).or_insert(entity);
+
+        Ok(())
+    }
+}
+
+//Reads the '<!DOCTYPE>' at the start of a file, stopping at the first element so that only the prolog is read
+pub fn read_doctype<I: Iterator<Item = Result<char, String>>>(chars: I) -> Result<Option<Doctype>, String> {
+    let mut markup: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut bracket_depth = 0;
+
+    for c in chars {
+        let c = c?;
+
+        let complete = match markup {
+            None => {
+                if c == '<' {
+                    markup = Some(String::new());
+                }
+                continue;
+            },
+            Some(ref mut markup) => {
+                let complete = if markup.is_empty() && c != '!' && c != '?' {
+                    //Any other markup is an element, which comes after any document type declaration
+                    return Ok(None);
+                }
+                else if markup.starts_with("!--") {
+                    c == '>' && markup.len() >= 5 && markup.ends_with("--")
+                }
+                else if markup.starts_with('?') {
+                    c == '>' && markup.ends_with('?')
+                }
+                else if let Some(q) = quote {
+                    if c == q {
+                        quote = None;
+                    }
+                    false
+                }
+                else {
+                    match c {
+                        '"' | '\'' if markup.starts_with("!DOCTYPE") => { quote = Some(c); false },
+                        '[' | ']' => { bracket_depth = declaration_depth(bracket_depth, c)?; false },
+                        '>' => bracket_depth == 0,
+                        _ => false,
+                    }
+                };
+
+                if !complete {
+                    markup.push(c);
+                }
+
+                complete
+            },
+        };
+
+        if complete {
+            let text = markup.take().unwrap_or_default();
+
+            if let Some(doctype) = text.strip_prefix("!DOCTYPE") {
+                return parse_doctype(doctype).map(Some);
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+//Reads the inside of a '<!DOCTYPE ...>' after the keyword
+fn parse_doctype(text: &str) -> Result<Doctype, String> {
+    let mut scanner = Scanner::new(text);
+
+    let name = match scanner.name() {
+        Ok(name) => name,
+        Err(e) => return Err(format!("{} in <!DOCTYPE>", e)),
+    };
+    scanner.skip_space();
+
+    let (public_id, system_id) = if scanner.eat("SYSTEM") {
+        (None, Some(scanner.quoted()?))
+    }
+    else if scanner.eat("PUBLIC") {
+        (Some(scanner.quoted()?), Some(scanner.quoted()?))
+    }
+    else {
+        (None, None)
+    };
+
+    scanner.skip_space();
+
+    let internal_subset = if scanner.peek() == Some('[') {
+        let rest: String = scanner.chars[scanner.position + 1..].iter().collect();
+
+        match rest.rfind(']') {
+            Some(end) => rest[..end].to_owned(),
+            None => return Err("Expected ']' at the end of the internal subset of <!DOCTYPE>".to_owned()),
+        }
+    }
+    else {
+        String::new()
+    };
+
+    Ok(Doctype {
+        name,
+        public_id,
+        system_id,
+        internal_subset,
+    })
+}
+
+impl Dtd {
+    fn new(root_name: Option<String>) -> Dtd {
+        Dtd {
+            root_name,
+            elements: HashMap::new(),
+            attributes: HashMap::new(),
+            entities: HashMap::new(),
+            parameter_entities: HashMap::new(),
+            missing_files: Vec::new(),
+        }
+    }
+
+    //Reads the declarations of a document type: its internal subset, then its external file if that is on this computer.
+    //External files, including those of entities, are only read from inside 'directory', the directory of the document,
+    //and not at all without one.
+    pub fn load(doctype: &Doctype, directory: Option<&Path>) -> Result<Dtd, String> {
+        let mut dtd = Dtd::new(Some(doctype.name.clone()));
+
+        {
+            let mut reader = DtdReader { dtd: &mut dtd, depth: 0, directory };
+
+            reader.read_subset(&doctype.internal_subset, directory)?;
+
+            if let (Some(ref system_id), Some(directory)) = (&doctype.system_id, directory) {
+                let path = resolve_system_id(system_id, directory, directory)?;
+
+                //A file which was not sent along with the document is left out, so that it can still be read
+                if is_local(system_id) && !path.exists() {
+                    reader.dtd.missing_files.push(path);
+                }
+                else if is_local(system_id) {
+                    let text = read_external(&path)?;
+
+                    if let Err(e) = reader.read_subset(&text, path.parent()) {
+                        return Err(format!("{} in {}", e, path.display()));
+                    }
+                }
+            }
+        }
+
+        Ok(dtd)
+    }
+
+    //Reads a DTD from its text, such as an external file, whose entities are only read from files inside 'directory'
+    pub fn parse(text: &str, directory: Option<&Path>) -> Result<Dtd, String> {
+        let mut dtd = Dtd::new(None);
+
+        DtdReader { dtd: &mut dtd, depth: 0, directory }.read_subset(text, directory)?;
+
+        Ok(dtd)
+    }
+
+    //External DTD files the document type declaration names which could not be found, and so were left out
+    pub fn missing_files(&self) -> &[PathBuf] {
+        &self.missing_files
+    }
+
+    //Whether the DTD declares any general entities, which the text of a document must be read through 'EntityExpander' to replace
+    pub fn has_entities(&self) -> bool {
+        !self.entities.is_empty()
+    }
+
+    //Replaces references to declared entities in some text, leaving others such as '&amp;' to be read as XML
+    fn replace_entities(&self, text: &str, depth: usize) -> Result<String, String> {
+        let mut replaced = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find('&') {
+            replaced.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let end = rest.find(';').unwrap_or(0);
+
+            match self.replacement_text(&rest[1..end.max(1)], depth + 1)? {
+                Some(text) if end > 0 => {
+                    replaced.push_str(&text);
+                    rest = &rest[end + 1..];
+                },
+                _ => {
+                    replaced.push('&');
+                    rest = &rest[1..];
+                },
+            }
+
+            if replaced.len() > MAX_EXPANSION {
+                return Err(format!("Entities expand to more than {} characters", MAX_EXPANSION));
+            }
+        }
+
+        replaced.push_str(rest);
+        Ok(replaced)
+    }
+
+    //The text a reference to an entity is replaced with, or None if the entity is not declared or is not read
+    fn replacement_text(&self, name: &str, depth: usize) -> Result<Option<String>, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("Entity &{}; refers to itself", name));
+        }
+
+        let text = match self.entities.get(name) {
+            Some(Entity::Internal(text)) => text.clone(),
+            Some(Entity::External(path)) => read_external(path)?,
+            Some(&Entity::Unparsed) | None => return Ok(None),
+        };
+
+        self.replace_entities(&text, depth).map(Some)
+    }
+
+    //Adds the default values of attributes declared with one to each element which does not have them
+    pub fn apply_defaults(&self, document: &mut XMLDocument) {
+        let root = document.root();
+        let elements: Vec<NodeId> = document.descendants(root).filter(|&node| document.is_element(node)).collect();
+
+        for node in elements {
+            let definitions = match self.attributes.get(document.name(node)) {
+                Some(definitions) => definitions,
+                None => continue,
+            };
+
+            for definition in definitions {
+                let value = match definition.default {
+                    AttributeDefault::Fixed(ref value) | AttributeDefault::Value(ref value) => value,
+                    _ => continue,
+                };
+
+                if document.attribute(node, &definition.name).is_none() {
+                    let value = decode_entities(&self.replace_entities(value, 0).unwrap_or_else(|_| value.clone()));
+                    document.set_attribute(node, &definition.name, &value);
+                }
+            }
+        }
+    }
+
+    //Checks each element of a document against its declaration in the DTD, and each ID reference against the IDs found.
+    //No problems means the document is valid.
+    pub fn validate(&self, document: &XMLDocument) -> Vec<ValidationError> {
+        let mut errors: Vec<ValidationError> = Vec::new();
+        let mut ids: HashMap<String, NodeId> = HashMap::new();
+        let mut references: Vec<(NodeId, String, String)> = Vec::new();
+
+        let root = document.root();
+
+        if let Some(ref root_name) = self.root_name {
+            match document.child_elements(root).first() {
+                Some(&node) if document.name(node) != root_name => {
+                    errors.push(ValidationError::new(document, node, format!("The root element is '{}', but the document type declares '{}'", document.name(node), root_name)));
+                },
+                _ => {},
+            }
+        }
+
+        for node in document.descendants(root) {
+            if !document.is_element(node) {
+                continue;
+            }
+
+            let name = document.name(node);
+
+            match self.elements.get(name) {
+                Some(content) => self.check_content(document, node, content, &mut errors),
+                None => errors.push(ValidationError::new(document, node, format!("Element '{}' is not declared in the DTD", name))),
+            }
+
+            self.check_attributes(document, node, &mut ids, &mut references, &mut errors);
+        }
+
+        for (node, attribute, id) in references {
+            if !ids.contains_key(&id) {
+                errors.push(ValidationError::new(document, node, format!("Attribute '{}' of '{}' refers to the ID '{}', which no element has", attribute, document.name(node), id)));
+            }
+        }
+
+        sort_by_position(&mut errors);
+        errors
+    }
+
+    fn check_content(&self, document: &XMLDocument, node: NodeId, content: &ContentSpec, errors: &mut Vec<ValidationError>) {
+        let name = document.name(node);
+        let children = document.child_elements(node);
+        let has_text = document.has_text(node);
+
+        match *content {
+            ContentSpec::Any => {},
+            ContentSpec::Empty => {
+                if has_text || !children.is_empty() {
+                    errors.push(ValidationError::new(document, node, format!("Element '{}' is declared EMPTY, but has content", name)));
+                }
+            },
+            ContentSpec::Mixed(ref names) => {
+                for child in children {
+                    if !names.iter().any(|allowed| allowed == document.name(child)) {
+                        errors.push(ValidationError::new(document, child, format!("Element '{}' is not allowed in '{}'", document.name(child), name)));
+                    }
+                }
+            },
+            ContentSpec::Children(ref particle) => {
+                if has_text {
+                    errors.push(ValidationError::new(document, node, format!("Element '{}' cannot contain text", name)));
+                }
+
+                let names: Vec<&str> = children.iter().map(|&child| document.name(child)).collect();
+
+                match check_content_model(names.len(), |length, expected| match_particle(particle, &names[..length], &[0], expected)) {
+                    Some(ContentProblem::Incomplete(expected)) => {
+                        errors.push(ValidationError::new(document, node, format!("Element '{}' is incomplete, {}", name, expected)));
+                    },
+                    Some(ContentProblem::Unexpected(index, expected)) => {
+                        errors.push(ValidationError::new(document, children[index], format!("Element '{}' is not expected here in '{}', {}", names[index], name, expected)));
+                    },
+                    None => {},
+                }
+            },
+        }
+    }
+
+    fn check_attributes(&self, document: &XMLDocument, node: NodeId, ids: &mut HashMap<String, NodeId>, references: &mut Vec<(NodeId, String, String)>, errors: &mut Vec<ValidationError>) {
+        let element = document.name(node);
+        let no_definitions: Vec<AttributeDefinition> = Vec::new();
+        let definitions = self.attributes.get(element).unwrap_or(&no_definitions);
+
+        for (name, value) in document.attributes(node) {
+            let definition = match definitions.iter().find(|definition| definition.name == name) {
+                Some(definition) => definition,
+                //Namespace declarations are allowed without being declared
+                None if declared_prefix(name).is_some() => continue,
+                None => {
+                    errors.push(ValidationError::new(document, node, format!("Attribute '{}' of '{}' is not declared in the DTD", name, element)));
+                    continue;
+                },
+            };
+
+            //Values other than CDATA are compared with their spaces collapsed
+            let normalized = if definition.kind == AttributeType::CData {
+                value.to_owned()
+            }
+            else {
+                value.split_whitespace().collect::<Vec<&str>>().join(" ")
+            };
+
+            let problem = match definition.kind {
+                AttributeType::CData => None,
+                AttributeType::Id => {
+                    if !is_name(&normalized) {
+                        Some(format!("'{}' is not a valid ID", normalized))
+                    }
+                    else if ids.contains_key(&normalized) {
+                        Some(format!("The ID '{}' is used more than once", normalized))
+                    }
+                    else {
+                        ids.insert(normalized.clone(), node);
+                        None
+                    }
+                },
+                AttributeType::IdRef | AttributeType::IdRefs => {
+                    let single = definition.kind == AttributeType::IdRef;
+
+                    if normalized.split(' ').all(is_name) && (!single || !normalized.contains(' ')) {
+                        for id in normalized.split(' ') {
+                            references.push((node, name.to_owned(), id.to_owned()));
+                        }
+                        None
+                    }
+                    else {
+                        Some(format!("'{}' is not a valid {}", normalized, if single { "IDREF" } else { "IDREFS" }))
+                    }
+                },
+                AttributeType::Entity | AttributeType::Entities => {
+                    let single = definition.kind == AttributeType::Entity;
+                    let all_unparsed = normalized.split(' ').all(|entity| matches!(self.entities.get(entity), Some(&Entity::Unparsed)));
+
+                    if all_unparsed && (!single || !normalized.contains(' ')) {
+                        None
+                    }
+                    else {
+                        Some(format!("'{}' does not name an unparsed entity", normalized))
+                    }
+                },
+                AttributeType::NmToken | AttributeType::NmTokens => {
+                    let single = definition.kind == AttributeType::NmToken;
+
+                    if !normalized.is_empty() && normalized.split(' ').all(|token| token.chars().all(is_name_char)) && (!single || !normalized.contains(' ')) {
+                        None
+                    }
+                    else {
+                        Some(format!("'{}' is not a valid {}", normalized, if single { "NMTOKEN" } else { "NMTOKENS" }))
+                    }
+                },
+                AttributeType::Enumeration(ref values) => {
+                    if values.contains(&normalized) {
+                        None
+                    }
+                    else {
+                        Some(format!("'{}' is not one of {}", normalized, values.join(", ")))
+                    }
+                },
+            };
+
+            let problem = problem.or_else(|| match definition.default {
+                AttributeDefault::Fixed(ref fixed) if decode_entities(fixed) != normalized => Some(format!("The value must be '{}'", fixed)),
+                _ => None,
+            });
+
+            if let Some(problem) = problem {
+                errors.push(ValidationError::new(document, node, format!("Invalid value of attribute '{}' of '{}': {}", name, element, problem)));
+            }
+        }
+
+        for definition in definitions {
+            if let AttributeDefault::Required = definition.default {
+                if document.attribute(node, &definition.name).is_none() {
+                    errors.push(ValidationError::new(document, node, format!("Missing required attribute '{}' on '{}'", definition.name, element)));
+                }
+            }
+        }
+    }
+}
+
+//Finds every position in 'names' a content particle can end at, starting from any of 'starts'.
+//The names of elements which could have come next when the names ran out are added to 'expected'.
+fn match_particle(particle: &ContentParticle, names: &[&str], starts: &[usize], expected: &mut Vec<String>) -> Vec<usize> {
+    match_repeated(particle.min_occurs, particle.max_occurs, starts, |current| match particle.term {
+        ContentTerm::Name(ref name) => {
+            let mut next: Vec<usize> = Vec::new();
+
+            for &start in current {
+                if start == names.len() {
+                    expected.push(format!("'{}'", name));
+                }
+                else if names[start] == name {
+                    next.push(start + 1);
+                }
+            }
+
+            next
+        },
+        ContentTerm::Sequence(ref particles) => {
+            let mut positions = current.to_vec();
+
+            for particle in particles {
+                if positions.is_empty() {
+                    break;
+                }
+                positions = match_particle(particle, names, &positions, expected);
+            }
+
+            positions
+        },
+        ContentTerm::Choice(ref particles) => particles.iter()
+            .flat_map(|particle| match_particle(particle, names, current, expected))
+            .collect(),
+    })
+}
+
+//Replaces references to the entities of a DTD as the characters of a file are read, so that entities holding markup are read as markup.
+//References are only replaced in text and attribute values, never in comments, CDATA sections or processing instructions.
+pub struct EntityExpander<'a, I: Iterator<Item = Result<char, String>>> {
+    chars: I,
+    dtd: &'a Dtd,
+    //Characters ready to be read, which have already been expanded
+    pending: VecDeque<char>,
+    //A character read while looking for the end of a reference, which has not been looked at yet
+    lookahead: Option<char>,
+    //The markup read since its '<', if inside one
+    markup: Option<String>,
+    quote: Option<char>,
+    bracket_depth: usize,
+}
+
+impl<'a, I: Iterator<Item = Result<char, String>>> EntityExpander<'a, I> {
+    pub fn new(chars: I, dtd: &'a Dtd) -> EntityExpander<'a, I> {
+        EntityExpander {
+            chars,
+            dtd,
+            pending: VecDeque::new(),
+            lookahead: None,
+            markup: None,
+            quote: None,
+            bracket_depth: 0,
+        }
+    }
+
+    //Whether an entity reference at this point should be replaced
+    fn in_replaceable_text(&self) -> bool {
+        match self.markup {
+            None => true,
+            //Attribute values of start tags
+            Some(ref markup) => self.quote.is_some() && !markup.starts_with('!') && !markup.starts_with('?'),
+        }
+    }
+
+    //Follows where a character is, in the same way as the lexer
+    fn track(&mut self, c: char) -> Result<(), String> {
+        let complete = match self.markup {
+            None => {
+                if c == '<' {
+                    self.markup = Some(String::new());
+                }
+                return Ok(());
+            },
+            Some(ref mut markup) => {
+                let complete = if markup.starts_with("!--") {
+                    c == '>' && markup.len() >= 5 && markup.ends_with("--")
+                }
+                else if markup.starts_with("![CDATA[") {
+                    c == '>' && markup.len() >= 10 && markup.ends_with("]]")
+                }
+                else if markup.starts_with('?') {
+                    c == '>' && markup.ends_with('?')
+                }
+                else if let Some(quote) = self.quote {
+                    if c == quote {
+                        self.quote = None;
+                    }
+                    false
+                }
+                else {
+                    match c {
+                        '"' | '\'' => { self.quote = Some(c); false },
+                        '[' | ']' if markup.starts_with('!') => { self.bracket_depth = declaration_depth(self.bracket_depth, c)?; false },
+                        '>' => self.bracket_depth == 0,
+                        _ => false,
+                    }
+                };
+
+                if !complete {
+                    markup.push(c);
+                }
+
+                complete
+            },
+        };
+
+        if complete {
+            self.markup = None;
+            self.bracket_depth = 0;
+        }
+
+        Ok(())
+    }
+
+    fn next_char(&mut self) -> Option<Result<char, String>> {
+        match self.lookahead.take() {
+            Some(c) => Some(Ok(c)),
+            None => self.chars.next(),
+        }
+    }
+
+    fn push(&mut self, text: &str) -> Result<(), String> {
+        for c in text.chars() {
+            self.track(c)?;
+            self.pending.push_back(c);
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, I: Iterator<Item = Result<char, String>>> Iterator for EntityExpander<'a, I> {
+    type Item = Result<char, String>;
+
+    fn next(&mut self) -> Option<Result<char, String>> {
+        if let Some(c) = self.pending.pop_front() {
+            return Some(Ok(c));
+        }
+
+        let c = match self.next_char()? {
+            Ok(c) => c,
+            Err(e) => return Some(Err(e)),
+        };
+
+        if c != '&' || !self.in_replaceable_text() {
+            return Some(self.track(c).map(|_| c));
+        }
+
+        //Read the name of the reference up to its ';'
+        let mut name = String::new();
+        let mut terminated = false;
+
+        loop {
+            match self.next_char() {
+                Some(Ok(';')) => {
+                    terminated = true;
+                    break;
+                },
+                Some(Ok(c)) if (is_name_char(c) || c == '#') && name.len() < 256 => name.push(c),
+                Some(Ok(c)) => {
+                    self.lookahead = Some(c);
+                    break;
+                },
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            }
+        }
+
+        let replacement = if terminated {
+            match self.dtd.replacement_text(&name, 0) {
+                Ok(replacement) => replacement,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        else {
+            None
+        };
+
+        let pushed = match replacement {
+            Some(text) => self.push(&text),
+            None => {
+                let reference = if terminated { format!("&{};", name) } else { format!("&{}", name) };
+                self.push(&reference)
+            },
+        };
+
+        match pushed {
+            Ok(_) => self.next(),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {lexer, parser};
+
+    const DOCTYPE: &str = "<!DOCTYPE catalog [
+  <!ENTITY company \"Acme &amp; Sons\">
+  <!ELEMENT catalog (cd+, note?)>
+  <!ELEMENT cd (#PCDATA)>
+  <!ELEMENT note EMPTY>
+  <!ATTLIST cd id ID #REQUIRED format (CD|LP) \"CD\">
+]>";
+
+    //Reads the document type declaration at the start of 'xml' into a DTD
+    fn dtd(xml: &str) -> Dtd {
+        let doctype = read_doctype(xml.chars().map(Ok)).unwrap().unwrap();
+        Dtd::load(&doctype, None).unwrap()
+    }
+
+    //Reads 'xml' with its entities replaced by the DTD
+    fn expand(xml: &str, dtd: &Dtd) -> Result<String, String> {
+        EntityExpander::new(xml.chars().map(Ok), dtd).collect()
+    }
+
+    #[test]
+    fn entities_are_replaced_in_text_and_attribute_values() {
+        let dtd = dtd(DOCTYPE);
+        let xml = format!("{}<catalog><cd id=\"a\" by=\"&company;\">&company;<!-- &company; --></cd></catalog>", DOCTYPE);
+
+        let expanded = expand(&xml, &dtd).unwrap();
+        assert!(expanded.ends_with("<catalog><cd id=\"a\" by=\"Acme &amp; Sons\">Acme &amp; Sons<!-- &company; --></cd></catalog>"), "{}", expanded);
+    }
+
+    #[test]
+    fn validation_reports_content_models_and_attributes() {
+        let dtd = dtd(DOCTYPE);
+        let mut document = parser(&lexer("<catalog><cd id=\"a\">x</cd><cd id=\"a\"/><note>text</note><cd/></catalog>".to_owned()).unwrap()).unwrap();
+        dtd.apply_defaults(&mut document);
+
+        let catalog = document.child_elements(document.root())[0];
+        assert_eq!(document.attribute(document.child_elements(catalog)[0], "format"), Some("CD"));
+
+        let errors: Vec<String> = dtd.validate(&document).into_iter().map(|error| error.message).collect();
+        assert_eq!(errors, vec![
+            "Element 'cd' is not expected here in 'catalog', expected no more elements",
+            "Invalid value of attribute 'id' of 'cd': The ID 'a' is used more than once",
+            "Element 'note' is declared EMPTY, but has content",
+            "Missing required attribute 'id' on 'cd'",
+        ]);
+    }
+
+    #[test]
+    fn unmatched_brackets_in_declarations_are_an_error() {
+        assert_eq!(read_doctype("<!DOCTYPE a ]><a/>".chars().map(Ok)).err(), Some("Unmatched ']' in declaration".to_owned()));
+
+        let dtd = dtd(DOCTYPE);
+        assert_eq!(expand("<!DOCTYPE a ]><a/>", &dtd).err(), Some("Unmatched ']' in declaration".to_owned()));
+        assert!(expand("<a><![CDATA[ ]] ]]></a>", &dtd).is_ok());
+    }
+}
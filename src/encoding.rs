@@ -0,0 +1,621 @@
+//
+//  Description  :    Detects the character encoding of input files and transcodes them to UTF-8
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::io::BufRead;
+use std::collections::VecDeque;
+
+//Characters for the bytes 0x80 to 0x9F in Windows-1252, which differ from Latin-1
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum Encoding {
+    UTF8,
+    UTF16LE,
+    UTF16BE,
+    Windows1252,
+    Latin1,
+}
+
+impl Encoding {
+    //Finds an encoding from a name such as those used in XML declarations
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" | "us-ascii" | "ascii" => Some(Encoding::UTF8),
+            "utf-16" | "utf-16le" | "utf16" | "utf16le" => Some(Encoding::UTF16LE),
+            "utf-16be" | "utf16be" => Some(Encoding::UTF16BE),
+            "windows-1252" | "cp1252" => Some(Encoding::Windows1252),
+            "iso-8859-1" | "latin-1" | "latin1" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Encoding::UTF8 => "UTF-8",
+            Encoding::UTF16LE => "UTF-16LE",
+            Encoding::UTF16BE => "UTF-16BE",
+            Encoding::Windows1252 => "windows-1252",
+            Encoding::Latin1 => "ISO-8859-1",
+        }
+    }
+
+    //The name written in XML declarations, where the byte order mark gives the byte order of UTF-16
+    pub fn xml_name(&self) -> &'static str {
+        match *self {
+            Encoding::UTF16LE | Encoding::UTF16BE => "UTF-16",
+            _ => self.name(),
+        }
+    }
+}
+
+//Finds the bytes of the byte order mark for an encoding
+fn bom_bytes(encoding: Encoding) -> &'static [u8] {
+    match encoding {
+        Encoding::UTF8 => &[0xEF, 0xBB, 0xBF],
+        Encoding::UTF16LE => &[0xFF, 0xFE],
+        Encoding::UTF16BE => &[0xFE, 0xFF],
+        Encoding::Windows1252 | Encoding::Latin1 => &[],
+    }
+}
+
+//Finds the encoding given by a byte order mark, along with the length of the mark
+fn detect_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        Some((Encoding::UTF8, 3))
+    }
+    else if bytes.starts_with(&[0xFF, 0xFE]) {
+        Some((Encoding::UTF16LE, 2))
+    }
+    else if bytes.starts_with(&[0xFE, 0xFF]) {
+        Some((Encoding::UTF16BE, 2))
+    }
+    else {
+        None
+    }
+}
+
+//Whether a file starts with a byte order mark
+pub fn has_bom(bytes: &[u8]) -> bool {
+    detect_bom(bytes).is_some()
+}
+
+//Finds the 'encoding' given in an XML declaration at the start of the file
+fn declared_encoding(bytes: &[u8]) -> Option<Encoding> {
+    if !bytes.starts_with(b"<?xml") {
+        return None;
+    }
+
+    //The declaration itself is always ASCII
+    let end = bytes.iter().position(|&b| b == b'>')?;
+    let declaration = String::from_utf8_lossy(&bytes[..end]);
+
+    let start = declaration.find("encoding")? + "encoding".len();
+
+    let value: String = declaration[start..].trim_start().trim_start_matches('=').trim_start()
+        .chars()
+        .skip(1)
+        .take_while(|&c| c != '"' && c != '\'')
+        .collect();
+
+    Encoding::from_name(&value)
+}
+
+//Guesses the encoding of a file from its byte order mark, XML declaration or contents
+pub fn detect_encoding(bytes: &[u8]) -> Encoding {
+    if let Some((encoding, _)) = detect_bom(bytes) {
+        return encoding;
+    }
+
+    //UTF-16 files without a byte order mark still start with '<?' in XML
+    if bytes.starts_with(&[0x00, b'<', 0x00, b'?']) {
+        return Encoding::UTF16BE;
+    }
+    if bytes.starts_with(&[b'<', 0x00, b'?', 0x00]) {
+        return Encoding::UTF16LE;
+    }
+
+    if let Some(encoding) = declared_encoding(bytes) {
+        return encoding;
+    }
+
+    //Anything which is not valid UTF-8 is most likely from Excel on Windows
+    match ::std::str::from_utf8(bytes) {
+        Ok(_) => Encoding::UTF8,
+        //A character cut off at the end of a partly read file is still UTF-8
+        Err(ref e) if e.error_len().is_none() => Encoding::UTF8,
+        Err(_) => Encoding::Windows1252,
+    }
+}
+
+fn windows_1252_char(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+//Decodes two-byte units of UTF-16 text
+fn decode_utf16(bytes: &[u8], little_endian: bool) -> Result<String, String> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err("Truncated UTF-16 text".to_owned());
+    }
+
+    let units: Vec<u16> = bytes.chunks(2)
+        .map(|pair| if little_endian { (pair[1] as u16) << 8 | pair[0] as u16 } else { (pair[0] as u16) << 8 | pair[1] as u16 })
+        .collect();
+
+    match String::from_utf16(&units) {
+        Ok(text) => Ok(text),
+        Err(_) => Err("Invalid UTF-16 text".to_owned()),
+    }
+}
+
+//Converts the bytes of a file in the given encoding to a UTF-8 string, removing any byte order mark
+pub fn decode(bytes: &[u8], encoding: Encoding) -> Result<String, String> {
+    let bytes = match detect_bom(bytes) {
+        Some((bom_encoding, length)) if bom_encoding == encoding => &bytes[length..],
+        _ => bytes,
+    };
+
+    match encoding {
+        Encoding::UTF8 => match String::from_utf8(bytes.to_vec()) {
+            Ok(text) => Ok(text),
+            Err(e) => Err(format!("Invalid UTF-8 text at byte {}", e.utf8_error().valid_up_to())),
+        },
+        Encoding::UTF16LE => decode_utf16(bytes, true),
+        Encoding::UTF16BE => decode_utf16(bytes, false),
+        Encoding::Windows1252 => Ok(bytes.iter().map(|&b| windows_1252_char(b)).collect()),
+        Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+    }
+}
+
+//The byte for a character in a single byte encoding, if it has one
+fn single_byte(c: char, encoding: Encoding) -> Option<u8> {
+    let code = c as u32;
+
+    match WINDOWS_1252_HIGH.iter().position(|&high| high == c) {
+        //Windows-1252 replaces the control characters 0x80 to 0x9F
+        Some(index) if encoding == Encoding::Windows1252 => Some(0x80 + index as u8),
+        _ if code < 0x80 || (0xA0..0x100).contains(&code) => Some(code as u8),
+        _ if code < 0x100 && encoding == Encoding::Latin1 => Some(code as u8),
+        _ => None,
+    }
+}
+
+//Converts a string to the bytes of a file in the given encoding, optionally starting with a byte order mark
+pub fn encode(text: &str, encoding: Encoding, bom: bool) -> Result<Vec<u8>, String> {
+    let mut bytes: Vec<u8> = Vec::new();
+
+    if bom {
+        bytes.extend_from_slice(bom_bytes(encoding));
+    }
+
+    match encoding {
+        Encoding::UTF8 => bytes.extend_from_slice(text.as_bytes()),
+        Encoding::UTF16LE => {
+            for unit in text.encode_utf16() {
+                bytes.push((unit & 0xFF) as u8);
+                bytes.push((unit >> 8) as u8);
+            }
+        },
+        Encoding::UTF16BE => {
+            for unit in text.encode_utf16() {
+                bytes.push((unit >> 8) as u8);
+                bytes.push((unit & 0xFF) as u8);
+            }
+        },
+        Encoding::Windows1252 | Encoding::Latin1 => {
+            for c in text.chars() {
+                match single_byte(c, encoding) {
+                    Some(byte) => bytes.push(byte),
+                    None => return Err(format!("Character '{}' cannot be written as {}", c, encoding.name())),
+                }
+            }
+        },
+    }
+
+    Ok(bytes)
+}
+
+//Where XML written in pieces has got to, as character references can only replace characters in text and attribute values
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum Markup {
+    Text,
+    Tag,
+    //An attribute value inside the given quote
+    Value(char),
+    Comment,
+    ProcessingInstruction,
+    CData,
+    //A declaration such as '<!DOCTYPE a [ ... ]>', inside the given depth of '[' and ']'
+    Declaration(usize),
+}
+
+impl Markup {
+    //The part of the XML after the character at the start of 'rest'
+    fn next(self, rest: &str) -> Markup {
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => return self,
+        };
+
+        match self {
+            Markup::Text if c == '<' => {
+                if rest.starts_with("<!--") {
+                    Markup::Comment
+                }
+                else if rest.starts_with("<![CDATA[") {
+                    Markup::CData
+                }
+                else if rest.starts_with("<!") {
+                    Markup::Declaration(0)
+                }
+                else if rest.starts_with("<?") {
+                    Markup::ProcessingInstruction
+                }
+                else {
+                    Markup::Tag
+                }
+            },
+            Markup::Tag if c == '"' || c == '\'' => Markup::Value(c),
+            Markup::Tag if c == '>' => Markup::Text,
+            Markup::Value(quote) if c == quote => Markup::Tag,
+            Markup::Comment if rest.starts_with("-->") => Markup::Text,
+            Markup::ProcessingInstruction if rest.starts_with("?>") => Markup::Text,
+            Markup::CData if rest.starts_with("]]>") => Markup::Text,
+            Markup::Declaration(depth) if c == '[' => Markup::Declaration(depth + 1),
+            Markup::Declaration(depth) if c == ']' && depth > 0 => Markup::Declaration(depth - 1),
+            Markup::Declaration(0) if c == '>' => Markup::Text,
+            markup => markup,
+        }
+    }
+}
+
+//Converts a piece of XML to the bytes of a file in the given encoding. Characters the encoding does not have are
+//written as character references in text and attribute values, and are an error anywhere else.
+pub fn encode_xml(text: &str, encoding: Encoding, markup: &mut Markup) -> Result<Vec<u8>, String> {
+    if encoding != Encoding::Windows1252 && encoding != Encoding::Latin1 {
+        return encode(text, encoding, false);
+    }
+
+    let mut bytes: Vec<u8> = Vec::new();
+
+    for (index, c) in text.char_indices() {
+        *markup = markup.next(&text[index..]);
+
+        match single_byte(c, encoding) {
+            Some(byte) => bytes.push(byte),
+            None if *markup == Markup::Text || matches!(*markup, Markup::Value(_)) => {
+                bytes.extend_from_slice(format!("&#x{:X};", c as u32).as_bytes());
+            },
+            None => return Err(format!("Character '{}' cannot be written as {}", c, encoding.name())),
+        }
+    }
+
+    Ok(bytes)
+}
+
+//Reads the characters of a file in the given encoding one at a time, without reading the whole file
+pub struct CharReader<R: BufRead> {
+    reader: R,
+    encoding: Encoding,
+    started: bool,
+    //Whether UTF-8 was only guessed from the first bytes and no character outside ASCII has been read as UTF-8 yet,
+    //so invalid UTF-8 later on means the file is Windows-1252
+    guessed_utf8: bool,
+    //Bytes read ahead while checking a character, to be read again
+    pending: VecDeque<u8>,
+}
+
+impl<R: BufRead> CharReader<R> {
+    pub fn new(reader: R, encoding: Encoding) -> CharReader<R> {
+        CharReader {
+            reader,
+            encoding,
+            started: false,
+            guessed_utf8: false,
+            pending: VecDeque::new(),
+        }
+    }
+
+    //Starts reading a file in the encoding detected from its first bytes. A file which only looks like UTF-8 there is
+    //read as Windows-1252 from the first byte which is not, as the encoding would have been detected from the whole file.
+    //This only happens while everything read so far is ASCII, which reads the same either way. Once a character has been
+    //read as multibyte UTF-8, a later invalid byte is an error rather than a change of encoding part way through.
+    pub fn with_detected_encoding(mut reader: R) -> Result<CharReader<R>, String> {
+        let (encoding, guessed_utf8) = match reader.fill_buf() {
+            Ok(buffer) => {
+                let encoding = detect_encoding(buffer);
+                (encoding, encoding == Encoding::UTF8 && detect_bom(buffer).is_none() && declared_encoding(buffer).is_none())
+            },
+            Err(e) => return Err(format!("Could not read file: {}", e)),
+        };
+
+        let mut chars = CharReader::new(reader, encoding);
+        chars.guessed_utf8 = guessed_utf8;
+
+        Ok(chars)
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    //Reads bytes which are not UTF-8 as Windows-1252 if the encoding was guessed, or fails
+    fn invalid_utf8(&mut self, bytes: &[u8], message: &str) -> Result<Option<char>, String> {
+        if !self.guessed_utf8 {
+            return Err(message.to_owned());
+        }
+
+        self.encoding = Encoding::Windows1252;
+        self.guessed_utf8 = false;
+
+        for &byte in bytes[1..].iter().rev() {
+            self.pending.push_front(byte);
+        }
+
+        Ok(Some(windows_1252_char(bytes[0])))
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, String> {
+        if let Some(byte) = self.pending.pop_front() {
+            return Ok(Some(byte));
+        }
+
+        let byte = match self.reader.fill_buf() {
+            Ok([]) => return Ok(None),
+            Ok(buffer) => buffer[0],
+            Err(e) => return Err(format!("Could not read file: {}", e)),
+        };
+
+        self.reader.consume(1);
+
+        Ok(Some(byte))
+    }
+
+    fn read_utf16_unit(&mut self) -> Result<Option<u16>, String> {
+        let first = match self.read_byte()? {
+            Some(byte) => byte as u16,
+            None => return Ok(None),
+        };
+
+        let second = match self.read_byte()? {
+            Some(byte) => byte as u16,
+            None => return Err("Truncated UTF-16 text".to_owned()),
+        };
+
+        if self.encoding == Encoding::UTF16LE {
+            Ok(Some(second << 8 | first))
+        }
+        else {
+            Ok(Some(first << 8 | second))
+        }
+    }
+
+    fn read_char(&mut self) -> Result<Option<char>, String> {
+        //Skip any byte order mark at the start of the file
+        if !self.started {
+            self.started = true;
+
+            let bom_length = match self.reader.fill_buf() {
+                Ok(buffer) => match detect_bom(buffer) {
+                    Some((bom_encoding, length)) if bom_encoding == self.encoding => length,
+                    _ => 0,
+                },
+                Err(e) => return Err(format!("Could not read file: {}", e)),
+            };
+
+            self.reader.consume(bom_length);
+        }
+
+        match self.encoding {
+            Encoding::UTF8 => {
+                let first = match self.read_byte()? {
+                    Some(byte) => byte,
+                    None => return Ok(None),
+                };
+
+                //The leading bits of the first byte give the length of the character
+                let length = match first {
+                    0x00..=0x7F => 1,
+                    0xC0..=0xDF => 2,
+                    0xE0..=0xEF => 3,
+                    0xF0..=0xF7 => 4,
+                    _ => return self.invalid_utf8(&[first], "Invalid UTF-8 text"),
+                };
+
+                let mut bytes: Vec<u8> = vec![first];
+                while bytes.len() < length {
+                    match self.read_byte()? {
+                        Some(byte) => bytes.push(byte),
+                        None => return self.invalid_utf8(&bytes, "Truncated UTF-8 text"),
+                    }
+                }
+
+                match ::std::str::from_utf8(&bytes) {
+                    Ok(text) => {
+                        //The file is UTF-8 now that it has a character which could not be read as Windows-1252 too
+                        if length > 1 {
+                            self.guessed_utf8 = false;
+                        }
+
+                        Ok(text.chars().next())
+                    },
+                    Err(_) => self.invalid_utf8(&bytes, "Invalid UTF-8 text"),
+                }
+            },
+            Encoding::UTF16LE | Encoding::UTF16BE => {
+                let mut units: Vec<u16> = match self.read_utf16_unit()? {
+                    Some(unit) => vec![unit],
+                    None => return Ok(None),
+                };
+
+                //Characters outside the basic plane take a surrogate pair
+                if units[0] >= 0xD800 && units[0] < 0xDC00 {
+                    match self.read_utf16_unit()? {
+                        Some(unit) => units.push(unit),
+                        None => return Err("Truncated UTF-16 text".to_owned()),
+                    }
+                }
+
+                match ::std::char::decode_utf16(units).next() {
+                    Some(Ok(c)) => Ok(Some(c)),
+                    _ => Err("Invalid UTF-16 text".to_owned()),
+                }
+            },
+            Encoding::Windows1252 => Ok(self.read_byte()?.map(windows_1252_char)),
+            Encoding::Latin1 => Ok(self.read_byte()?.map(|b| b as char)),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for CharReader<R> {
+    type Item = Result<char, String>;
+
+    fn next(&mut self) -> Option<Result<char, String>> {
+        match self.read_char() {
+            Ok(Some(c)) => Some(Ok(c)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn detects_byte_order_marks_and_utf16_without_one() {
+        assert_eq!(detect_encoding(&[0xEF, 0xBB, 0xBF, b'a']), Encoding::UTF8);
+        assert_eq!(detect_encoding(&[0xFE, 0xFF, 0x00, b'a']), Encoding::UTF16BE);
+        assert_eq!(detect_encoding(&[b'<', 0x00, b'?', 0x00, b'x', 0x00]), Encoding::UTF16LE);
+    }
+
+    #[test]
+    fn detects_the_declared_encoding() {
+        assert_eq!(detect_encoding(b"<?xml version=\"1.0\" encoding='ISO-8859-1'?><a/>"), Encoding::Latin1);
+    }
+
+    #[test]
+    fn invalid_utf8_is_read_as_windows_1252() {
+        let bytes = b"caf\xE9 \x80";
+
+        assert_eq!(detect_encoding(bytes), Encoding::Windows1252);
+        assert_eq!(decode(bytes, Encoding::Windows1252).unwrap(), "café €");
+    }
+
+    #[test]
+    fn reads_utf16_one_character_at_a_time_without_the_byte_order_mark() {
+        let bytes: &[u8] = &[0xFF, 0xFE, b'h', 0x00, 0xE9, 0x00, 0x3D, 0xD8, 0x00, 0xDE];
+        let text: Result<String, String> = CharReader::new(bytes, Encoding::UTF16LE).collect();
+
+        assert_eq!(text.unwrap(), "hé\u{1F600}");
+    }
+
+    #[test]
+    fn encodes_with_a_byte_order_mark() {
+        assert_eq!(encode("a€", Encoding::UTF16BE, true).unwrap(), vec![0xFE, 0xFF, 0x00, b'a', 0x20, 0xAC]);
+        assert_eq!(encode("é€", Encoding::Windows1252, false).unwrap(), vec![0xE9, 0x80]);
+    }
+
+    #[test]
+    fn characters_outside_the_encoding_are_an_error() {
+        assert!(encode("€", Encoding::Latin1, false).is_err());
+    }
+
+    //Encodes XML as ISO-8859-1 in one piece
+    fn latin1_xml(xml: &str) -> Result<String, String> {
+        encode_xml(xml, Encoding::Latin1, &mut Markup::Text).map(|bytes| decode(&bytes, Encoding::Latin1).unwrap())
+    }
+
+    #[test]
+    fn xml_text_and_values_get_character_references() {
+        assert_eq!(latin1_xml("<a b=\"€\" c='é'>€ ok</a>").unwrap(), "<a b=\"&#x20AC;\" c='é'>&#x20AC; ok</a>");
+        assert_eq!(latin1_xml("<!DOCTYPE a [<!ENTITY e \"x\">]><a>\u{1F600}</a>").unwrap(), "<!DOCTYPE a [<!ENTITY e \"x\">]><a>&#x1F600;</a>");
+    }
+
+    #[test]
+    fn xml_names_and_comments_cannot_be_referenced() {
+        assert!(latin1_xml("<€/>").is_err());
+        assert!(latin1_xml("<a><!-- € --></a>").is_err());
+        assert!(latin1_xml("<?p €?>").is_err());
+    }
+
+    #[test]
+    fn markup_carries_on_between_pieces() {
+        let mut markup = Markup::Text;
+
+        assert_eq!(encode_xml("<a b=\"", Encoding::Windows1252, &mut markup).unwrap(), b"<a b=\"".to_vec());
+        assert_eq!(encode_xml("\u{2260}\">", Encoding::Windows1252, &mut markup).unwrap(), b"&#x2260;\">".to_vec());
+        assert!(encode_xml("<\u{2260}/>", Encoding::Windows1252, &mut markup).is_err());
+    }
+
+    //Reads bytes through a small buffer, as a file would be, so detection only sees the first part
+    fn read_detected(bytes: &[u8]) -> Result<String, String> {
+        CharReader::with_detected_encoding(BufReader::with_capacity(8192, bytes))?.collect()
+    }
+
+    #[test]
+    fn windows_1252_after_the_first_buffer_is_detected() {
+        let mut bytes = vec![b'a'; 20000];
+        bytes.extend_from_slice(b"caf\xE9 \x80");
+
+        let text = read_detected(&bytes).unwrap();
+        assert_eq!(text.len(), 20000 + "café €".len());
+        assert!(text.ends_with("café €"));
+    }
+
+    #[test]
+    fn bytes_read_ahead_of_an_invalid_character_are_kept() {
+        let mut bytes = vec![b'a'; 10000];
+        bytes.extend_from_slice(b"\xC3x\xE2\x80y");
+
+        assert!(read_detected(&bytes).unwrap().ends_with("Ãxâ€y"));
+    }
+
+    #[test]
+    fn invalid_utf8_after_utf8_characters_is_an_error() {
+        let mut bytes = vec![b'a'; 10000];
+        bytes.extend_from_slice("café".as_bytes());
+        bytes.extend_from_slice(b" \x80");
+
+        assert_eq!(read_detected(&bytes).unwrap_err(), "Invalid UTF-8 text");
+    }
+
+    #[test]
+    fn utf8_split_across_buffers_is_still_utf8() {
+        let mut bytes = vec![b'a'; 8191];
+        bytes.extend_from_slice("é€".as_bytes());
+
+        assert!(read_detected(&bytes).unwrap().ends_with("é€"));
+    }
+
+    #[test]
+    fn declared_utf8_does_not_fall_back() {
+        let mut bytes = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>".to_vec();
+        bytes.extend(vec![b'a'; 10000]);
+        bytes.extend_from_slice(b"\xE9xy");
+
+        assert_eq!(read_detected(&bytes), Err("Invalid UTF-8 text".to_owned()));
+    }
+
+    #[test]
+    fn given_utf8_does_not_fall_back() {
+        let chars = CharReader::new(&b"caf\xE9"[..], Encoding::UTF8);
+        assert!(chars.collect::<Result<String, String>>().is_err());
+    }
+}
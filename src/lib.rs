@@ -0,0 +1,1831 @@
+//
+//  Description  :    An XML/CSV parser and formatter library
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::iter::Peekable;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub mod canonical;
+pub mod column_mapping;
+pub mod css_selector;
+pub mod csv_sniffer;
+pub mod csv_stream;
+pub mod csv_types;
+pub mod document;
+pub mod dtd;
+pub mod encoding;
+pub mod lossless;
+pub mod namespaces;
+pub mod pattern;
+pub mod pull;
+pub mod relaxng;
+pub mod sax;
+pub mod selection;
+pub mod tokenizer;
+pub mod validation;
+pub mod xml_stream;
+pub mod xml_writer;
+pub mod xpath;
+pub mod xsd;
+pub mod xsd_inference;
+
+use canonical::{CanonicalOptions, canonicalize};
+use column_mapping::{ColumnMapping, mapped_csv_formatter};
+use csv_sniffer::{CSVDialect, LineEnding, sniff_csv, SNIFF_SAMPLE_LINES};
+use encoding::{Encoding, CharReader, Markup, detect_encoding, decode, encode, encode_xml, has_bom};
+use lossless::LosslessDocument;
+use csv_stream::csv_to_xml_stream;
+use csv_types::{ColumnType, infer_column_types};
+use document::{XMLDocument, NodeId, NodeKind, Position};
+use dtd::{Dtd, EntityExpander, read_doctype};
+use namespaces::{NamespaceMap, declared_prefix, column_name, qualify_names};
+use pull::{XMLEvent, EventReader};
+use relaxng::RelaxNG;
+use xml_stream::{TermReader, xml_to_csv_stream};
+use xpath::{XPathItem, item_string};
+use xml_writer::write_node;
+use selection::Selection;
+use tokenizer::Tokenizer;
+use validation::ValidationError;
+use xsd::Schema;
+use xsd_inference::{SchemaInference, csv_schema};
+
+trait Stack<T> {
+    fn top(&mut self) -> &mut T;
+}
+
+impl<T> Stack<T> for Vec<T> {
+    fn top(&mut self) -> &mut T {
+        match self.len() {
+            0 => panic!("Error: Vector does not have any elements!"),
+            n => &mut self[n - 1],
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum XMLTerm {
+    OpeningTag(String),
+    ClosingTag(String),
+    Text(String),
+    Attribute(String, String),
+    Comment(String),
+    ProcessingInstruction(String, String),
+    BlankLine,
+    None,
+}
+
+impl XMLTerm {
+    fn get_string(&mut self) -> Option<&mut String> {
+        match *self {
+            XMLTerm::OpeningTag(ref mut s) => Some(s),
+            XMLTerm::ClosingTag(ref mut s) => Some(s),
+            XMLTerm::Text(ref mut s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+//Finds the character for an entity reference such as 'amp' or '#x20'
+fn entity_char(name: &str) -> Option<char> {
+    let code = if let Some(hex) = name.strip_prefix("#x") {
+        u32::from_str_radix(hex, 16).ok()
+    }
+    else if let Some(decimal) = name.strip_prefix('#') {
+        decimal.parse::<u32>().ok()
+    }
+    else {
+        return match name {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => None,
+        };
+    };
+
+    code.and_then(::std::char::from_u32)
+}
+
+//Replaces entity references such as '&amp;' with their characters, leaving unknown references as they are
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let replacement = match rest.find(';') {
+            Some(end) => entity_char(&rest[1..end]).map(|c| (c, end + 1)),
+            None => None,
+        };
+
+        match replacement {
+            Some((c, length)) => {
+                decoded.push(c);
+                rest = &rest[length..];
+            },
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+//Replaces the characters which cannot appear in XML text with entity references
+pub fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+//Replaces the characters which cannot appear in a quoted XML attribute value with entity references
+pub fn escape_attribute(value: &str) -> String {
+    escape_text(value).replace('"', "&quot;")
+}
+
+//Whether the whitespace between two pieces of markup leaves at least one empty line
+pub fn is_blank_line(text: &str) -> bool {
+    text.trim().is_empty() && text.matches('\n').count() >= 2
+}
+
+//Pushes a term to a vector of 'XMLTerm's
+fn push_term(terms: &mut Vec<XMLTerm>, current_term: &mut XMLTerm) -> XMLTerm {
+    //Copy term
+    let mut new_term: XMLTerm = match *current_term {
+        XMLTerm::OpeningTag(ref s) => XMLTerm::OpeningTag(s.trim().to_owned()),
+        XMLTerm::ClosingTag(ref s) => XMLTerm::ClosingTag(s.trim().to_owned()),
+        XMLTerm::Text(ref s) if is_blank_line(s) => XMLTerm::BlankLine,
+        XMLTerm::Text(ref s) => XMLTerm::Text(decode_entities(s.trim())),
+        _ => XMLTerm::None,
+    };
+
+    if new_term == XMLTerm::BlankLine {
+        terms.push(new_term);
+        return XMLTerm::None;
+    }
+
+    //If the content of the term is non-empty, push it
+    if !new_term.get_string().unwrap_or(&mut "".to_owned()).is_empty() {
+        terms.push(new_term.clone());
+    }
+
+    XMLTerm::None
+}
+
+//Converts the inside of an opening tag, such as 'game platform="PC"', into terms
+fn lex_tag(markup: &str, terms: &mut Vec<XMLTerm>) -> Result<(), String> {
+    //A tag such as '<br/>' opens and closes an element
+    let self_closing = markup.ends_with('/');
+    let markup = if self_closing { &markup[..markup.len() - 1] } else { markup };
+
+    let mut chars = markup.trim().chars().peekable();
+
+    let name: String = chars.by_ref().take_while(|c| !c.is_whitespace()).collect();
+    if name.is_empty() {
+        return Err(format!("Missing tag name in <{}>", markup));
+    }
+
+    terms.push(XMLTerm::OpeningTag(name.clone()));
+
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut attribute_name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c.is_whitespace() {
+                break;
+            }
+            attribute_name.push(c);
+            chars.next();
+        }
+
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.next() != Some('=') {
+            return Err(format!("Expected '=' after attribute {} of tag {}", attribute_name, name));
+        }
+
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let quote = match chars.next() {
+            Some(c) if c == '"' || c == '\'' => c,
+            _ => return Err(format!("Expected a quoted value for attribute {} of tag {}", attribute_name, name)),
+        };
+
+        let mut value = String::new();
+        loop {
+            match chars.next() {
+                Some(c) if c == quote => break,
+                Some(c) => value.push(c),
+                None => return Err(format!("Unterminated value for attribute {} of tag {}", attribute_name, name)),
+            }
+        }
+
+        terms.push(XMLTerm::Attribute(attribute_name, decode_entities(&value)));
+    }
+
+    if self_closing {
+        terms.push(XMLTerm::ClosingTag(name));
+    }
+
+    Ok(())
+}
+
+//Converts everything between a '<' and its '>' into terms
+fn lex_markup(markup: &str, terms: &mut Vec<XMLTerm>) -> Result<(), String> {
+    if markup.starts_with("!--") {
+        terms.push(XMLTerm::Comment(markup[3..markup.len() - 2].to_owned()));
+    }
+    else if markup.starts_with("![CDATA[") {
+        //Character data is kept exactly as written
+        let data = &markup[8..markup.len() - 2];
+        if !data.is_empty() {
+            terms.push(XMLTerm::Text(data.to_owned()));
+        }
+    }
+    else if markup.starts_with('?') {
+        let instruction = &markup[1..markup.len() - 1];
+        let target: String = instruction.chars().take_while(|c| !c.is_whitespace()).collect();
+        let data = instruction[target.len()..].trim().to_owned();
+        terms.push(XMLTerm::ProcessingInstruction(target, data));
+    }
+    else if markup.starts_with('!') {
+        //Ignore document type declarations
+    }
+    else if let Some(closing) = markup.strip_prefix('/') {
+        let name = closing.trim();
+
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("Invalid closing tag </{}>", closing));
+        }
+
+        terms.push(XMLTerm::ClosingTag(name.to_owned()));
+    }
+    else {
+        lex_tag(markup, terms)?;
+    }
+
+    Ok(())
+}
+
+//The nesting of '[' and ']' in a declaration such as '<!DOCTYPE a [ ... ]>' after the character 'c'
+pub(crate) fn declaration_depth(depth: usize, c: char) -> Result<usize, String> {
+    match c {
+        '[' => Ok(depth + 1),
+        ']' => depth.checked_sub(1).ok_or_else(|| "Unmatched ']' in declaration".to_owned()),
+        _ => Ok(depth),
+    }
+}
+
+//The state of the lexer between characters, so that a file can be read in pieces
+pub struct XMLLexer {
+    current_term: XMLTerm,
+    markup: Option<String>,
+    quote: Option<char>,
+    bracket_depth: usize,
+}
+
+impl Default for XMLLexer {
+    fn default() -> XMLLexer {
+        XMLLexer::new()
+    }
+}
+
+impl XMLLexer {
+    pub fn new() -> XMLLexer {
+        XMLLexer {
+            current_term: XMLTerm::None,
+            markup: None,
+            quote: None,
+            bracket_depth: 0,
+        }
+    }
+
+    //Reads the next character of a file, pushing any terms it completes
+    pub fn lex_char(&mut self, c: char, terms: &mut Vec<XMLTerm>) -> Result<(), String> {
+        let complete = match self.markup {
+            None => {
+                if c == '<' {
+                    //Check if we should end a text term
+                    if let XMLTerm::Text(_) = self.current_term {
+                        self.current_term = push_term(terms, &mut self.current_term)
+                    }
+
+                    //Start reading a tag
+                    self.markup = Some(String::new());
+                }
+                else {
+                    //Create a new text element if we are outside of any elements
+                    if self.current_term == XMLTerm::None { self.current_term = XMLTerm::Text(String::new()) }
+
+                    //Add character to current element content
+                    if let Some(s) = self.current_term.get_string() {
+                        s.push(c);
+                    }
+                }
+
+                return Ok(());
+            },
+            Some(ref mut markup) => {
+                let complete = if let Some(quote) = self.quote {
+                    //Nothing ends a tag inside a quoted attribute value
+                    if c == quote {
+                        self.quote = None;
+                    }
+                    false
+                }
+                else if markup.starts_with("!--") {
+                    c == '>' && markup.len() >= 5 && markup.ends_with("--")
+                }
+                else if markup.starts_with("![CDATA[") {
+                    c == '>' && markup.len() >= 10 && markup.ends_with("]]")
+                }
+                else if markup.starts_with('?') {
+                    c == '>' && markup.len() >= 2 && markup.ends_with('?')
+                }
+                else {
+                    match c {
+                        '"' | '\'' => { self.quote = Some(c); false },
+                        '[' | ']' if markup.starts_with('!') => { self.bracket_depth = declaration_depth(self.bracket_depth, c)?; false },
+                        '<' if !markup.starts_with('!') => return Err("Unexpected '<'".to_owned()),
+                        '>' => self.bracket_depth == 0,
+                        _ => false,
+                    }
+                };
+
+                if !complete {
+                    markup.push(c);
+                }
+
+                complete
+            },
+        };
+
+        if complete {
+            let markup = self.markup.take().unwrap();
+            self.bracket_depth = 0;
+
+            lex_markup(&markup, terms)?;
+        }
+
+        Ok(())
+    }
+
+    //Whether the lexer is part way through a piece of markup, between its '<' and '>'
+    pub fn in_markup(&self) -> bool {
+        self.markup.is_some()
+    }
+
+    //Ends the file, pushing any remaining text
+    pub fn finish(&mut self, terms: &mut Vec<XMLTerm>) -> Result<(), String> {
+        if self.markup.is_some() {
+            return Err("Unexpected end of file inside a tag".to_owned());
+        }
+
+        if let XMLTerm::Text(_) = self.current_term {
+            self.current_term = push_term(terms, &mut self.current_term)
+        }
+
+        Ok(())
+    }
+}
+
+//Converts a read XML file into a vector of 'XMLTerm's
+pub fn lexer(file_contents: String) -> Result<Vec<XMLTerm>, String> {
+    TermReader::new(file_contents.chars().map(Ok)).collect()
+}
+
+//Converts a string of 'XMLTerm's into a XML tree
+pub fn parser(terms: &[XMLTerm]) -> Result<XMLDocument, String> {
+    event_parser(EventReader::new(terms.iter().cloned().map(Ok)))
+}
+
+//Converts a stream of 'XMLEvent's into a XML tree
+pub fn event_parser<I: Iterator<Item = Result<XMLEvent, String>>>(events: I) -> Result<XMLDocument, String> {
+    let mut document = XMLDocument::new();
+    let mut node_stack: Vec<NodeId> = Vec::new();
+
+    //Whether a blank line was read since the last node, to be kept with the next one
+    let mut blank_line = false;
+
+    node_stack.push(document.root());
+
+    for event in events {
+        let event = event?;
+
+        let new_node = match event {
+            XMLEvent::StartElement(name, attributes) => {
+                //Create a new node as a child of the current node
+                let new_node = document.append_element(*node_stack.top(), &name);
+                let mut declares_namespaces = false;
+
+                for (name, value) in attributes {
+                    declares_namespaces = declares_namespaces || declared_prefix(&name).is_some();
+                    document.push_attribute(new_node, &name, value);
+                }
+
+                //The element's own declarations are in scope for its name
+                if declares_namespaces {
+                    document.resolve_namespace(new_node);
+                }
+
+                //Make this tag the current node
+                node_stack.push(new_node);
+                Some(new_node)
+            },
+            XMLEvent::EndElement(_) => {
+                //The events have already been checked to close in order, so step back to this node's parent
+                node_stack.pop();
+                None
+            },
+            XMLEvent::Text(s) => {
+                //Set the data of the current node
+                document.push_data(*node_stack.top(), &s);
+                None
+            },
+            XMLEvent::Comment(s) => {
+                Some(document.append_comment(*node_stack.top(), &s))
+            },
+            XMLEvent::ProcessingInstruction(target, data) => {
+                //The XML declaration is written again for each output, so is not kept
+                if target != "xml" {
+                    Some(document.append_processing_instruction(*node_stack.top(), &target, &data))
+                }
+                else {
+                    None
+                }
+            },
+            XMLEvent::BlankLine => {
+                blank_line = true;
+                continue;
+            },
+        };
+
+        if let Some(new_node) = new_node {
+            document.set_blank_line_before(new_node, blank_line);
+        }
+
+        blank_line = false;
+    }
+    
+    Ok(document)
+}
+
+//Converts a read XML file into a XML tree which knows the line and column each node starts at, for reporting errors
+pub fn parse_with_positions(file_contents: &str) -> Result<XMLDocument, String> {
+    let mut document = parser(&lexer(file_contents.to_owned())?)?;
+
+    //Find where each start tag, comment and processing instruction begins, which is the order of the nodes in the tree
+    let mut xml_lexer = XMLLexer::new();
+    let mut terms: Vec<XMLTerm> = Vec::new();
+    let mut positions: Vec<Position> = Vec::new();
+    let mut markup_start = Position { line: 1, column: 1 };
+    let mut position = Position { line: 1, column: 1 };
+
+    for c in file_contents.chars() {
+        let was_in_markup = xml_lexer.in_markup();
+        xml_lexer.lex_char(c, &mut terms)?;
+
+        if !was_in_markup && xml_lexer.in_markup() {
+            markup_start = position;
+        }
+        else if was_in_markup && !xml_lexer.in_markup() {
+            let is_node = terms.iter().any(|term| match *term {
+                XMLTerm::OpeningTag(_) | XMLTerm::Comment(_) => true,
+                XMLTerm::ProcessingInstruction(ref target, _) => target != "xml",
+                _ => false,
+            });
+
+            if is_node {
+                positions.push(markup_start);
+            }
+        }
+        terms.clear();
+
+        if c == '\n' {
+            position.line += 1;
+            position.column = 1;
+        }
+        else {
+            position.column += 1;
+        }
+    }
+
+    let root = document.root();
+    let nodes: Vec<NodeId> = document.descendants(root).filter(|&node| document.kind(node) != NodeKind::Text).collect();
+
+    for (node, position) in nodes.into_iter().zip(positions) {
+        document.set_position(node, position);
+    }
+
+    Ok(document)
+}
+
+//Converts a read XML file into a XML tree keeping all of its text, including whitespace, as canonical XML needs
+pub fn parse_keeping_whitespace(file_contents: &str) -> Result<XMLDocument, String> {
+    //Line endings are read as '\n', as the XML specification requires
+    let file_contents = file_contents.replace("\r\n", "\n").replace('\r', "\n");
+
+    event_parser(EventReader::new(Tokenizer::keeping_whitespace(&file_contents).map(|token| token.map(|token| token.to_term()))))
+}
+
+//Recursively converts an XML tree node into key/values in a map for CSV formatting
+fn recursive_csv_format<'a>(document: &'a XMLDocument, node: NodeId, path: &mut Vec<&'a str>, keymap: &mut HashMap<String, Vec<String>>, titles: &mut HashMap<String, String>, options: &OutputOptions, index: &mut usize) {
+    path.push(document.name(node));
+
+    let children = document.child_elements(node);
+
+    if children.is_empty() { //If we are an 'end node'
+        let data = document.text(node);
+
+        if !data.is_empty() && !document.name(node).is_empty() {
+            //Add this data to the map, keyed by the node path
+            let key = path.join("/");
+
+            if !titles.contains_key(&key) {
+                let title = if options.expanded_names { column_name(document, node, &options.namespaces) } else { document.name(node).to_owned() };
+                titles.insert(key.clone(), title);
+            }
+
+            let column = keymap.entry(key).or_default();
+            column.resize(*index, String::new());
+            column.push(data);
+        }
+
+    }
+    else {
+        //Recurse to children
+        for child in children {
+            recursive_csv_format(document, child, path, keymap, titles, options, index);
+        }
+
+        *index += 1;
+    }
+
+    path.pop();
+}
+
+//Quotes a CSV entry if it contains a delimiter, quote or line break
+fn csv_escape(entry: &str) -> String {
+    if entry.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", entry.replace('"', "\"\""))
+    }
+    else {
+        entry.to_owned()
+    }
+}
+
+//Converts an XML tree into a CSV file string, titling the columns as chosen in the options
+pub fn csv_formatter(document: &XMLDocument, options: &OutputOptions) -> String {
+    let mut keymap: HashMap<String, Vec<String>> = HashMap::new();
+    let mut titles: HashMap<String, String> = HashMap::new();
+
+    //The 'depth' of the csv
+    let mut index: usize = 0;
+
+    //Populate the map from the tree
+    recursive_csv_format(document, document.root(), &mut Vec::new(), &mut keymap, &mut titles, options, &mut index);
+
+    let mut csv_string = String::new();
+
+    //Push 'column' titles
+    for key in keymap.keys() {
+        csv_string.push_str(&csv_escape(&titles[key]));
+        csv_string.push(',');
+    }
+
+    csv_string.pop();
+    csv_string.push('\n');
+
+    //Push row data
+    for row in 0..index {
+        for vec in keymap.values() {
+            if vec.len() > row {
+                csv_string.push_str(&csv_escape(&vec[row]));
+                csv_string.push(',');
+            }
+        }
+
+        csv_string.pop();
+        csv_string.push('\n');
+    }
+
+    csv_string
+}
+
+//Reads the records of a CSV file one at a time from its characters
+pub struct CSVRecordReader<I: Iterator<Item = Result<char, String>>> {
+    chars: Peekable<I>,
+    dialect: CSVDialect,
+    records_read: usize,
+}
+
+impl<I: Iterator<Item = Result<char, String>>> CSVRecordReader<I> {
+    pub fn new(chars: I, dialect: &CSVDialect) -> CSVRecordReader<I> {
+        CSVRecordReader {
+            chars: chars.peekable(),
+            dialect: dialect.clone(),
+            records_read: 0,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<char, String>>> Iterator for CSVRecordReader<I> {
+    type Item = Result<Vec<String>, String>;
+
+    fn next(&mut self) -> Option<Result<Vec<String>, String>> {
+        let mut record: Vec<String> = Vec::new();
+        let mut entry = String::new();
+        let mut in_quotes = false;
+        let mut entry_quoted = false;
+
+        //Read character-by-character
+        while let Some(c) = self.chars.next() {
+            let c = match c {
+                Ok(c) => c,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if in_quotes {
+                if Some(c) == self.dialect.quote {
+                    //A doubled quote inside a quoted entry is a literal quote
+                    if self.chars.peek() == Some(&Ok(c)) {
+                        entry.push(c);
+                        self.chars.next();
+                    }
+                    else {
+                        in_quotes = false;
+                    }
+                }
+                else {
+                    entry.push(c);
+                }
+
+                continue;
+            }
+
+            match c {
+                c if Some(c) == self.dialect.quote && entry.is_empty() && !entry_quoted => {
+                    //Start a quoted entry
+                    in_quotes = true;
+                    entry_quoted = true;
+                },
+                c if c == self.dialect.delimiter => {
+                    //End the current entry
+                    record.push(entry);
+                    entry = String::new();
+                    entry_quoted = false;
+                },
+                '\r' | '\n' => {
+                    //Treat '\r\n' as a single line break
+                    if c == '\r' && self.chars.peek() == Some(&Ok('\n')) {
+                        self.chars.next();
+                    }
+
+                    //End the current record, ignoring blank lines
+                    if !record.is_empty() || !entry.is_empty() || entry_quoted {
+                        record.push(entry);
+                        self.records_read += 1;
+                        return Some(Ok(record));
+                    }
+                },
+                _ => entry.push(c),
+            }
+        }
+
+        if in_quotes {
+            return Some(Err(format!("Unterminated quoted entry in row {}", self.records_read)));
+        }
+
+        //Return the final record if the file does not end with a line break
+        if !record.is_empty() || !entry.is_empty() || entry_quoted {
+            record.push(entry);
+            self.records_read += 1;
+            return Some(Ok(record));
+        }
+
+        None
+    }
+}
+
+//Converts a read CSV file into rows of entries, stopping after 'record_limit' rows if given
+pub fn csv_lexer(file_contents: &str, dialect: &CSVDialect, record_limit: Option<usize>) -> Result<Vec<Vec<String>>, String> {
+    let mut records: Vec<Vec<String>> = Vec::new();
+
+    for record in CSVRecordReader::new(file_contents.chars().map(Ok), dialect) {
+        records.push(record?);
+
+        if record_limit.is_some_and(|limit| records.len() >= limit) {
+            break;
+        }
+    }
+
+    Ok(records)
+}
+
+//Where the column names of a CSV file come from
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum CSVHeader {
+    FirstLine,
+    Generated,
+    Supplied(Vec<String>),
+}
+
+//Splits a read CSV file into its column names and its rows of entries
+fn csv_columns(file_contents: &str, dialect: &CSVDialect, header: &CSVHeader) -> Result<(Vec<String>, Vec<Vec<String>>), String> {
+    let mut keymap: Vec<String> = Vec::new();
+    let mut rows: Vec<Vec<String>> = Vec::new();
+
+    //Find all of the records of the file
+    let file_records: Vec<Vec<String>> = csv_lexer(file_contents, dialect, None)?;
+
+    let mut read_keys: bool = false;
+
+    match *header {
+        CSVHeader::FirstLine => {},
+        CSVHeader::Generated => {
+            //Name the columns 'col1', 'col2', ... up to the widest row
+            let width = file_records.iter().map(|record| record.len()).max().unwrap_or(0);
+
+            for key_index in 0..width {
+                keymap.push(format!("col{}", key_index + 1));
+            }
+
+            read_keys = true;
+        },
+        CSVHeader::Supplied(ref names) => {
+            for name in names {
+                keymap.push(name.trim().to_owned());
+            }
+
+            read_keys = true;
+        },
+    }
+
+    for line_entries in file_records {
+        //If we have not yet read the 'column titles' row
+        if !read_keys {
+            //Read key names
+            for entry in line_entries {
+                keymap.push(entry);
+            }
+
+            read_keys = true;
+        }
+        else {
+            //Create a new row
+            let mut row: Vec<String> = Vec::new();
+
+            for entry in line_entries {
+                //Add all of the entries to the row
+                row.push(entry);
+            }
+
+            //Add the row to the vec
+            rows.push(row);
+        }
+    }
+
+    if rows.is_empty() {
+        return Err("No entries in CSV file".to_owned());
+    }
+
+    Ok((keymap, rows))
+}
+
+//Converts a read CSV file into an XML tree
+pub fn csv_parser(file_contents: String, dialect: &CSVDialect, header: &CSVHeader, namespaces: &NamespaceMap) -> Result<XMLDocument, String> {
+    let (keymap, rows) = csv_columns(&file_contents, dialect, header)?;
+
+    csv_document(&keymap, &rows, namespaces)
+}
+
+//Converts the column names and rows of a CSV file into an XML tree
+fn csv_document(keymap: &[String], rows: &[Vec<String>], namespaces: &NamespaceMap) -> Result<XMLDocument, String> {
+    let mut document = XMLDocument::new();
+    let root = document.root();
+    let root2 = document.append_element(root, "root2");
+
+    //Columns titled '{uri}local' or 'prefix:local' become elements in that namespace, declared on the outermost element
+    let (keymap, declarations) = qualify_names(keymap, namespaces);
+
+    for (name, uri) in declarations {
+        document.set_attribute(root2, &name, &uri);
+    }
+
+    //Convert to XML tree
+    for (row_index, row) in rows.iter().enumerate() {
+        let new_node = document.append_element(root2, "element");
+
+        for key_index in 0..keymap.len() {
+            if row.len() <= key_index {
+                return Err(format!("Expected key {} for row {}", key_index, row_index));
+            }
+
+            let new_sub_node = document.append_element(new_node, &keymap[key_index]);
+            document.push_data(new_sub_node, &row[key_index]);
+        }
+    }
+
+    Ok(document)
+}
+
+//Recursively converts an XML node yielding 'XMLTerm's
+fn recursive_xml_reverse_parse(document: &XMLDocument, node: NodeId, terms: &mut Vec<XMLTerm>) {
+    let data = document.data(node);
+
+    if document.blank_line_before(node) {
+        terms.push(XMLTerm::BlankLine);
+    }
+
+    match document.kind(node) {
+        NodeKind::Element => {},
+        NodeKind::Text => {
+            terms.push(XMLTerm::Text(data.to_owned()));
+            return;
+        },
+        NodeKind::Comment => {
+            terms.push(XMLTerm::Comment(data.to_owned()));
+            return;
+        },
+        NodeKind::ProcessingInstruction => {
+            terms.push(XMLTerm::ProcessingInstruction(document.name(node).to_owned(), data.to_owned()));
+            return;
+        },
+    }
+
+    //Write opening tag
+    terms.push(XMLTerm::OpeningTag(document.name(node).to_owned()));
+
+    for (name, value) in document.attributes(node) {
+        terms.push(XMLTerm::Attribute(name.to_owned(), value.to_owned()));
+    }
+
+    //Recurse for children, which include the text in its place among them
+    for &child in document.children(node) {
+        recursive_xml_reverse_parse(document, child, terms);
+    }
+
+    //Write closing tag
+    terms.push(XMLTerm::ClosingTag(document.name(node).to_owned()));
+}
+
+//Converts an XML tree into a vector of 'XMLTerm's
+pub fn xml_reverse_parser(document: &XMLDocument) -> Vec<XMLTerm> {
+    let mut terms: Vec<XMLTerm> = Vec::new();
+
+    let root = document.root();
+    if document.child_elements(root).is_empty() {
+        panic!("Invalid XML tree");
+    }
+
+    //Recursively create terms from tree, including any comments around the outermost element
+    for &child in document.children(root) {
+        recursive_xml_reverse_parse(document, child, &mut terms);
+    }
+
+    terms
+}
+
+//The output encodings offered to users, by name
+pub const OUTPUT_ENCODINGS: [&str; 4] = ["UTF-8", "UTF-8 with BOM", "UTF-16LE", "ISO-8859-1"];
+
+//The output line endings offered to users, by name
+pub const OUTPUT_LINE_ENDINGS: [&str; 2] = ["LF", "CRLF"];
+
+//The XML indentation offered to users, by name
+pub const OUTPUT_INDENTS: [&str; 4] = ["2 spaces", "4 spaces", "Tabs", "Compact"];
+
+//What each level of nesting in a written XML file is indented by
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum Indent {
+    Spaces(usize),
+    Tabs,
+}
+
+//How converted files are written. The layout options only apply to XML files.
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct OutputOptions {
+    pub encoding: Encoding,
+    pub bom: bool,
+    pub line_ending: LineEnding,
+    pub indent: Indent,
+    //Writes each outermost node on a single line, without indentation
+    pub compact: bool,
+    //Moves the attributes of a tag onto their own lines when the tag would be longer than this
+    pub max_line_width: Option<usize>,
+    //Writes elements with no text or children as '<name/>'
+    pub self_closing: bool,
+    //Keeps the blank lines between nodes of a file which was read
+    pub preserve_blank_lines: bool,
+    //Prefixes to write namespaced names with, in column titles and in XML made from them
+    pub namespaces: NamespaceMap,
+    //Titles columns of namespaced elements '{uri}local', or with a prefix from 'namespaces', rather than as written in the file
+    pub expanded_names: bool,
+}
+
+impl Default for OutputOptions {
+    fn default() -> OutputOptions {
+        OutputOptions::new()
+    }
+}
+
+impl OutputOptions {
+    pub fn new() -> OutputOptions {
+        OutputOptions {
+            encoding: Encoding::UTF8,
+            bom: false,
+            line_ending: LineEnding::LF,
+            indent: Indent::Spaces(2),
+            compact: false,
+            max_line_width: None,
+            self_closing: false,
+            preserve_blank_lines: false,
+            namespaces: NamespaceMap::new(),
+            expanded_names: false,
+        }
+    }
+
+    //Sets the indentation from a name, such as '4', '4 spaces', 'tabs' or 'compact'
+    pub fn set_indent(&mut self, name: &str) -> Result<(), String> {
+        let lower_name = name.trim().to_lowercase();
+        let width = lower_name.trim_end_matches("spaces").trim_end_matches("space").trim();
+
+        if lower_name == "tab" || lower_name == "tabs" {
+            self.indent = Indent::Tabs;
+            self.compact = false;
+        }
+        else if lower_name == "compact" {
+            self.compact = true;
+        }
+        else if let Ok(width) = width.parse::<usize>() {
+            self.indent = Indent::Spaces(width);
+            self.compact = false;
+        }
+        else {
+            return Err(format!("Unknown indentation: {}", name));
+        }
+
+        Ok(())
+    }
+
+    //Sets the encoding from a name, where names ending in 'with BOM' or '-bom' also write a byte order mark
+    pub fn set_encoding(&mut self, name: &str) -> Result<(), String> {
+        let lower_name = name.trim().to_lowercase();
+        let (encoding_name, bom) = if lower_name.ends_with(" with bom") {
+            (&lower_name[..lower_name.len() - " with bom".len()], true)
+        }
+        else if lower_name.ends_with("-bom") {
+            (&lower_name[..lower_name.len() - "-bom".len()], true)
+        }
+        else {
+            (&lower_name[..], false)
+        };
+
+        match Encoding::from_name(encoding_name) {
+            Some(encoding) => {
+                self.encoding = encoding;
+                //UTF-16 files always need a byte order mark to be read back
+                self.bom = bom || encoding == Encoding::UTF16LE || encoding == Encoding::UTF16BE;
+                Ok(())
+            },
+            None => Err(format!("Unknown encoding: {}", name)),
+        }
+    }
+
+    pub fn set_line_ending(&mut self, name: &str) -> Result<(), String> {
+        match LineEnding::from_name(name) {
+            Some(line_ending) => { self.line_ending = line_ending; Ok(()) },
+            None => Err(format!("Unknown line ending: {}", name)),
+        }
+    }
+}
+
+//The XML declaration which starts a file written with the given options
+pub fn xml_declaration(options: &OutputOptions) -> String {
+    format!("<?xml version=\"1.0\" encoding=\"{}\"?>\n", options.encoding.xml_name())
+}
+
+//Converts a vector of 'XMLTerm's into a XML file string, laid out as chosen in the options
+pub fn xml_formatter(terms: Vec<XMLTerm>, options: &OutputOptions) -> String {
+    let mut xml_string = String::new();
+
+    xml_string.push_str(&xml_declaration(options));
+
+    let mut terms = terms.into_iter().peekable();
+    let mut open_elements: Vec<String> = Vec::new();
+
+    while let Some(term) = terms.next() {
+        let depth = open_elements.len();
+
+        match term {
+            XMLTerm::OpeningTag(name) => {
+                //Gather the attributes and text which follow the tag
+                let mut attributes: Vec<(String, String)> = Vec::new();
+                while let Some(&XMLTerm::Attribute(..)) = terms.peek() {
+                    if let Some(XMLTerm::Attribute(name, value)) = terms.next() {
+                        attributes.push((name, value));
+                    }
+                }
+
+                let mut text = String::new();
+                if let Some(&XMLTerm::Text(_)) = terms.peek() {
+                    if let Some(XMLTerm::Text(s)) = terms.next() {
+                        text = s;
+                    }
+                }
+
+                //An element closed straight away is written on one line
+                let has_children = !matches!(terms.peek(), Some(&XMLTerm::ClosingTag(_)));
+
+                let attributes: Vec<(&str, &str)> = attributes.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+                xml_string.push_str(&xml_writer::element_start(&name, &attributes, &text, depth, has_children, options));
+
+                if has_children {
+                    open_elements.push(name);
+                }
+                else {
+                    terms.next();
+                }
+            },
+            XMLTerm::ClosingTag(name) => {
+                open_elements.pop();
+                xml_string.push_str(&xml_writer::element_end(&name, open_elements.len(), options));
+            },
+            XMLTerm::Text(s) => {
+                //Text after the children of an element goes on its own line
+                xml_string.push_str(&format!("{}{}{}", xml_writer::indentation(depth, options), escape_text(&s), xml_writer::line_end(depth, options)));
+            },
+            XMLTerm::Comment(s) => xml_string.push_str(&xml_writer::comment(&s, depth, options)),
+            //The XML declaration has already been written for the chosen encoding
+            XMLTerm::ProcessingInstruction(ref target, _) if target == "xml" => {},
+            XMLTerm::ProcessingInstruction(target, data) => xml_string.push_str(&xml_writer::processing_instruction(&target, &data, depth, options)),
+            XMLTerm::BlankLine => xml_string.push_str(xml_writer::blank_line(options)),
+            //Attributes are only written as part of their tag
+            XMLTerm::Attribute(_, _) | XMLTerm::None => {},
+        }
+    }
+
+    xml_string
+}
+
+
+//Writes a file in pieces with the chosen encoding and line endings
+pub struct EncodedWriter<W: Write> {
+    writer: W,
+    options: OutputOptions,
+    //Where the XML written so far has got to
+    markup: Markup,
+}
+
+impl<W: Write> EncodedWriter<W> {
+    //Starts writing a file, beginning with a byte order mark if one was chosen
+    pub fn new(mut writer: W, options: &OutputOptions) -> Result<EncodedWriter<W>, String> {
+        if options.bom {
+            let bom = encode("", options.encoding, true)?;
+
+            if let Err(e) = writer.write_all(&bom) {
+                return Err(format!("Could not write to file: {}", e));
+            }
+        }
+
+        Ok(EncodedWriter {
+            writer,
+            options: options.clone(),
+            markup: Markup::Text,
+        })
+    }
+
+    //Writes text such as CSV, where a character the encoding does not have is an error
+    pub fn write_str(&mut self, text: &str) -> Result<(), String> {
+        let bytes = match self.options.line_ending {
+            LineEnding::LF => encode(text, self.options.encoding, false),
+            line_ending => encode(&text.replace('\n', line_ending.as_str()), self.options.encoding, false),
+        };
+
+        self.write_bytes(bytes)
+    }
+
+    //Writes a piece of XML, where a character the encoding does not have is written as a character reference if it can be
+    pub fn write_xml(&mut self, xml: &str) -> Result<(), String> {
+        let bytes = match self.options.line_ending {
+            LineEnding::LF => encode_xml(xml, self.options.encoding, &mut self.markup),
+            line_ending => encode_xml(&xml.replace('\n', line_ending.as_str()), self.options.encoding, &mut self.markup),
+        };
+
+        self.write_bytes(bytes)
+    }
+
+    fn write_bytes(&mut self, bytes: Result<Vec<u8>, String>) -> Result<(), String> {
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(e) => return Err(format!("Could not encode file: {}", e)),
+        };
+
+        match self.writer.write_all(&bytes) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write to file: {}", e)),
+        }
+    }
+
+    //The options the file is written with
+    pub fn options(&self) -> &OutputOptions {
+        &self.options
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        match self.writer.flush() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write to file: {}", e)),
+        }
+    }
+}
+
+//Creates an output file to be written in pieces with the chosen encoding and line endings
+pub fn create_file(output_file: &str, options: &OutputOptions) -> Result<EncodedWriter<BufWriter<File>>, String> {
+    match File::create(output_file) {
+        Ok(file) => EncodedWriter::new(BufWriter::new(file), options),
+        Err(e) => Err(format!("Could not create file {}: {}", output_file, e)),
+    }
+}
+
+//Writes a formatted file string to an output file with the chosen encoding and line endings
+pub fn write_file(output_file: &str, file_contents: &str, options: &OutputOptions) -> Result<(), String> {
+    let mut writer = create_file(output_file, options)?;
+
+    writer.write_str(file_contents)?;
+    writer.flush()
+}
+
+//Writes an XML file string to an output file with the chosen encoding and line endings
+pub fn write_xml_file(output_file: &str, file_contents: &str, options: &OutputOptions) -> Result<(), String> {
+    let mut writer = create_file(output_file, options)?;
+
+    writer.write_xml(file_contents)?;
+    writer.flush()
+}
+
+//Writes a whole XML tree to an output file
+pub fn write_document(output_file: &str, document: &XMLDocument, options: &OutputOptions) -> Result<(), String> {
+    let mut writer = create_file(output_file, options)?;
+
+    writer.write_xml(&xml_declaration(options))?;
+    write_node(document, document.root(), &mut writer)?;
+    writer.flush()
+}
+
+//Opens an input file to be read one character at a time, detecting its encoding unless one is given
+pub fn open_file(input_file: &str, encoding: Option<Encoding>) -> Result<CharReader<BufReader<File>>, String> {
+    let reader = match File::open(input_file) {
+        Err(e) => return Err(format!("Could not open file {}: {}", input_file, e)),
+        Ok(file) => BufReader::new(file),
+    };
+
+    match encoding {
+        Some(encoding) => Ok(CharReader::new(reader, encoding)),
+        None => CharReader::with_detected_encoding(reader),
+    }
+}
+
+//Opens an XML file to be read one event at a time
+pub fn read_events(input_file: &str) -> Result<EventReader<TermReader<CharReader<BufReader<File>>>>, String> {
+    Ok(EventReader::new(TermReader::new(open_file(input_file, None)?)))
+}
+
+//Reads a whole input file into a string, detecting its encoding unless one is given
+pub fn read_file(input_file: &str, encoding: Option<Encoding>) -> Result<String, String> {
+    let mut file_contents: Vec<u8> = Vec::new();
+
+    let mut file = match File::open(input_file) {
+        Err(e) => return Err(format!("Could not open file {}: {}", input_file, e)),
+        Ok(file) => file,
+    };
+
+    if let Err(e) = file.read_to_end(&mut file_contents) {
+        return Err(format!("Could not read file {}: {}", input_file, e));
+    }
+
+    let encoding = match encoding {
+        Some(encoding) => encoding,
+        None => detect_encoding(&file_contents),
+    };
+
+    match decode(&file_contents, encoding) {
+        Ok(text) => Ok(text),
+        Err(e) => Err(format!("Could not decode file {} as {}: {}", input_file, encoding.name(), e)),
+    }
+}
+
+pub fn xml_to_csv(input_file: String, output_file: String, options: &OutputOptions) -> Result<(), String> {
+    let root = read_document(&input_file)?;
+
+    let csv_result = csv_formatter(&root, options);
+
+    write_file(&output_file, &csv_result, options)
+}
+
+//Parses an XML file into a tree. Only the internal subset of its DTD is read, never external files.
+pub fn read_document(input_file: &str) -> Result<XMLDocument, String> {
+    let dtd = read_file_dtd(input_file, false)?;
+
+    //Entities declared by the DTD are replaced as the file is read, so that any markup in them is parsed
+    let result = match dtd {
+        Some(ref dtd) if dtd.has_entities() => event_parser(EventReader::new(TermReader::new(EntityExpander::new(open_file(input_file, None)?, dtd)))),
+        _ => event_parser(read_events(input_file)?),
+    };
+
+    match result {
+        Err(error) => Err(format!("Could not parse XML file: {}", error)),
+        Ok(mut document) => {
+            if let Some(ref dtd) = dtd {
+                dtd.apply_defaults(&mut document);
+            }
+            Ok(document)
+        },
+    }
+}
+
+//Reads the DTD named by the document type declaration of an XML file, if it has one. External DTD and entity files
+//are only read if 'external' is set, as for validating, and then only from the directory of the file.
+fn read_file_dtd(input_file: &str, external: bool) -> Result<Option<Dtd>, String> {
+    let reader = match File::open(input_file) {
+        Err(e) => return Err(format!("Could not open file {}: {}", input_file, e)),
+        Ok(file) => BufReader::new(file),
+    };
+
+    let doctype = match read_doctype(CharReader::with_detected_encoding(reader)?) {
+        Ok(Some(doctype)) => doctype,
+        Ok(None) => return Ok(None),
+        Err(e) => return Err(format!("Could not parse XML file: {}", e)),
+    };
+
+    let directory = match Path::new(input_file).parent() {
+        Some(directory) if directory.as_os_str().is_empty() => Some(Path::new(".")),
+        directory => directory,
+    };
+
+    //External files which are missing are left out, and listed by the DTD for anything which needs them
+    match Dtd::load(&doctype, if external { directory } else { None }) {
+        Ok(dtd) => Ok(Some(dtd)),
+        Err(e) => Err(format!("Could not read the DTD of {}: {}", input_file, e)),
+    }
+}
+
+//Reads a DTD file, such as one to validate documents which do not name it
+pub fn read_dtd(dtd_file: &str) -> Result<Dtd, String> {
+    let file_contents = read_file(dtd_file, None)?;
+
+    match Dtd::parse(&file_contents, Path::new(dtd_file).parent()) {
+        Ok(dtd) => Ok(dtd),
+        Err(e) => Err(format!("Could not read DTD {}: {}", dtd_file, e)),
+    }
+}
+
+//Reads an XML file keeping its exact text, encoding and byte order mark, so that it can be edited and written back with nothing else changed
+pub fn read_lossless_document(input_file: &str) -> Result<LosslessDocument, String> {
+    let mut file_contents: Vec<u8> = Vec::new();
+
+    let mut file = match File::open(input_file) {
+        Err(e) => return Err(format!("Could not open file {}: {}", input_file, e)),
+        Ok(file) => file,
+    };
+
+    if let Err(e) = file.read_to_end(&mut file_contents) {
+        return Err(format!("Could not read file {}: {}", input_file, e));
+    }
+
+    let encoding = detect_encoding(&file_contents);
+    let text = match decode(&file_contents, encoding) {
+        Ok(text) => text,
+        Err(e) => return Err(format!("Could not decode file {} as {}: {}", input_file, encoding.name(), e)),
+    };
+
+    match LosslessDocument::parse(&text, encoding, has_bom(&file_contents)) {
+        Err(error) => Err(format!("Could not parse XML file: {}", error)),
+        Ok(document) => Ok(document),
+    }
+}
+
+//Writes a document read by 'read_lossless_document' in the encoding it was read with. The line endings are kept as they were in the text.
+pub fn write_lossless_document(output_file: &str, document: &LosslessDocument) -> Result<(), String> {
+    let mut options = OutputOptions::new();
+    options.encoding = document.encoding();
+    options.bom = document.bom();
+
+    write_xml_file(output_file, &document.to_xml(), &options)
+}
+
+//Reads a whole XML file into a tree which knows where each node was in the file
+pub fn read_document_with_positions(input_file: &str) -> Result<XMLDocument, String> {
+    Ok(read_positioned_document(input_file, false)?.0)
+}
+
+//Reads a whole XML file with the entities of the DTD it names replaced, along with the DTD
+fn read_expanded_file(input_file: &str, external: bool) -> Result<(String, Option<Dtd>), String> {
+    let mut file_contents = read_file(input_file, None)?;
+    let dtd = read_file_dtd(input_file, external)?;
+
+    if let Some(ref dtd) = dtd {
+        if dtd.has_entities() {
+            file_contents = EntityExpander::new(file_contents.chars().map(Ok), dtd).collect::<Result<String, String>>()?;
+        }
+    }
+
+    Ok((file_contents, dtd))
+}
+
+//Reads a whole XML file with positions, along with the DTD it names. Entities which hold line breaks move the positions of what follows them.
+fn read_positioned_document(input_file: &str, external: bool) -> Result<(XMLDocument, Option<Dtd>), String> {
+    let (file_contents, dtd) = read_expanded_file(input_file, external)?;
+
+    match parse_with_positions(&file_contents) {
+        Err(error) => Err(format!("Could not parse XML file: {}", error)),
+        Ok(mut document) => {
+            if let Some(ref dtd) = dtd {
+                dtd.apply_defaults(&mut document);
+            }
+            Ok((document, dtd))
+        },
+    }
+}
+
+//Reads a whole XML file keeping all of its text, with the entities and default attributes of the DTD it names
+fn read_whole_document(input_file: &str) -> Result<XMLDocument, String> {
+    let (file_contents, dtd) = read_expanded_file(input_file, false)?;
+
+    match parse_keeping_whitespace(&file_contents) {
+        Err(error) => Err(format!("Could not parse XML file: {}", error)),
+        Ok(mut document) => {
+            if let Some(ref dtd) = dtd {
+                dtd.apply_defaults(&mut document);
+            }
+            Ok(document)
+        },
+    }
+}
+
+//Reads an XSD file into a schema for validating documents
+pub fn read_schema(schema_file: &str) -> Result<Schema, String> {
+    let document = read_document_with_positions(schema_file)?;
+
+    match Schema::parse(&document) {
+        Ok(schema) => Ok(schema),
+        Err(e) => Err(format!("Could not read schema {}: {}", schema_file, e)),
+    }
+}
+
+//Checks an XML file against an XSD file, returning every place the file breaks the schema
+pub fn validate_xml(input_file: &str, schema_file: &str) -> Result<Vec<ValidationError>, String> {
+    let schema = read_schema(schema_file)?;
+    let document = read_positioned_document(input_file, true)?.0;
+
+    Ok(schema.validate(&document))
+}
+
+//Reads a RELAX NG schema written in the compact syntax
+pub fn read_relax_ng(rnc_file: &str) -> Result<RelaxNG, String> {
+    match RelaxNG::parse(&read_file(rnc_file, None)?) {
+        Ok(schema) => Ok(schema),
+        Err(e) => Err(format!("Could not read schema {}: {}", rnc_file, e)),
+    }
+}
+
+//Checks an XML file against a RELAX NG compact schema, returning every place the file breaks the schema
+pub fn validate_xml_relax_ng(input_file: &str, rnc_file: &str) -> Result<Vec<ValidationError>, String> {
+    let schema = read_relax_ng(rnc_file)?;
+    let document = read_positioned_document(input_file, true)?.0;
+
+    Ok(schema.validate(&document))
+}
+
+//Checks an XML file against a DTD file, or against the DTD its document type declaration names if none is given
+pub fn validate_xml_with_dtd(input_file: &str, dtd_file: Option<&str>) -> Result<Vec<ValidationError>, String> {
+    let (document, declared_dtd) = read_positioned_document(input_file, true)?;
+
+    let dtd = match (dtd_file, declared_dtd) {
+        (Some(dtd_file), _) => read_dtd(dtd_file)?,
+        (None, Some(dtd)) => match dtd.missing_files().first() {
+            Some(path) => return Err(format!("Could not find DTD file {} to validate against", path.display())),
+            None => dtd,
+        },
+        (None, None) => return Err(format!("{} has no document type declaration to validate against", input_file)),
+    };
+
+    Ok(dtd.validate(&document))
+}
+
+//Writes an XSD describing the elements, attributes and value types found in sample XML files, which all of them match
+pub fn infer_xsd(input_files: &[String], output_file: String, options: &OutputOptions) -> Result<(), String> {
+    let mut inference = SchemaInference::new();
+
+    for input_file in input_files {
+        let document = read_document(input_file)?;
+
+        if let Err(e) = inference.add_document(&document) {
+            return Err(format!("Could not infer a schema from {}: {}", input_file, e));
+        }
+    }
+
+    write_document(&output_file, &inference.to_document(), options)
+}
+
+//Finds the text of every part of an XML file selected by an XPath expression or 'css:' selector, with prefixes from the namespace map
+pub fn xml_query(input_file: &str, expression: &str, namespaces: &NamespaceMap) -> Result<Vec<String>, String> {
+    let selection = Selection::parse_with_namespaces(expression, namespaces)?;
+    let document = read_document(input_file)?;
+    let items = selection.evaluate(&document, document.root());
+
+    Ok(items.iter().map(|item| item_string(&document, item)).collect())
+}
+
+//Converts an XML file to a CSV file with the records and columns chosen by a mapping file
+pub fn xml_to_csv_mapped(input_file: String, output_file: String, mapping_file: String, options: &OutputOptions) -> Result<(), String> {
+    let mapping = match ColumnMapping::parse_with_namespaces(&read_file(&mapping_file, None)?, &options.namespaces) {
+        Ok(mapping) => mapping,
+        Err(e) => return Err(format!("Could not read mapping file {}: {}", mapping_file, e)),
+    };
+
+    let root = read_document(&input_file)?;
+
+    let csv_result = mapped_csv_formatter(&root, &mapping);
+
+    write_file(&output_file, &csv_result, options)
+}
+
+//Converts an XML file to a CSV file one record at a time, without reading the whole file into memory, returning the number of records
+pub fn xml_to_csv_streaming(input_file: String, output_file: String, record_name: Option<String>, options: &OutputOptions) -> Result<usize, String> {
+    let chars = open_file(&input_file, None)?;
+    let mut writer = create_file(&output_file, options)?;
+
+    let rows = xml_to_csv_stream(TermReader::new(chars), &mut writer, record_name.as_deref())?;
+    writer.flush()?;
+
+    Ok(rows)
+}
+
+//Converts a CSV file to an XML file, guessing the column names and encoding if they are not given
+pub fn csv_to_xml(input_file: String, output_file: String, header: Option<CSVHeader>, encoding: Option<Encoding>, options: &OutputOptions) -> Result<(), String> {
+    csv_to_xml_with_xsd(input_file, output_file, None, header, encoding, options)
+}
+
+//Converts a CSV file to XML, also writing an XSD describing that XML if 'schema_file' is given. The schema is made from
+//the same reading of the CSV file as the XML, with each column typed from its values and allowed to be blank if any cell was.
+pub fn csv_to_xml_with_xsd(input_file: String, output_file: String, schema_file: Option<String>, header: Option<CSVHeader>, encoding: Option<Encoding>, options: &OutputOptions) -> Result<(), String> {
+    let root = read_csv_document(&input_file, header, encoding, options)?;
+
+    write_document(&output_file, &root, options)?;
+
+    match schema_file {
+        Some(schema_file) => write_document(&schema_file, &csv_schema(&root)?, options),
+        None => Ok(()),
+    }
+}
+
+//Infers the type of each column of a CSV file, with the share of its values which have the type and how many are null
+pub fn describe_csv(input_file: &str, header: Option<CSVHeader>, encoding: Option<Encoding>) -> Result<Vec<ColumnType>, String> {
+    let (names, rows) = read_csv_rows(input_file, header, encoding)?;
+
+    Ok(infer_column_types(&names, &rows))
+}
+
+//Reads a CSV file into its column names and rows, detecting its format and, unless 'header' says, whether its first line names the columns
+fn read_csv_rows(input_file: &str, header: Option<CSVHeader>, encoding: Option<Encoding>) -> Result<(Vec<String>, Vec<Vec<String>>), String> {
+    let file_contents = read_file(input_file, encoding)?;
+
+    let dialect = match sniff_csv(&file_contents, SNIFF_SAMPLE_LINES) {
+        Ok(dialect) => dialect,
+        Err(e) => return Err(format!("Could not parse CSV file: {}", e)),
+    };
+
+    let header = match header {
+        Some(header) => header,
+        None if dialect.has_header => CSVHeader::FirstLine,
+        None => CSVHeader::Generated,
+    };
+
+    match csv_columns(&file_contents, &dialect, &header) {
+        Ok(columns) => Ok(columns),
+        Err(e) => Err(format!("Could not parse CSV file: {}", e)),
+    }
+}
+
+//Reads a CSV file into the XML tree it converts to, detecting its format
+fn read_csv_document(input_file: &str, header: Option<CSVHeader>, encoding: Option<Encoding>, options: &OutputOptions) -> Result<XMLDocument, String> {
+    let (names, rows) = read_csv_rows(input_file, header, encoding)?;
+
+    match csv_document(&names, &rows, &options.namespaces) {
+        Ok(root) => Ok(root),
+        Err(e) => Err(format!("Could not parse CSV file: {}", e)),
+    }
+}
+
+//Converts a CSV file to an XML file one record at a time, without reading the whole file into memory, returning the number of records
+pub fn csv_to_xml_streaming(input_file: String, output_file: String, header: Option<CSVHeader>, encoding: Option<Encoding>, options: &OutputOptions) -> Result<usize, String> {
+    let reader = match File::open(&input_file) {
+        Err(e) => return Err(format!("Could not open file {}: {}", input_file, e)),
+        Ok(file) => file,
+    };
+
+    let writer = match File::create(&output_file) {
+        Err(e) => return Err(format!("Could not create file {}: {}", output_file, e)),
+        Ok(file) => BufWriter::new(file),
+    };
+
+    csv_to_xml_stream(reader, writer, header, encoding, options)
+}
+
+//Rewrites an XML file with the layout, encoding and line endings chosen in the options, such as to re-indent or minify it
+pub fn xml_to_xml(input_file: String, output_file: String, options: &OutputOptions) -> Result<(), String> {
+    let root = read_document(&input_file)?;
+
+    if root.child_elements(root.root()).is_empty() {
+        return Err("No elements in XML file".to_owned());
+    }
+
+    write_document(&output_file, &root, options)
+}
+
+//Rewrites an XML file through a lossless parse, which gives back the same file if it is well formed
+pub fn xml_to_xml_lossless(input_file: String, output_file: String) -> Result<(), String> {
+    let document = read_lossless_document(&input_file)?;
+
+    write_lossless_document(&output_file, &document)
+}
+
+//Writes an XML file, or the first node chosen by an XPath or CSS selector, as canonical XML
+pub fn xml_to_canonical(input_file: String, output_file: String, selection: Option<String>, namespaces: &NamespaceMap, options: &CanonicalOptions) -> Result<(), String> {
+    let selection = match selection {
+        Some(expression) => Some(Selection::parse_with_namespaces(&expression, namespaces)?),
+        None => None,
+    };
+
+    //Canonical XML keeps the whitespace of the file, which reading a document otherwise trims
+    let root = read_whole_document(&input_file)?;
+
+    if root.child_elements(root.root()).is_empty() {
+        return Err("No elements in XML file".to_owned());
+    }
+
+    let node = match selection {
+        Some(selection) => match selection.evaluate(&root, root.root()).into_iter().next() {
+            Some(XPathItem::Node(node)) => node,
+            Some(_) => return Err("Only a node can be written as canonical XML".to_owned()),
+            None => return Err("Nothing was selected to write".to_owned()),
+        },
+        None => root.root(),
+    };
+
+    let canonical = canonicalize(&root, node, options);
+
+    //Canonical XML is always UTF-8 with '\n' line endings
+    write_xml_file(&output_file, &canonical, &OutputOptions::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    //Converts CSV text to XML text with the default dialect and output options
+    fn csv_text_to_xml(file_contents: &str, header: &CSVHeader) -> String {
+        let document = csv_parser(file_contents.to_owned(), &CSVDialect::new(), header, &NamespaceMap::new()).unwrap();
+        xml_formatter(xml_reverse_parser(&document), &OutputOptions::new())
+    }
+
+    #[test]
+    fn headerless_csv_gets_generated_column_names() {
+        let xml = csv_text_to_xml("1,2,3\n4,5,6\n", &CSVHeader::Generated);
+
+        assert!(xml.contains("<col1>1</col1>"), "{}", xml);
+        assert!(xml.contains("<col3>6</col3>"), "{}", xml);
+    }
+
+    #[test]
+    fn supplied_column_names_keep_the_first_line_as_data() {
+        let xml = csv_text_to_xml("1,2\n3,4\n", &CSVHeader::Supplied(vec!["a".to_owned(), " b ".to_owned()]));
+
+        assert!(xml.contains("<a>1</a>"), "{}", xml);
+        assert!(xml.contains("<b>4</b>"), "{}", xml);
+    }
+
+    #[test]
+    fn first_line_header_with_no_rows_is_an_error() {
+        assert!(csv_parser("a,b\n".to_owned(), &CSVDialect::new(), &CSVHeader::FirstLine, &NamespaceMap::new()).is_err());
+    }
+
+    #[test]
+    fn writes_the_chosen_encoding_and_line_endings() {
+        let mut options = OutputOptions::new();
+        options.set_encoding("UTF-16LE").unwrap();
+        options.set_line_ending("CRLF").unwrap();
+
+        let mut output: Vec<u8> = Vec::new();
+        {
+            let mut writer = EncodedWriter::new(&mut output, &options).unwrap();
+            writer.write_str("a\n").unwrap();
+        }
+
+        //UTF-16 is always written with a byte order mark
+        assert_eq!(output, vec![0xFF, 0xFE, b'a', 0x00, b'\r', 0x00, b'\n', 0x00]);
+    }
+
+    #[test]
+    fn only_xml_writes_character_references() {
+        let mut options = OutputOptions::new();
+        options.set_encoding("ISO-8859-1").unwrap();
+
+        let mut output: Vec<u8> = Vec::new();
+        {
+            let mut writer = EncodedWriter::new(&mut output, &options).unwrap();
+            writer.write_xml("<a>€</a>\n").unwrap();
+            assert!(writer.write_str("€,1\n").is_err());
+        }
+
+        assert_eq!(output, b"<a>&#x20AC;</a>\n".to_vec());
+    }
+
+    //Writes a file for a test into the temporary directory, returning its path
+    fn temp_file(name: &str, contents: &[u8]) -> String {
+        let path = env::temp_dir().join(format!("xmlparser-{}-{}", ::std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn external_entities_are_not_read_by_default() {
+        let secret_file = temp_file("secret.txt", b"secret");
+        let input_file = temp_file("xxe.xml", format!("<!DOCTYPE r [<!ENTITY x SYSTEM \"{}\">]><r><row><v>&x;</v></row></r>", secret_file).as_bytes());
+
+        let document = read_document(&input_file).unwrap();
+        let value = document.descendants(document.root()).find(|&node| document.name(node) == "v").unwrap();
+
+        assert_eq!(document.text(value), "&x;");
+    }
+
+    #[test]
+    fn validating_only_reads_entities_from_the_directory_of_the_document() {
+        let directory = env::temp_dir().join(format!("xmlparser-{}-entities", ::std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("inside.txt"), "inside").unwrap();
+        let secret_file = temp_file("outside.txt", b"outside");
+
+        let inside_file = directory.join("inside.xml");
+        fs::write(&inside_file, "<!DOCTYPE r [<!ELEMENT r (#PCDATA)><!ENTITY x SYSTEM \"inside.txt\">]><r>&x;</r>").unwrap();
+        assert!(validate_xml_with_dtd(&inside_file.to_string_lossy(), None).unwrap().is_empty());
+
+        let outside_file = directory.join("outside.xml");
+        fs::write(&outside_file, format!("<!DOCTYPE r [<!ELEMENT r (#PCDATA)><!ENTITY x SYSTEM \"{}\">]><r>&x;</r>", secret_file)).unwrap();
+        assert!(validate_xml_with_dtd(&outside_file.to_string_lossy(), None).unwrap_err().contains("is outside the directory of the document"));
+    }
+
+    #[test]
+    fn unmatched_bracket_in_declaration_is_an_error() {
+        assert_eq!(lexer("<!DOCTYPE a ]><a>1</a>".to_owned()).err(), Some("Unmatched ']' in declaration".to_owned()));
+        assert!(lexer("<!DOCTYPE a [ <!ENTITY e \"x\"> ]><a>1</a>".to_owned()).is_ok());
+    }
+
+    //An XML file in Windows-1252 whose first byte which is not ASCII comes after the first 8 KB
+    fn late_windows_1252_xml() -> Vec<u8> {
+        let mut bytes = b"<root>\n".to_vec();
+        for index in 0..400 {
+            bytes.extend_from_slice(format!("<r><a>{}</a><b>padding text</b></r>\n", index).as_bytes());
+        }
+        bytes.extend_from_slice(b"<r><a>x</a><b>caf\xE9 \x80</b></r>\n</root>\n");
+        bytes
+    }
+
+    #[test]
+    fn read_document_detects_windows_1252_after_the_first_buffer() {
+        let input_file = temp_file("late-1252.xml", &late_windows_1252_xml());
+        let document = read_document(&input_file).unwrap();
+
+        let last = *document.descendants(document.root()).collect::<Vec<NodeId>>().last().unwrap();
+        assert_eq!(document.text(last), "café €");
+    }
+
+    #[test]
+    fn xml_to_csv_detects_windows_1252_after_the_first_buffer() {
+        let input_file = temp_file("late-1252-csv.xml", &late_windows_1252_xml());
+        let output_file = temp_file("late-1252.csv", b"");
+
+        xml_to_csv(input_file, output_file.clone(), &OutputOptions::new()).unwrap();
+
+        let csv = fs::read_to_string(&output_file).unwrap();
+        assert!(csv.lines().last().unwrap().split(',').any(|cell| cell == "café €"), "{}", csv);
+    }
+
+    #[test]
+    fn mixed_content_keeps_text_in_place_among_children() {
+        let document = parser(&lexer("<p>one<b>two</b>three<!--c-->four</p>".to_owned()).unwrap()).unwrap();
+        let p = document.child_elements(document.root())[0];
+        assert_eq!(document.text(p), "onethreefour");
+
+        let mut output = Vec::new();
+        xml_writer::write_xml(&document, p, &mut output, &OutputOptions::new()).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let positions: Vec<usize> = ["one", "<b>two</b>", "three", "<!--c-->", "four"].iter().map(|part| output.find(part).unwrap()).collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]), "{}", output);
+    }
+
+    #[test]
+    fn xml_to_xml_reindents_and_minifies() {
+        let input_file = temp_file("reformat.xml", b"<a>\n<b x='1'>one</b>\n      <!--c-->\n</a>");
+        let output_file = input_file.replace(".xml", "-out.xml");
+
+        let mut options = OutputOptions::new();
+        options.indent = Indent::Spaces(4);
+        xml_to_xml(input_file.clone(), output_file.clone(), &options).unwrap();
+        assert!(fs::read_to_string(&output_file).unwrap().ends_with("<a>\n    <b x=\"1\">one</b>\n    <!--c-->\n</a>\n"));
+
+        options.compact = true;
+        xml_to_xml(input_file, output_file.clone(), &options).unwrap();
+        assert!(fs::read_to_string(&output_file).unwrap().ends_with("<a><b x=\"1\">one</b><!--c--></a>\n"));
+    }
+
+    #[test]
+    fn canonical_selection_uses_the_namespace_map() {
+        let input_file = temp_file("select.xml", b"<feed xmlns=\"urn:atom\"><entry id=\"1\"/></feed>");
+        let output_file = input_file.replace(".xml", "-c14n.xml");
+        let namespaces = NamespaceMap::parse("a=urn:atom").unwrap();
+
+        xml_to_canonical(input_file.clone(), output_file.clone(), Some("//a:entry".to_owned()), &namespaces, &CanonicalOptions::new()).unwrap();
+        assert_eq!(fs::read_to_string(&output_file).unwrap(), "<entry xmlns=\"urn:atom\" id=\"1\"></entry>");
+
+        assert!(xml_to_canonical(input_file, output_file, Some("//a:entry".to_owned()), &NamespaceMap::new(), &CanonicalOptions::new()).is_err());
+    }
+
+    #[test]
+    fn schema_errors_give_the_line_and_column() {
+        let schema_file = temp_file("count.xsd", b"<xs:schema xmlns:xs=\"http://www.w3.org/2001/XMLSchema\"><xs:element name=\"count\" type=\"xs:int\"/></xs:schema>");
+        let input_file = temp_file("count.xml", b"<?xml version=\"1.0\"?>\n  <count>many</count>");
+
+        let errors = validate_xml(&input_file, &schema_file).unwrap();
+        let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+
+        assert_eq!(errors, vec!["line 2, column 3: Invalid content of 'count': 'many' is not a valid int"]);
+    }
+
+    #[test]
+    fn csv_schema_matches_the_converted_xml() {
+        let input_file = temp_file("typed.csv", b"name;count;price\nwidget;3;1,5\ngadget;;2\n");
+        let output_file = input_file.replace(".csv", ".xml");
+        let schema_file = input_file.replace(".csv", ".xsd");
+
+        csv_to_xml_with_xsd(input_file, output_file.clone(), Some(schema_file.clone()), None, None, &OutputOptions::new()).unwrap();
+
+        let schema = fs::read_to_string(&schema_file).unwrap();
+        assert!(schema.contains("<xs:element name=\"count\" type=\"nullableInteger\">"), "{}", schema);
+        assert!(validate_xml(&output_file, &schema_file).unwrap().is_empty());
+    }
+
+    #[test]
+    fn describe_csv_types_the_columns_after_the_header() {
+        let input_file = temp_file("describe.csv", b"name,count\nwidget,3\ngadget,null\n");
+        let column_types = describe_csv(&input_file, None, None).unwrap();
+        let descriptions: Vec<String> = column_types.iter().map(|column| column.to_string()).collect();
+
+        assert_eq!(descriptions, vec!["name: string (100.0% of values, 0 of 2 null)", "count: integer (100.0% of values, 1 of 2 null)"]);
+    }
+
+    #[test]
+    fn describe_csv_names_columns_as_conversion_does() {
+        let input_file = temp_file("describe-names.csv", b"1,2\n3,4,5\n");
+
+        let names = |header: Option<CSVHeader>| -> Vec<String> {
+            describe_csv(&input_file, header, None).unwrap().into_iter().map(|column| column.name).collect()
+        };
+
+        assert_eq!(names(Some(CSVHeader::Generated)), vec!["col1", "col2", "col3"]);
+        assert_eq!(names(Some(CSVHeader::Supplied(vec![" a ".to_owned(), "b".to_owned()]))), vec!["a", "b"]);
+        assert!(describe_csv(&temp_file("describe-empty.csv", b""), Some(CSVHeader::FirstLine), None).is_err());
+    }
+}
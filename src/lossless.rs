@@ -0,0 +1,508 @@
+//
+//  Description  :    Lossless parsing, which keeps the exact text of a file so that it can be written back unchanged
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::collections::HashMap;
+use document::{XMLDocument, NodeId, NodeKind};
+use encoding::Encoding;
+use {XMLLexer, XMLTerm, lexer, parser, escape_text, escape_attribute};
+
+//A piece of markup as it was written in the file
+enum Markup {
+    StartTag(String, String),
+    EndTag(String, String),
+    Comment(String),
+    ProcessingInstruction(String, String),
+}
+
+//An attribute of a start tag as it was written, such as the ' id' and 'id = '1'' of '<game id = '1'>'
+struct RawAttribute {
+    space: String,
+    text: String,
+    quote: char,
+}
+
+//The text around a node in the file it was read from, along with the node as it was read so that edits can be found
+struct NodeTrivia {
+    parent: Option<NodeId>,
+    //Everything between the end of the previous piece of markup and the start of this node, such as indentation, text and CDATA sections
+    before: String,
+    //The whole start tag, comment or processing instruction
+    markup: String,
+    attributes: Vec<RawAttribute>,
+    //What follows the attributes of a start tag, such as ' />'
+    tag_end: String,
+    //Everything between the last child, or the start tag if there are none, and the end tag
+    inner_end: String,
+    //Empty for elements written as '<name/>'
+    end_tag: String,
+    name: String,
+    data: String,
+    attribute_values: Vec<(String, String)>,
+    children: Vec<NodeId>,
+}
+
+//A document which remembers the exact text it was read from. Writing it without edits gives back the same file,
+//and after edits only the parts which were changed are written differently.
+pub struct LosslessDocument {
+    document: XMLDocument,
+    trivia: HashMap<NodeId, NodeTrivia>,
+    encoding: Encoding,
+    bom: bool,
+}
+
+//Splits a file into pieces of markup, each with the text before it, followed by any text after the last one.
+//Declarations, document types and CDATA sections are not nodes, so are kept with the text around them.
+fn split_markup(source: &str) -> Result<(Vec<(String, Markup)>, String), String> {
+    let mut xml_lexer = XMLLexer::new();
+    let mut terms: Vec<XMLTerm> = Vec::new();
+    let mut pieces: Vec<(String, Markup)> = Vec::new();
+
+    let mut text_start: usize = 0;
+    let mut markup_start: usize = 0;
+
+    for (index, c) in source.char_indices() {
+        let was_in_markup = xml_lexer.in_markup();
+        xml_lexer.lex_char(c, &mut terms)?;
+        terms.clear();
+
+        if !was_in_markup && xml_lexer.in_markup() {
+            markup_start = index;
+        }
+        else if was_in_markup && !xml_lexer.in_markup() {
+            let raw = &source[markup_start..index + c.len_utf8()];
+
+            let markup = if raw.starts_with("<!--") {
+                Markup::Comment(raw.to_owned())
+            }
+            else if raw.starts_with("<!") || is_xml_declaration(raw) {
+                continue;
+            }
+            else if let Some(instruction) = raw.strip_prefix("<?") {
+                let target: String = instruction.chars().take_while(|&c| !c.is_whitespace() && c != '?').collect();
+                Markup::ProcessingInstruction(target, raw.to_owned())
+            }
+            else if let Some(end_tag) = raw.strip_prefix("</") {
+                Markup::EndTag(end_tag[..end_tag.len() - 1].trim().to_owned(), raw.to_owned())
+            }
+            else {
+                let name: String = raw[1..].chars().take_while(|&c| !c.is_whitespace() && c != '/' && c != '>').collect();
+                Markup::StartTag(name, raw.to_owned())
+            };
+
+            pieces.push((source[text_start..markup_start].to_owned(), markup));
+            text_start = index + c.len_utf8();
+        }
+    }
+
+    xml_lexer.finish(&mut terms)?;
+
+    Ok((pieces, source[text_start..].to_owned()))
+}
+
+fn is_xml_declaration(raw: &str) -> bool {
+    raw.starts_with("<?xml") && raw[5..].starts_with(|c: char| c.is_whitespace() || c == '?')
+}
+
+//Splits a start tag into its attributes as written and what follows them
+fn split_start_tag(raw: &str, name: &str) -> (Vec<RawAttribute>, String) {
+    let mut attributes: Vec<RawAttribute> = Vec::new();
+    let mut rest = &raw[1 + name.len()..];
+
+    loop {
+        let space_length = rest.len() - rest.trim_start().len();
+        let after_space = &rest[space_length..];
+
+        if after_space.is_empty() || after_space.starts_with('>') || after_space.starts_with('/') {
+            break;
+        }
+
+        //The lexer has already checked that every attribute has a quoted value
+        let quote_start = match after_space.find(['"', '\'']) {
+            Some(quote_start) => quote_start,
+            None => break,
+        };
+        let quote = after_space[quote_start..].chars().next().unwrap_or('"');
+        let quote_end = match after_space[quote_start + 1..].find(quote) {
+            Some(quote_end) => quote_start + 1 + quote_end,
+            None => break,
+        };
+
+        attributes.push(RawAttribute {
+            space: rest[..space_length].to_owned(),
+            text: after_space[..quote_end + 1].to_owned(),
+            quote,
+        });
+
+        rest = &after_space[quote_end + 1..];
+    }
+
+    (attributes, rest.to_owned())
+}
+
+//The whitespace which starts the last line of some text, such as the indentation before a node
+fn line_indent(text: &str) -> Option<&str> {
+    let mut start = text.rfind('\n')?;
+
+    //Keep the '\r' of a '\r\n', so new lines end as the others in the file do
+    if text[..start].ends_with('\r') {
+        start -= 1;
+    }
+
+    if text[start..].trim().is_empty() {
+        Some(&text[start..])
+    }
+    else {
+        None
+    }
+}
+
+//The whitespace at the end of some text, which is all that is kept around text which was replaced
+fn trailing_space(text: &str) -> &str {
+    &text[text.trim_end().len()..]
+}
+
+fn quoted_value(value: &str, quote: char) -> String {
+    if quote == '\'' {
+        format!("'{}'", escape_text(value).replace('\'', "&apos;"))
+    }
+    else {
+        format!("\"{}\"", escape_attribute(value))
+    }
+}
+
+impl LosslessDocument {
+    //Reads a file's text, keeping what is needed to write it back exactly
+    pub fn parse(source: &str, encoding: Encoding, bom: bool) -> Result<LosslessDocument, String> {
+        let document = parser(&lexer(source.to_owned())?)?;
+        let (pieces, trailing_text) = split_markup(source)?;
+
+        let mut lossless = LosslessDocument {
+            document,
+            trivia: HashMap::new(),
+            encoding,
+            bom,
+        };
+
+        let mut pieces = pieces.into_iter();
+        let root = lossless.document.root();
+
+        for child in lossless.markup_children(root) {
+            lossless.record_node(child, &mut pieces)?;
+        }
+
+        let root_trivia = lossless.snapshot(root, None, String::new(), String::new(), trailing_text);
+        lossless.trivia.insert(root, root_trivia);
+
+        Ok(lossless)
+    }
+
+    //The children of a node which were written as markup. Text is kept in the gaps between them.
+    fn markup_children(&self, node: NodeId) -> Vec<NodeId> {
+        self.document.children(node).iter().cloned().filter(|&child| self.document.kind(child) != NodeKind::Text).collect()
+    }
+
+    fn snapshot(&self, node: NodeId, parent: Option<NodeId>, before: String, markup: String, inner_end: String) -> NodeTrivia {
+        NodeTrivia {
+            parent,
+            before,
+            markup,
+            attributes: Vec::new(),
+            tag_end: String::new(),
+            inner_end,
+            end_tag: String::new(),
+            name: self.document.name(node).to_owned(),
+            data: self.document.text(node),
+            attribute_values: self.document.attributes(node).iter().map(|&(name, value)| (name.to_owned(), value.to_owned())).collect(),
+            children: self.markup_children(node),
+        }
+    }
+
+    //Matches a node and everything below it to the markup of the file, which comes in the same order
+    fn record_node<I: Iterator<Item = (String, Markup)>>(&mut self, node: NodeId, pieces: &mut I) -> Result<(), String> {
+        let name = self.document.name(node).to_owned();
+        let parent = self.document.parent(node);
+
+        let (before, markup) = match pieces.next() {
+            Some(piece) => piece,
+            None => return Err(format!("Could not find {} in the file", name)),
+        };
+
+        let trivia = match (self.document.kind(node), markup) {
+            (NodeKind::Comment, Markup::Comment(raw)) => self.snapshot(node, parent, before, raw, String::new()),
+            (NodeKind::ProcessingInstruction, Markup::ProcessingInstruction(ref target, ref raw)) if *target == name => {
+                self.snapshot(node, parent, before, raw.clone(), String::new())
+            },
+            (NodeKind::Element, Markup::StartTag(ref tag_name, ref raw)) if *tag_name == name => {
+                let (attributes, tag_end) = split_start_tag(raw, &name);
+
+                if attributes.len() != self.document.attributes(node).len() {
+                    return Err(format!("Could not read the attributes of {} as written", name));
+                }
+
+                let mut inner_end = String::new();
+                let mut end_tag = String::new();
+
+                //An element written as '<name/>' has no end tag to look for
+                if !raw.ends_with("/>") {
+                    for child in self.markup_children(node) {
+                        self.record_node(child, pieces)?;
+                    }
+
+                    match pieces.next() {
+                        Some((text, Markup::EndTag(ref end_name, ref raw))) if *end_name == name => {
+                            inner_end = text;
+                            end_tag = raw.clone();
+                        },
+                        _ => return Err(format!("Could not find the closing tag of {} in the file", name)),
+                    }
+                }
+
+                let mut trivia = self.snapshot(node, parent, before, raw.clone(), inner_end);
+                trivia.attributes = attributes;
+                trivia.tag_end = tag_end;
+                trivia.end_tag = end_tag;
+                trivia
+            },
+            _ => return Err(format!("Could not match {} to the markup of the file", name)),
+        };
+
+        self.trivia.insert(node, trivia);
+
+        Ok(())
+    }
+
+    pub fn document(&self) -> &XMLDocument {
+        &self.document
+    }
+
+    //The tree to edit. Nodes added or moved are written with the indentation of their new neighbours.
+    pub fn document_mut(&mut self) -> &mut XMLDocument {
+        &mut self.document
+    }
+
+    //The encoding of the file that was read
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    //Whether the file that was read started with a byte order mark
+    pub fn bom(&self) -> bool {
+        self.bom
+    }
+
+    //The text of the document, which is the text that was read if nothing has been edited
+    pub fn to_xml(&self) -> String {
+        let mut output = String::new();
+        self.write_children(self.document.root(), &mut output);
+        output
+    }
+
+    //The indentation of a node, taken from the text before it in the file
+    fn indent_of(&self, node: NodeId) -> String {
+        match self.trivia.get(&node).and_then(|trivia| line_indent(&trivia.before)) {
+            Some(indent) => indent.to_owned(),
+            None if node == self.document.root() => String::new(),
+            None => "\n".to_owned(),
+        }
+    }
+
+    //The text to write before a child which was not in this place when the file was read, copying the indentation of its siblings
+    fn new_child_gap(&self, parent: NodeId) -> String {
+        let sibling_indent = self.document.children(parent).iter()
+            .filter_map(|child| self.trivia.get(child))
+            .filter(|trivia| trivia.parent == Some(parent))
+            .filter_map(|trivia| line_indent(&trivia.before))
+            .next();
+
+        match sibling_indent {
+            Some(indent) => indent.to_owned(),
+            None if parent == self.document.root() => "\n".to_owned(),
+            None => format!("{}  ", self.indent_of(parent)),
+        }
+    }
+
+    //Writes the children of a node along with the text between them, which holds the node's own text
+    fn write_children(&self, node: NodeId, output: &mut String) {
+        let children = self.markup_children(node);
+        let trivia = self.trivia.get(&node);
+
+        let mut gaps: Vec<String> = children.iter()
+            .map(|child| match self.trivia.get(child) {
+                Some(child_trivia) if child_trivia.parent == Some(node) => child_trivia.before.clone(),
+                _ => self.new_child_gap(node),
+            })
+            .collect();
+
+        match trivia {
+            //An element which was read without children keeps its text in front of the first one added
+            Some(trivia) if trivia.children.is_empty() && !children.is_empty() => {
+                gaps[0] = format!("{}{}", trivia.inner_end, gaps[0]);
+                gaps.push(self.indent_of(node));
+            },
+            Some(trivia) => gaps.push(trivia.inner_end.clone()),
+            None if !children.is_empty() => gaps.push(self.indent_of(node)),
+            None => gaps.push(String::new()),
+        }
+
+        //If the text was changed, it replaces the first text in the file, and the rest of the old text is dropped
+        let data = self.document.text(node);
+
+        if trivia.map_or(!data.is_empty(), |trivia| trivia.data != data) {
+            let text_index = gaps.iter().position(|gap| !gap.trim().is_empty()).unwrap_or(0);
+
+            for (index, gap) in gaps.iter_mut().enumerate() {
+                *gap = if index != text_index {
+                    trailing_space(gap).to_owned()
+                }
+                else if gap.trim().is_empty() {
+                    format!("{}{}", escape_text(&data), gap)
+                }
+                else {
+                    let leading_space = &gap[..gap.len() - gap.trim_start().len()];
+                    format!("{}{}{}", leading_space, escape_text(&data), trailing_space(gap))
+                };
+            }
+        }
+
+        for (index, &child) in children.iter().enumerate() {
+            output.push_str(&gaps[index]);
+            self.write_node(child, output);
+        }
+
+        output.push_str(&gaps[children.len()]);
+    }
+
+    fn write_node(&self, node: NodeId, output: &mut String) {
+        let document = &self.document;
+        let trivia = self.trivia.get(&node);
+
+        match document.kind(node) {
+            NodeKind::Text => output.push_str(&escape_text(document.data(node))),
+            NodeKind::Comment => match trivia {
+                Some(trivia) if trivia.data == document.data(node) => output.push_str(&trivia.markup),
+                _ => output.push_str(&format!("<!--{}-->", document.data(node))),
+            },
+            NodeKind::ProcessingInstruction => match trivia {
+                Some(trivia) if trivia.name == document.name(node) && trivia.data == document.data(node) => output.push_str(&trivia.markup),
+                _ if !document.data(node).is_empty() => output.push_str(&format!("<?{} {}?>", document.name(node), document.data(node))),
+                _ => output.push_str(&format!("<?{}?>", document.name(node))),
+            },
+            NodeKind::Element => self.write_element(node, trivia, output),
+        }
+    }
+
+    fn write_element(&self, node: NodeId, trivia: Option<&NodeTrivia>, output: &mut String) {
+        let document = &self.document;
+        let name = document.name(node);
+        let attributes = document.attributes(node);
+        let is_empty = document.children(node).is_empty();
+
+        let trivia = match trivia {
+            Some(trivia) => trivia,
+            None => {
+                output.push('<');
+                output.push_str(name);
+                for &(attribute_name, value) in &attributes {
+                    output.push_str(&format!(" {}={}", attribute_name, quoted_value(value, '"')));
+                }
+                output.push('>');
+
+                self.write_children(node, output);
+                output.push_str(&format!("</{}>", name));
+                return;
+            },
+        };
+
+        let self_closing = trivia.end_tag.is_empty();
+        let unchanged = trivia.name == name && attributes.len() == trivia.attribute_values.len() &&
+            attributes.iter().zip(&trivia.attribute_values).all(|(&(attribute_name, value), (old_name, old_value))| attribute_name == old_name && value == old_value);
+
+        if unchanged && (is_empty || !self_closing) {
+            output.push_str(&trivia.markup);
+        }
+        else {
+            //Rebuild the start tag, keeping each attribute which was not changed as it was written
+            output.push('<');
+            output.push_str(name);
+
+            let first_quote = trivia.attributes.first().map_or('"', |attribute| attribute.quote);
+            let new_space = match trivia.attributes.last() {
+                Some(attribute) if attribute.space.contains('\n') => attribute.space.clone(),
+                _ => " ".to_owned(),
+            };
+
+            for &(attribute_name, value) in &attributes {
+                let original = trivia.attribute_values.iter().position(|(old_name, _)| old_name == attribute_name);
+
+                match original {
+                    Some(index) if trivia.attribute_values[index].1 == value => {
+                        output.push_str(&trivia.attributes[index].space);
+                        output.push_str(&trivia.attributes[index].text);
+                    },
+                    Some(index) => {
+                        output.push_str(&trivia.attributes[index].space);
+                        output.push_str(&format!("{}={}", attribute_name, quoted_value(value, trivia.attributes[index].quote)));
+                    },
+                    None => output.push_str(&format!("{}{}={}", new_space, attribute_name, quoted_value(value, first_quote))),
+                }
+            }
+
+            //An element written as '<name/>' which now has something inside it needs an end tag
+            if self_closing && !is_empty {
+                output.push_str(trivia.tag_end.trim_end_matches("/>").trim_end());
+                output.push('>');
+            }
+            else {
+                output.push_str(&trivia.tag_end);
+            }
+        }
+
+        if self_closing && is_empty {
+            return;
+        }
+
+        self.write_children(node, output);
+
+        if trivia.name == name && !self_closing {
+            output.push_str(&trivia.end_tag);
+        }
+        else {
+            output.push_str(&format!("</{}>", name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "<?xml version='1.0'?>\r\n<!-- games -->\r\n<games>\r\n    <game platform='PC'  id=\"1\">Doom</game>\r\n    <game platform=\"SNES\"/>\r\n</games>\r\n";
+
+    fn parse(source: &str) -> LosslessDocument {
+        LosslessDocument::parse(source, Encoding::UTF8, false).unwrap()
+    }
+
+    #[test]
+    fn unedited_documents_are_written_back_exactly() {
+        assert_eq!(parse(SOURCE).to_xml(), SOURCE);
+    }
+
+    #[test]
+    fn edits_only_change_what_was_edited() {
+        let mut lossless = parse(SOURCE);
+        {
+            let document = lossless.document_mut();
+            let games = document.child_elements(document.root())[0];
+            let doom = document.child_elements(games)[0];
+
+            document.set_attribute(doom, "platform", "DOS");
+            let added = document.append_element(games, "game");
+            document.set_text(added, "Zelda");
+        }
+
+        assert_eq!(lossless.to_xml(), SOURCE.replace("'PC'", "'DOS'").replace("\"SNES\"/>\r\n", "\"SNES\"/>\r\n    <game>Zelda</game>\r\n"));
+    }
+}
@@ -0,0 +1,279 @@
+//
+//  Description  :    XML namespaces, resolving prefixes and matching names by namespace URI and local name
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use document::{XMLDocument, NodeId};
+
+//The namespace the 'xml' prefix is always bound to
+pub const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+//Splits a name such as 'x:book' into its prefix and local name, where names without a prefix have an empty one
+pub fn split_name(name: &str) -> (&str, &str) {
+    match name.find(':') {
+        Some(index) => (&name[..index], &name[index + 1..]),
+        None => ("", name),
+    }
+}
+
+//The prefix declared by an attribute such as 'xmlns:x', which is empty for the default namespace
+pub fn declared_prefix(name: &str) -> Option<&str> {
+    if name == "xmlns" {
+        Some("")
+    }
+    else {
+        name.strip_prefix("xmlns:")
+    }
+}
+
+//Splits a name written as '{uri}local' into its namespace URI and local name
+pub fn split_expanded_name(name: &str) -> Option<(&str, &str)> {
+    if !name.starts_with('{') {
+        return None;
+    }
+
+    name.find('}').map(|end| (&name[1..end], &name[end + 1..]))
+}
+
+//Prefixes chosen by a user to stand for namespace URIs in queries and column names
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct NamespaceMap {
+    prefixes: Vec<(String, String)>,
+}
+
+impl Default for NamespaceMap {
+    fn default() -> NamespaceMap {
+        NamespaceMap::new()
+    }
+}
+
+impl NamespaceMap {
+    pub fn new() -> NamespaceMap {
+        NamespaceMap {
+            prefixes: Vec::new(),
+        }
+    }
+
+    //Reads a list such as 'atom=http://www.w3.org/2005/Atom,soap=http://schemas.xmlsoap.org/soap/envelope/'
+    pub fn parse(text: &str) -> Result<NamespaceMap, String> {
+        let mut map = NamespaceMap::new();
+
+        for binding in text.split(',').map(|binding| binding.trim()).filter(|binding| !binding.is_empty()) {
+            let mut parts = binding.splitn(2, '=');
+            let prefix = parts.next().unwrap_or("").trim();
+
+            match parts.next() {
+                Some(uri) if !prefix.is_empty() => map.insert(prefix, uri.trim()),
+                _ => return Err(format!("Expected 'prefix=uri' in namespace list, found: {}", binding)),
+            }
+        }
+
+        Ok(map)
+    }
+
+    //Binds a prefix, replacing any earlier binding of it
+    pub fn insert(&mut self, prefix: &str, uri: &str) {
+        match self.prefixes.iter_mut().find(|&&mut (ref bound, _)| bound == prefix) {
+            Some(binding) => binding.1 = uri.to_owned(),
+            None => self.prefixes.push((prefix.to_owned(), uri.to_owned())),
+        }
+    }
+
+    pub fn get(&self, prefix: &str) -> Option<&str> {
+        if prefix == "xml" {
+            return Some(XML_NAMESPACE);
+        }
+
+        self.prefixes.iter().find(|&(bound, _)| bound == prefix).map(|(_, uri)| uri.as_str())
+    }
+
+    //The prefix bound to a namespace URI, if any
+    pub fn prefix_of(&self, uri: &str) -> Option<&str> {
+        self.prefixes.iter().find(|&(_, bound)| bound == uri).map(|(prefix, _)| prefix.as_str())
+    }
+
+    pub fn bindings(&self) -> &[(String, String)] {
+        &self.prefixes
+    }
+}
+
+//A name to look for in a document
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum NameTest {
+    //A name as it is written in the file, such as 'x:book'
+    Written(String),
+    //A namespace URI and local name, written '{uri}book' or with a prefix from the namespace map. No namespace is an empty URI.
+    Expanded(String, String),
+    //Every name in a namespace, written '{uri}*' or 'x:*'
+    AnyInNamespace(String),
+    //A local name in any namespace or none, written '*:book', or '*|book' in CSS
+    LocalName(String),
+}
+
+impl NameTest {
+    //Reads a name from a query, resolving its prefix if the namespace map has it
+    pub fn parse(name: &str, namespaces: &NamespaceMap) -> Result<NameTest, String> {
+        let (uri, local_name) = match split_expanded_name(name) {
+            Some((uri, local_name)) => (uri, local_name),
+            None => {
+                let (prefix, local_name) = split_name(name);
+
+                if prefix == "*" && !local_name.is_empty() && local_name != "*" {
+                    return Ok(NameTest::LocalName(local_name.to_owned()));
+                }
+
+                match namespaces.get(prefix) {
+                    Some(uri) if !prefix.is_empty() => (uri, local_name),
+                    _ if local_name == "*" => return Err(format!("Unknown namespace prefix in {}", name)),
+                    _ => return Ok(NameTest::Written(name.to_owned())),
+                }
+            },
+        };
+
+        if local_name == "*" {
+            Ok(NameTest::AnyInNamespace(uri.to_owned()))
+        }
+        else if local_name.is_empty() {
+            Err(format!("Missing local name in {}", name))
+        }
+        else {
+            Ok(NameTest::Expanded(uri.to_owned(), local_name.to_owned()))
+        }
+    }
+
+    fn matches_name(&self, name: &str, namespace: Option<&str>, local_name: &str) -> bool {
+        match *self {
+            NameTest::Written(ref written) => name == written,
+            NameTest::Expanded(ref uri, ref test_name) => namespace.unwrap_or("") == uri && local_name == test_name,
+            NameTest::AnyInNamespace(ref uri) => namespace.unwrap_or("") == uri,
+            NameTest::LocalName(ref test_name) => local_name == test_name,
+        }
+    }
+
+    //Checks the name of an element
+    pub fn matches(&self, document: &XMLDocument, node: NodeId) -> bool {
+        document.is_element(node) && self.matches_name(document.name(node), document.namespace(node), document.local_name(node))
+    }
+
+    //Checks the name of an attribute of an element
+    pub fn matches_attribute(&self, document: &XMLDocument, node: NodeId, name: &str) -> bool {
+        self.matches_name(name, document.attribute_namespace(node, name), split_name(name).1)
+    }
+}
+
+//The name of an element as a column title, '{uri}local' if it is in a namespace, or 'prefix:local' if the map has a prefix for it
+pub fn column_name(document: &XMLDocument, node: NodeId, namespaces: &NamespaceMap) -> String {
+    match document.namespace(node) {
+        Some(uri) => match namespaces.prefix_of(uri) {
+            Some(prefix) => format!("{}:{}", prefix, document.local_name(node)),
+            None => format!("{{{}}}{}", uri, document.local_name(node)),
+        },
+        None => document.name(node).to_owned(),
+    }
+}
+
+//Turns column titles written '{uri}local' or 'prefix:local' into element names, using the prefixes of the map and making up
+//prefixes for other namespaces. Also gives the declarations the outermost element needs for the prefixes used.
+pub fn qualify_names(names: &[String], namespaces: &NamespaceMap) -> (Vec<String>, Vec<(String, String)>) {
+    let mut qualified_names: Vec<String> = Vec::new();
+    let mut declarations: Vec<(String, String)> = Vec::new();
+
+    for name in names {
+        let (uri, local_name) = match split_expanded_name(name) {
+            Some((uri, local_name)) => (uri, local_name),
+            None => {
+                let (prefix, local_name) = split_name(name);
+
+                match namespaces.get(prefix) {
+                    Some(uri) if !prefix.is_empty() && prefix != "xml" => (uri, local_name),
+                    _ => {
+                        qualified_names.push(name.clone());
+                        continue;
+                    },
+                }
+            },
+        };
+
+        if uri.is_empty() {
+            qualified_names.push(local_name.to_owned());
+            continue;
+        }
+
+        let declared = declarations.iter().find(|&(_, declared_uri)| declared_uri == uri).map(|(prefix, _)| prefix.clone());
+        let prefix = match declared {
+            Some(prefix) => prefix,
+            None => {
+                let prefix = match namespaces.prefix_of(uri) {
+                    Some(prefix) => prefix.to_owned(),
+                    None => {
+                        //Make up a prefix which is not used for anything else
+                        let mut number = 1;
+                        while namespaces.get(&format!("ns{}", number)).is_some() || declarations.iter().any(|(declared, _)| *declared == format!("ns{}", number)) {
+                            number += 1;
+                        }
+                        format!("ns{}", number)
+                    },
+                };
+
+                declarations.push((prefix.clone(), uri.to_owned()));
+                prefix
+            },
+        };
+
+        qualified_names.push(format!("{}:{}", prefix, local_name));
+    }
+
+    let declarations = declarations.into_iter().map(|(prefix, uri)| (format!("xmlns:{}", prefix), uri)).collect();
+
+    (qualified_names, declarations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {lexer, parser};
+
+    const FEED: &str = "<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:m=\"urn:m\"><entry m:id=\"1\"><m:rating xmlns:m=\"urn:other\"/><plain xmlns=\"\"/></entry></feed>";
+
+    #[test]
+    fn elements_take_the_namespace_in_scope_for_their_prefix() {
+        let document = parser(&lexer(FEED.to_owned()).unwrap()).unwrap();
+        let feed = document.child_elements(document.root())[0];
+        let entry = document.child_elements(feed)[0];
+        let children = document.child_elements(entry);
+
+        assert_eq!(document.namespace(entry), Some("http://www.w3.org/2005/Atom"));
+        assert_eq!(document.attribute_namespace(entry, "m:id"), Some("urn:m"));
+        assert_eq!(document.namespace(children[0]), Some("urn:other"));
+        assert_eq!(document.local_name(children[0]), "rating");
+        assert_eq!(document.namespace(children[1]), None);
+    }
+
+    #[test]
+    fn name_tests_resolve_prefixes_from_the_map() {
+        let document = parser(&lexer(FEED.to_owned()).unwrap()).unwrap();
+        let feed = document.child_elements(document.root())[0];
+        let namespaces = NamespaceMap::parse("a=http://www.w3.org/2005/Atom, o=urn:other").unwrap();
+
+        assert!(NameTest::parse("a:feed", &namespaces).unwrap().matches(&document, feed));
+        assert!(NameTest::parse("{http://www.w3.org/2005/Atom}feed", &namespaces).unwrap().matches(&document, feed));
+        assert!(NameTest::parse("*:feed", &namespaces).unwrap().matches(&document, feed));
+        assert!(!NameTest::parse("o:feed", &namespaces).unwrap().matches(&document, feed));
+        assert!(NameTest::parse("x:*", &namespaces).is_err());
+        assert!(NamespaceMap::parse("a").is_err());
+    }
+
+    #[test]
+    fn column_titles_become_prefixed_names_with_declarations() {
+        let namespaces = NamespaceMap::parse("a=urn:a").unwrap();
+        let titles = vec!["{urn:a}x".to_owned(), "{urn:b}y".to_owned(), "a:z".to_owned(), "{}w".to_owned(), "v".to_owned()];
+        let (names, declarations) = qualify_names(&titles, &namespaces);
+
+        assert_eq!(names, vec!["a:x", "ns1:y", "a:z", "w", "v"]);
+        assert_eq!(declarations, vec![("xmlns:a".to_owned(), "urn:a".to_owned()), ("xmlns:ns1".to_owned(), "urn:b".to_owned())]);
+    }
+}
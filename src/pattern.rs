@@ -0,0 +1,426 @@
+//
+//  Description  :    The regular expressions of XML Schema patterns, which must match the whole of a value
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use validation::match_repeated;
+
+//A set of characters, such as '[a-z]', '\d' or '.'
+#[derive(Debug)]
+#[derive(Clone)]
+enum CharClass {
+    Char(char),
+    Range(char, char),
+    //Any character except line breaks
+    Any,
+    Digit,
+    Space,
+    Word,
+    NameStart,
+    NameChar,
+    Category(String),
+    Not(Box<CharClass>),
+    Union(Vec<CharClass>),
+    //'[a-z-[aeiou]]', every character of the first class which is not in the second
+    Subtract(Box<CharClass>, Box<CharClass>),
+}
+
+impl CharClass {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            CharClass::Char(expected) => c == expected,
+            CharClass::Range(first, last) => c >= first && c <= last,
+            CharClass::Any => c != '\n' && c != '\r',
+            CharClass::Digit => c.is_numeric(),
+            CharClass::Space => c == ' ' || c == '\t' || c == '\n' || c == '\r',
+            //Everything except punctuation, separators and control characters
+            CharClass::Word => c.is_alphanumeric() || "$+<=>^`|~".contains(c),
+            CharClass::NameStart => c.is_alphabetic() || c == '_' || c == ':',
+            CharClass::NameChar => c.is_alphanumeric() || c == '.' || c == '-' || c == '_' || c == ':',
+            CharClass::Category(ref category) => match category.as_str() {
+                "L" => c.is_alphabetic(),
+                "Lu" => c.is_uppercase(),
+                "Ll" => c.is_lowercase(),
+                "N" | "Nd" => c.is_numeric(),
+                "P" => c.is_ascii_punctuation() && !"$+<=>^`|~".contains(c),
+                "S" => "$+<=>^`|~".contains(c),
+                "Z" | "Zs" => c.is_whitespace() && !c.is_control(),
+                "C" | "Cc" => c.is_control(),
+                _ => false,
+            },
+            CharClass::Not(ref class) => !class.matches(c),
+            CharClass::Union(ref classes) => classes.iter().any(|class| class.matches(c)),
+            CharClass::Subtract(ref class, ref removed) => class.matches(c) && !removed.matches(c),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum Piece {
+    Class(CharClass),
+    //Alternatives, each a sequence of pieces
+    Group(Vec<Vec<Piece>>),
+    Repeat(Box<Piece>, usize, Option<usize>),
+}
+
+//A compiled pattern, such as '[A-Z]{3}-\d+'
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Pattern {
+    expression: String,
+    alternatives: Vec<Vec<Piece>>,
+}
+
+//Reads a pattern one character at a time
+struct PatternReader<'a> {
+    expression: &'a str,
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl<'a> PatternReader<'a> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {} of pattern {}", message, self.position, self.expression)
+    }
+
+    //Reads alternatives separated by '|', up to the end of the pattern or a closing ')'
+    fn alternatives(&mut self) -> Result<Vec<Vec<Piece>>, String> {
+        let mut alternatives: Vec<Vec<Piece>> = vec![Vec::new()];
+
+        loop {
+            match self.peek() {
+                None | Some(')') => break,
+                Some('|') => {
+                    self.position += 1;
+                    alternatives.push(Vec::new());
+                },
+                Some(_) => {
+                    let piece = self.piece()?;
+                    if let Some(alternative) = alternatives.last_mut() {
+                        alternative.push(piece);
+                    }
+                },
+            }
+        }
+
+        Ok(alternatives)
+    }
+
+    fn piece(&mut self) -> Result<Piece, String> {
+        let atom = match self.peek() {
+            Some('(') => {
+                self.position += 1;
+                let alternatives = self.alternatives()?;
+                if !self.eat(')') {
+                    return Err(self.error("Expected ')'"));
+                }
+                Piece::Group(alternatives)
+            },
+            Some('[') => {
+                self.position += 1;
+                Piece::Class(self.class_expression()?)
+            },
+            Some('.') => {
+                self.position += 1;
+                Piece::Class(CharClass::Any)
+            },
+            Some('\\') => {
+                self.position += 1;
+                Piece::Class(self.escape()?)
+            },
+            Some(c) if "?*+{}]".contains(c) => return Err(self.error(&format!("Unexpected '{}'", c))),
+            Some(c) => {
+                self.position += 1;
+                Piece::Class(CharClass::Char(c))
+            },
+            None => return Err(self.error("Unexpected end")),
+        };
+
+        let (min, max) = match self.peek() {
+            Some('?') => (0, Some(1)),
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('{') => {
+                self.position += 1;
+                return self.quantity(atom);
+            },
+            _ => return Ok(atom),
+        };
+
+        self.position += 1;
+        Ok(Piece::Repeat(Box::new(atom), min, max))
+    }
+
+    //Reads the rest of a quantity such as '{2,5}'
+    fn quantity(&mut self, atom: Piece) -> Result<Piece, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c != '}') {
+            self.position += 1;
+        }
+        if !self.eat('}') {
+            return Err(self.error("Expected '}'"));
+        }
+
+        let quantity: String = self.chars[start..self.position - 1].iter().collect();
+        let mut parts = quantity.splitn(2, ',');
+        let min = parts.next().unwrap_or("").trim().parse::<usize>();
+        let max = match parts.next() {
+            Some(max) if max.trim().is_empty() => Ok(None),
+            Some(max) => max.trim().parse::<usize>().map(Some),
+            None => min.clone().map(Some),
+        };
+
+        match (min, max) {
+            (Ok(min), Ok(max)) if max.is_none_or(|max| max >= min) => Ok(Piece::Repeat(Box::new(atom), min, max)),
+            _ => Err(self.error(&format!("Invalid quantity {{{}}}", quantity))),
+        }
+    }
+
+    //Reads the character or class after a '\'
+    fn escape(&mut self) -> Result<CharClass, String> {
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Err(self.error("Unterminated escape")),
+        };
+        self.position += 1;
+
+        Ok(match c {
+            'n' => CharClass::Char('\n'),
+            'r' => CharClass::Char('\r'),
+            't' => CharClass::Char('\t'),
+            'd' => CharClass::Digit,
+            'D' => CharClass::Not(Box::new(CharClass::Digit)),
+            's' => CharClass::Space,
+            'S' => CharClass::Not(Box::new(CharClass::Space)),
+            'w' => CharClass::Word,
+            'W' => CharClass::Not(Box::new(CharClass::Word)),
+            'i' => CharClass::NameStart,
+            'I' => CharClass::Not(Box::new(CharClass::NameStart)),
+            'c' => CharClass::NameChar,
+            'C' => CharClass::Not(Box::new(CharClass::NameChar)),
+            'p' | 'P' => {
+                if !self.eat('{') {
+                    return Err(self.error("Expected '{' after \\p"));
+                }
+
+                let start = self.position;
+                while self.peek().is_some_and(|c| c != '}') {
+                    self.position += 1;
+                }
+                let category: String = self.chars[start..self.position].iter().collect();
+
+                if !self.eat('}') {
+                    return Err(self.error("Expected '}'"));
+                }
+                if !["L", "Lu", "Ll", "N", "Nd", "P", "S", "Z", "Zs", "C", "Cc"].contains(&category.as_str()) {
+                    return Err(self.error(&format!("Unsupported character category {}", category)));
+                }
+
+                if c == 'p' { CharClass::Category(category) } else { CharClass::Not(Box::new(CharClass::Category(category))) }
+            },
+            c if "\\|.-^?*+{}()[]".contains(c) => CharClass::Char(c),
+            c => return Err(self.error(&format!("Unknown escape \\{}", c))),
+        })
+    }
+
+    //Reads the inside of '[...]' after the '['
+    fn class_expression(&mut self) -> Result<CharClass, String> {
+        let negated = self.eat('^');
+        let mut classes: Vec<CharClass> = Vec::new();
+        let mut subtracted: Option<CharClass> = None;
+
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("Expected ']'")),
+            };
+
+            if c == ']' && !classes.is_empty() {
+                self.position += 1;
+                break;
+            }
+
+            //A '-' before a '[' subtracts a class, which must end this one
+            if c == '-' && self.chars.get(self.position + 1) == Some(&'[') {
+                self.position += 2;
+                subtracted = Some(self.class_expression()?);
+
+                if !self.eat(']') {
+                    return Err(self.error("Expected ']' after a subtracted class"));
+                }
+                break;
+            }
+
+            self.position += 1;
+            let first = if c == '\\' { self.escape()? } else { CharClass::Char(c) };
+
+            //A range such as 'a-z', where a '-' just before the ']' is itself a character
+            let is_range = self.peek() == Some('-') && self.chars.get(self.position + 1).is_some_and(|&next| next != ']' && next != '[');
+
+            match first {
+                CharClass::Char(first) if is_range => {
+                    self.position += 1;
+
+                    let last = match self.peek() {
+                        Some('\\') => {
+                            self.position += 1;
+                            match self.escape()? {
+                                CharClass::Char(last) => last,
+                                _ => return Err(self.error("A range must end with a single character")),
+                            }
+                        },
+                        Some(last) => { self.position += 1; last },
+                        None => return Err(self.error("Expected ']'")),
+                    };
+
+                    if last < first {
+                        return Err(self.error(&format!("Invalid range {}-{}", first, last)));
+                    }
+                    classes.push(CharClass::Range(first, last));
+                },
+                first => classes.push(first),
+            }
+        }
+
+        let mut class = CharClass::Union(classes);
+        if negated {
+            class = CharClass::Not(Box::new(class));
+        }
+
+        Ok(match subtracted {
+            Some(subtracted) => CharClass::Subtract(Box::new(class), Box::new(subtracted)),
+            None => class,
+        })
+    }
+}
+
+//Finds every position a piece can end at, starting from any of 'starts'
+fn match_piece(piece: &Piece, chars: &[char], starts: &[usize]) -> Vec<usize> {
+    let mut ends: Vec<usize> = match *piece {
+        Piece::Class(ref class) => starts.iter()
+            .filter(|&&start| start < chars.len() && class.matches(chars[start]))
+            .map(|&start| start + 1)
+            .collect(),
+        Piece::Group(ref alternatives) => alternatives.iter()
+            .flat_map(|alternative| match_sequence(alternative, chars, starts))
+            .collect(),
+        Piece::Repeat(ref piece, min, max) => match_repeated(min, max, starts, |current| match_piece(piece, chars, current)),
+    };
+
+    ends.sort();
+    ends.dedup();
+    ends
+}
+
+fn match_sequence(pieces: &[Piece], chars: &[char], starts: &[usize]) -> Vec<usize> {
+    let mut positions = starts.to_vec();
+
+    for piece in pieces {
+        if positions.is_empty() {
+            break;
+        }
+        positions = match_piece(piece, chars, &positions);
+    }
+
+    positions
+}
+
+impl Pattern {
+    pub fn parse(expression: &str) -> Result<Pattern, String> {
+        let mut reader = PatternReader {
+            expression,
+            chars: expression.chars().collect(),
+            position: 0,
+        };
+
+        let alternatives = reader.alternatives()?;
+
+        if reader.peek().is_some() {
+            return Err(reader.error("Unexpected ')'"));
+        }
+
+        Ok(Pattern {
+            expression: expression.to_owned(),
+            alternatives,
+        })
+    }
+
+    //Checks whether the whole of a value matches the pattern
+    pub fn matches(&self, value: &str) -> bool {
+        let chars: Vec<char> = value.chars().collect();
+
+        self.alternatives.iter().any(|alternative| match_sequence(alternative, &chars, &[0]).contains(&chars.len()))
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Whether a pattern matches each of the values
+    fn matches_all(expression: &str, values: &[&str]) -> Vec<bool> {
+        let pattern = Pattern::parse(expression).unwrap();
+        values.iter().map(|value| pattern.matches(value)).collect()
+    }
+
+    #[test]
+    fn quantities_repeat_the_piece_before_them() {
+        assert_eq!(matches_all("[A-Z]{3}-\\d+", &["ABC-1", "ABC-123", "AB-1", "ABC-"]), vec![true, true, false, false]);
+        assert_eq!(matches_all("a{2,}b?", &["a", "aa", "aaab", "aabb"]), vec![false, true, true, false]);
+        assert_eq!(matches_all("(ab|c){1,2}", &["", "ab", "cab", "abcab"]), vec![false, true, true, false]);
+    }
+
+    #[test]
+    fn large_quantities_stop_once_nothing_new_matches() {
+        assert!(Pattern::parse("(a?){50000000}").unwrap().matches("a"));
+        assert!(!Pattern::parse("(a?){50000000}").unwrap().matches("b"));
+    }
+
+    #[test]
+    fn categories_and_escapes() {
+        assert_eq!(matches_all("\\p{Lu}\\p{Ll}*", &["Élan", "élan", "E"]), vec![true, false, true]);
+        assert_eq!(matches_all("\\P{N}+", &["ab", "a1"]), vec![true, false]);
+        assert_eq!(matches_all("\\i\\c*", &["_a.b-1", "1a"]), vec![true, false]);
+        assert_eq!(matches_all("\\.\\s\\S", &[". x", ".  "]), vec![true, false]);
+    }
+
+    #[test]
+    fn classes_can_be_subtracted() {
+        assert_eq!(matches_all("[a-z-[aeiou]]+", &["xyz", "abc"]), vec![true, false]);
+        assert_eq!(matches_all("[^0-9-[x]]", &["a", "5", "x"]), vec![true, false, false]);
+    }
+
+    #[test]
+    fn dashes_at_the_ends_of_a_class_are_characters() {
+        assert_eq!(matches_all("[-a]+", &["-a-", "b"]), vec![true, false]);
+        assert_eq!(matches_all("[a-]+", &["a-", "b"]), vec![true, false]);
+        assert_eq!(matches_all("[+\\-]", &["-", "+", "\\"]), vec![true, true, false]);
+    }
+
+    #[test]
+    fn malformed_patterns_are_an_error() {
+        for expression in &["(a", "a)", "*a", "a{2,1}", "a{x}", "[z-a]", "[a-\\d]", "[ab", "\\q", "\\p{Xx}", "[a-z-[b]x]"] {
+            assert!(Pattern::parse(expression).is_err(), "{}", expression);
+        }
+    }
+}
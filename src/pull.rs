@@ -0,0 +1,185 @@
+//
+//  Description  :    Pull parsing of XML files as an iterator of events
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::iter::Peekable;
+use std::io::BufRead;
+use encoding::CharReader;
+use xml_stream::TermReader;
+use XMLTerm;
+
+//A complete part of an XML file, with the attributes of an element gathered into its start
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum XMLEvent {
+    StartElement(String, Vec<(String, String)>),
+    EndElement(String),
+    Text(String),
+    Comment(String),
+    ProcessingInstruction(String, String),
+    //An empty line in the file between two pieces of markup
+    BlankLine,
+}
+
+//Reads the 'XMLEvent's of a stream of 'XMLTerm's, checking that tags are closed in order
+pub struct EventReader<I: Iterator<Item = Result<XMLTerm, String>>> {
+    terms: Peekable<I>,
+    open_elements: Vec<String>,
+    finished: bool,
+}
+
+impl<I: Iterator<Item = Result<XMLTerm, String>>> EventReader<I> {
+    pub fn new(terms: I) -> EventReader<I> {
+        EventReader {
+            terms: terms.peekable(),
+            open_elements: Vec::new(),
+            finished: false,
+        }
+    }
+
+    //The number of elements which have been started but not yet ended
+    pub fn depth(&self) -> usize {
+        self.open_elements.len()
+    }
+
+    //Skips the rest of the most recently started element, up to and including its end
+    pub fn skip_element(&mut self) -> Result<(), String> {
+        let depth = self.depth();
+
+        if depth == 0 {
+            return Ok(());
+        }
+
+        while self.depth() >= depth {
+            match self.next() {
+                Some(Err(e)) => return Err(e),
+                Some(Ok(_)) => {},
+                None => return Err(format!("Missing closing tag for {}", self.open_elements[depth - 1])),
+            }
+        }
+
+        Ok(())
+    }
+
+    //Gathers the attributes which follow an opening tag
+    fn read_attributes(&mut self) -> Vec<(String, String)> {
+        let mut attributes: Vec<(String, String)> = Vec::new();
+
+        while let Some(&Ok(XMLTerm::Attribute(..))) = self.terms.peek() {
+            if let Some(Ok(XMLTerm::Attribute(name, value))) = self.terms.next() {
+                attributes.push((name, value));
+            }
+        }
+
+        attributes
+    }
+
+    fn read_event(&mut self) -> Option<Result<XMLEvent, String>> {
+        loop {
+            let term = match self.terms.next() {
+                Some(Ok(term)) => term,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    return self.open_elements.last().map(|name| Err(format!("Missing closing tag for {}", name)));
+                },
+            };
+
+            return Some(Ok(match term {
+                XMLTerm::OpeningTag(name) => {
+                    let attributes = self.read_attributes();
+                    self.open_elements.push(name.clone());
+
+                    XMLEvent::StartElement(name, attributes)
+                },
+                XMLTerm::ClosingTag(name) => {
+                    //Can only close the most recent opening tag!
+                    match self.open_elements.pop() {
+                        Some(ref expected) if *expected == name => XMLEvent::EndElement(name),
+                        Some(expected) => return Some(Err(format!("Unexpected closing tag. Found: {}, Expected: {}", name, expected))),
+                        None => return Some(Err(format!("Unexpected closing tag. Found: {}", name))),
+                    }
+                },
+                XMLTerm::Text(s) => XMLEvent::Text(s),
+                XMLTerm::Comment(s) => XMLEvent::Comment(s),
+                XMLTerm::ProcessingInstruction(target, data) => XMLEvent::ProcessingInstruction(target, data),
+                XMLTerm::BlankLine => XMLEvent::BlankLine,
+                XMLTerm::Attribute(name, _) => return Some(Err(format!("Unexpected attribute {} outside of a tag", name))),
+                XMLTerm::None => continue,
+            }));
+        }
+    }
+}
+
+impl<R: BufRead> EventReader<TermReader<CharReader<R>>> {
+    //Starts reading events from a file in the encoding detected from its first bytes
+    pub fn from_reader(reader: R) -> Result<EventReader<TermReader<CharReader<R>>>, String> {
+        Ok(EventReader::new(TermReader::new(CharReader::with_detected_encoding(reader)?)))
+    }
+}
+
+impl<I: Iterator<Item = Result<XMLTerm, String>>> Iterator for EventReader<I> {
+    type Item = Result<XMLEvent, String>;
+
+    fn next(&mut self) -> Option<Result<XMLEvent, String>> {
+        if self.finished {
+            return None;
+        }
+
+        //Stop after the end of the file or the first error
+        let event = self.read_event();
+        match event {
+            Some(Ok(_)) => {},
+            _ => self.finished = true,
+        }
+
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer;
+
+    fn events(xml: &str) -> EventReader<::std::vec::IntoIter<Result<XMLTerm, String>>> {
+        let terms: Vec<Result<XMLTerm, String>> = lexer(xml.to_owned()).unwrap().into_iter().map(Ok).collect();
+        EventReader::new(terms.into_iter())
+    }
+
+    #[test]
+    fn gathers_attributes_into_the_start_of_an_element() {
+        let read: Result<Vec<XMLEvent>, String> = events("<a x='1' y=\"2\">t<?pi data?></a>").collect();
+
+        assert_eq!(read.unwrap(), vec![
+            XMLEvent::StartElement("a".to_owned(), vec![("x".to_owned(), "1".to_owned()), ("y".to_owned(), "2".to_owned())]),
+            XMLEvent::Text("t".to_owned()),
+            XMLEvent::ProcessingInstruction("pi".to_owned(), "data".to_owned()),
+            XMLEvent::EndElement("a".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn skips_the_rest_of_an_element() {
+        let mut reader = events("<a><b><c>1</c></b><d/></a>");
+        reader.next();
+        reader.next();
+
+        reader.skip_element().unwrap();
+
+        assert_eq!(reader.next(), Some(Ok(XMLEvent::StartElement("d".to_owned(), Vec::new()))));
+        assert_eq!(reader.depth(), 2);
+    }
+
+    #[test]
+    fn stops_after_a_missing_closing_tag() {
+        let mut reader = events("<a><b>");
+        reader.next();
+        reader.next();
+
+        assert_eq!(reader.next(), Some(Err("Missing closing tag for b".to_owned())));
+        assert_eq!(reader.next(), None);
+    }
+}
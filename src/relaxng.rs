@@ -0,0 +1,1311 @@
+//
+//  Description  :    Validation of parsed XML trees against RELAX NG schemas written in the compact syntax
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use document::{XMLDocument, NodeId, Position};
+use namespaces::{split_name, declared_prefix, XML_NAMESPACE};
+use pattern::Pattern as RegexPattern;
+use validation::{ValidationError, sort_by_position};
+use xsd::{check_built_in, check_facet};
+
+//The datatype library of XML Schema, the only one besides the built in 'string' and 'token'
+pub const XSD_DATATYPES: &str = "http://www.w3.org/2001/XMLSchema-datatypes";
+
+const KEYWORDS: [&str; 19] = ["attribute", "default", "datatypes", "div", "element", "empty", "external", "grammar", "include",
+    "inherit", "list", "mixed", "namespace", "notAllowed", "parent", "start", "string", "text", "token"];
+
+//A set of names an element or attribute may have, such as 'title', 'dc:*' or '* - html:*'
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+enum NameClass {
+    //A namespace URI, empty for none, and a local name
+    Name(String, String),
+    AnyName(Option<Box<NameClass>>),
+    NamespaceName(String, Option<Box<NameClass>>),
+    Choice(Box<NameClass>, Box<NameClass>),
+}
+
+impl NameClass {
+    fn contains(&self, namespace: &str, local_name: &str) -> bool {
+        match *self {
+            NameClass::Name(ref uri, ref name) => uri == namespace && name == local_name,
+            NameClass::AnyName(ref except) => except.as_ref().is_none_or(|except| !except.contains(namespace, local_name)),
+            NameClass::NamespaceName(ref uri, ref except) => uri == namespace && except.as_ref().is_none_or(|except| !except.contains(namespace, local_name)),
+            NameClass::Choice(ref first, ref second) => first.contains(namespace, local_name) || second.contains(namespace, local_name),
+        }
+    }
+
+    //The names of the class, for messages
+    fn describe(&self, names: &mut Vec<String>) {
+        match *self {
+            NameClass::Name(_, ref name) => names.push(format!("'{}'", name)),
+            NameClass::AnyName(_) => names.push("any element".to_owned()),
+            NameClass::NamespaceName(ref uri, _) => names.push(format!("any element in {}", uri)),
+            NameClass::Choice(ref first, ref second) => {
+                first.describe(names);
+                second.describe(names);
+            },
+        }
+    }
+}
+
+//A parameter of a datatype, such as 'maxLength = "10"'
+#[derive(Debug)]
+#[derive(Clone)]
+struct Parameter {
+    name: String,
+    value: String,
+    //The compiled expression of a 'pattern' parameter
+    regex: Option<RegexPattern>,
+}
+
+impl PartialEq for Parameter {
+    fn eq(&self, other: &Parameter) -> bool {
+        self.name == other.name && self.value == other.value
+    }
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+enum Datatype {
+    String,
+    Token,
+    //A type of XML Schema, such as 'integer', with parameters restricting it
+    Xsd(String, Vec<Parameter>),
+}
+
+impl Datatype {
+    fn allows(&self, value: &str) -> bool {
+        match *self {
+            Datatype::String | Datatype::Token => true,
+            Datatype::Xsd(ref name, ref parameters) => {
+                let value = if name == "string" || name == "normalizedString" { value } else { value.trim() };
+
+                check_built_in(name, value).unwrap_or(false) && parameters.iter().all(|parameter| match parameter.regex {
+                    Some(ref regex) => regex.matches(value),
+                    None => check_facet(&parameter.name, &parameter.value, value).is_ok(),
+                })
+            },
+        }
+    }
+
+    //Whether two values of the type are the same, ignoring differences in whitespace for all but strings
+    fn equal(&self, value: &str, other: &str) -> bool {
+        match *self {
+            Datatype::String => value == other,
+            Datatype::Xsd(ref name, _) if name == "string" => value == other,
+            _ => value.split_whitespace().eq(other.split_whitespace()),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match *self {
+            Datatype::String => "string".to_owned(),
+            Datatype::Token => "token".to_owned(),
+            Datatype::Xsd(ref name, _) => format!("xsd:{}", name),
+        }
+    }
+}
+
+//A RELAX NG pattern. While a document is checked, each pattern is replaced by what it still allows after the part read so far.
+#[derive(Debug)]
+#[derive(PartialEq)]
+enum Pattern {
+    Empty,
+    NotAllowed,
+    Text,
+    Choice(Rc<Pattern>, Rc<Pattern>),
+    Interleave(Rc<Pattern>, Rc<Pattern>),
+    Group(Rc<Pattern>, Rc<Pattern>),
+    OneOrMore(Rc<Pattern>),
+    List(Rc<Pattern>),
+    Data(Datatype, Option<Rc<Pattern>>),
+    Value(Datatype, String),
+    Attribute(NameClass, Rc<Pattern>),
+    Element(NameClass, Rc<Pattern>),
+    //A named definition of the grammar, by its index
+    Ref(usize),
+    //The content of an element being read, then what follows the element
+    After(Rc<Pattern>, Rc<Pattern>),
+}
+
+//How to rebuild a pattern around what follows an element, when an element starts inside it
+#[derive(Clone)]
+enum Continuation {
+    InterleaveBefore(Rc<Pattern>),
+    InterleaveAfter(Rc<Pattern>),
+    GroupBefore(Rc<Pattern>),
+    After(Rc<Pattern>),
+}
+
+fn empty() -> Rc<Pattern> {
+    Rc::new(Pattern::Empty)
+}
+
+fn not_allowed() -> Rc<Pattern> {
+    Rc::new(Pattern::NotAllowed)
+}
+
+fn choice(first: Rc<Pattern>, second: Rc<Pattern>) -> Rc<Pattern> {
+    match (&*first, &*second) {
+        (&Pattern::NotAllowed, _) => second.clone(),
+        (_, &Pattern::NotAllowed) => first.clone(),
+        _ if first == second => first.clone(),
+        _ => Rc::new(Pattern::Choice(first.clone(), second.clone())),
+    }
+}
+
+fn group(first: Rc<Pattern>, second: Rc<Pattern>) -> Rc<Pattern> {
+    match (&*first, &*second) {
+        (&Pattern::NotAllowed, _) | (_, &Pattern::NotAllowed) => not_allowed(),
+        (&Pattern::Empty, _) => second.clone(),
+        (_, &Pattern::Empty) => first.clone(),
+        _ => Rc::new(Pattern::Group(first.clone(), second.clone())),
+    }
+}
+
+fn interleave(first: Rc<Pattern>, second: Rc<Pattern>) -> Rc<Pattern> {
+    match (&*first, &*second) {
+        (&Pattern::NotAllowed, _) | (_, &Pattern::NotAllowed) => not_allowed(),
+        (&Pattern::Empty, _) => second.clone(),
+        (_, &Pattern::Empty) => first.clone(),
+        _ => Rc::new(Pattern::Interleave(first.clone(), second.clone())),
+    }
+}
+
+fn after(first: Rc<Pattern>, second: Rc<Pattern>) -> Rc<Pattern> {
+    match (&*first, &*second) {
+        (&Pattern::NotAllowed, _) | (_, &Pattern::NotAllowed) => not_allowed(),
+        _ => Rc::new(Pattern::After(first.clone(), second.clone())),
+    }
+}
+
+fn one_or_more(pattern: Rc<Pattern>) -> Rc<Pattern> {
+    match *pattern {
+        Pattern::NotAllowed | Pattern::Empty => pattern.clone(),
+        _ => Rc::new(Pattern::OneOrMore(pattern.clone())),
+    }
+}
+
+fn is_not_allowed(pattern: &Rc<Pattern>) -> bool {
+    **pattern == Pattern::NotAllowed
+}
+
+fn is_keyword(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+enum Token {
+    //A name, which may be a keyword
+    Identifier(String),
+    //A name written with a '\' before it, which is never a keyword
+    Escaped(String),
+    PrefixedName(String, String),
+    //'prefix:*'
+    NamespaceName(String),
+    Literal(String),
+    Symbol(&'static str),
+    End,
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+//Splits a compact schema into tokens with where each starts, leaving out comments and annotations
+fn tokenize(text: &str) -> Result<Vec<(Token, Position)>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens: Vec<(Token, Position)> = Vec::new();
+    let mut index = 0;
+    let mut position = Position { line: 1, column: 1 };
+
+    //Moves past 'count' characters, following lines and columns
+    fn advance(chars: &[char], index: &mut usize, position: &mut Position, count: usize) {
+        for _ in 0..count {
+            if chars.get(*index) == Some(&'\n') {
+                position.line += 1;
+                position.column = 1;
+            }
+            else {
+                position.column += 1;
+            }
+            *index += 1;
+        }
+    }
+
+    fn read_name(chars: &[char], index: usize) -> usize {
+        let mut end = index;
+        while end < chars.len() && is_name_char(chars[end]) {
+            end += 1;
+        }
+        end
+    }
+
+    while index < chars.len() {
+        let c = chars[index];
+        let start = position;
+
+        if c.is_whitespace() {
+            advance(&chars, &mut index, &mut position, 1);
+        }
+        else if c == '#' {
+            while index < chars.len() && chars[index] != '\n' {
+                advance(&chars, &mut index, &mut position, 1);
+            }
+        }
+        else if c == '[' {
+            //Annotations are skipped, along with any brackets and literals inside them
+            let mut depth = 0;
+            let mut quote: Option<char> = None;
+
+            loop {
+                match chars.get(index) {
+                    None => return Err(format!("{}: Unterminated annotation", start)),
+                    Some(&c) if quote == Some(c) => quote = None,
+                    Some(_) if quote.is_some() => {},
+                    Some(&c) if c == '"' || c == '\'' => quote = Some(c),
+                    Some(&'[') => depth += 1,
+                    Some(&']') => depth -= 1,
+                    Some(_) => {},
+                }
+                advance(&chars, &mut index, &mut position, 1);
+
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+        else if c == '>' && chars.get(index + 1) == Some(&'>') {
+            //A following annotation, '>> name [ ... ]', whose name is skipped here and brackets on the next pass
+            advance(&chars, &mut index, &mut position, 2);
+            while chars.get(index).is_some_and(|c| c.is_whitespace()) {
+                advance(&chars, &mut index, &mut position, 1);
+            }
+            while chars.get(index).is_some_and(|&c| is_name_char(c) || c == ':') {
+                advance(&chars, &mut index, &mut position, 1);
+            }
+        }
+        else if c == '"' || c == '\'' {
+            let triple = chars.get(index + 1) == Some(&c) && chars.get(index + 2) == Some(&c);
+            let delimiter = if triple { 3 } else { 1 };
+            advance(&chars, &mut index, &mut position, delimiter);
+
+            let mut value = String::new();
+            loop {
+                match chars.get(index) {
+                    None => return Err(format!("{}: Unterminated literal", start)),
+                    Some(&'\n') if !triple => return Err(format!("{}: Unterminated literal", start)),
+                    Some(&q) if q == c && (!triple || (chars.get(index + 1) == Some(&c) && chars.get(index + 2) == Some(&c))) => {
+                        advance(&chars, &mut index, &mut position, delimiter);
+                        break;
+                    },
+                    Some(&q) => {
+                        value.push(q);
+                        advance(&chars, &mut index, &mut position, 1);
+                    },
+                }
+            }
+
+            tokens.push((Token::Literal(value), start));
+        }
+        else if c == '\\' || is_name_start(c) {
+            let escaped = c == '\\';
+            if escaped {
+                advance(&chars, &mut index, &mut position, 1);
+            }
+
+            let end = read_name(&chars, index);
+            if end == index {
+                return Err(format!("{}: Expected a name after '\\'", start));
+            }
+            let name: String = chars[index..end].iter().collect();
+            let length = end - index;
+            advance(&chars, &mut index, &mut position, length);
+
+            if chars.get(index) == Some(&':') && chars.get(index + 1) == Some(&'*') {
+                advance(&chars, &mut index, &mut position, 2);
+                tokens.push((Token::NamespaceName(name), start));
+            }
+            else if chars.get(index) == Some(&':') && chars.get(index + 1).is_some_and(|&c| is_name_start(c)) {
+                advance(&chars, &mut index, &mut position, 1);
+                let end = read_name(&chars, index);
+                let local_name: String = chars[index..end].iter().collect();
+                let length = end - index;
+                advance(&chars, &mut index, &mut position, length);
+                tokens.push((Token::PrefixedName(name, local_name), start));
+            }
+            else if escaped {
+                tokens.push((Token::Escaped(name), start));
+            }
+            else {
+                tokens.push((Token::Identifier(name), start));
+            }
+        }
+        else {
+            let symbol = ["|=", "&=", "{", "}", "(", ")", "=", ",", "|", "&", "?", "*", "+", "-", "~"].iter()
+                .find(|symbol| symbol.chars().enumerate().all(|(offset, s)| chars.get(index + offset) == Some(&s)));
+
+            match symbol {
+                Some(symbol) => {
+                    advance(&chars, &mut index, &mut position, symbol.len());
+                    tokens.push((Token::Symbol(symbol), start));
+                },
+                None => return Err(format!("{}: Unexpected '{}'", start, c)),
+            }
+        }
+    }
+
+    tokens.push((Token::End, position));
+    Ok(tokens)
+}
+
+//Reads a compact schema into patterns
+struct SchemaParser {
+    tokens: Vec<(Token, Position)>,
+    index: usize,
+    default_namespace: String,
+    namespaces: HashMap<String, String>,
+    datatypes: HashMap<String, String>,
+    definitions: HashMap<String, usize>,
+    patterns: Vec<Option<Rc<Pattern>>>,
+    names: Vec<String>,
+}
+
+impl SchemaParser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index].0
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        let index = (self.index + offset).min(self.tokens.len() - 1);
+        &self.tokens[index].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.index].0.clone();
+        if self.index < self.tokens.len() - 1 {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{}: {}", self.tokens[self.index].1, message)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        match *self.peek() {
+            Token::Symbol(known) => known == symbol,
+            _ => false,
+        }
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        if self.is_symbol(symbol) {
+            self.next();
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        }
+        else {
+            Err(self.error(&format!("Expected '{}'", symbol)))
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        *self.peek() == Token::Identifier(keyword.to_owned())
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.next();
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    //A name which may also be a keyword, such as a namespace prefix
+    fn identifier_or_keyword(&mut self) -> Result<String, String> {
+        match self.next() {
+            Token::Identifier(name) | Token::Escaped(name) => Ok(name),
+            _ => {
+                self.index -= 1;
+                Err(self.error("Expected a name"))
+            },
+        }
+    }
+
+    //A literal, which may be several joined by '~'
+    fn literal(&mut self) -> Result<String, String> {
+        let mut value = match self.next() {
+            Token::Literal(value) => value,
+            _ => {
+                self.index -= 1;
+                return Err(self.error("Expected a quoted value"));
+            },
+        };
+
+        while self.eat_symbol("~") {
+            match self.next() {
+                Token::Literal(more) => value.push_str(&more),
+                _ => {
+                    self.index -= 1;
+                    return Err(self.error("Expected a quoted value after '~'"));
+                },
+            }
+        }
+
+        Ok(value)
+    }
+
+    fn namespace_uri(&self, prefix: &str) -> Result<String, String> {
+        match self.namespaces.get(prefix) {
+            Some(uri) => Ok(uri.clone()),
+            None => Err(self.error(&format!("Namespace prefix {} is not declared", prefix))),
+        }
+    }
+
+    //The index of a named definition, reserving one if it has not been defined yet
+    fn definition_index(&mut self, name: &str) -> usize {
+        if let Some(&index) = self.definitions.get(name) {
+            return index;
+        }
+
+        self.patterns.push(None);
+        self.names.push(name.to_owned());
+        self.definitions.insert(name.to_owned(), self.patterns.len() - 1);
+        self.patterns.len() - 1
+    }
+
+    //Reads 'namespace', 'default namespace' and 'datatypes' declarations
+    fn declarations(&mut self) -> Result<(), String> {
+        loop {
+            let is_default = self.is_keyword("default") && *self.peek_at(1) == Token::Identifier("namespace".to_owned());
+
+            if is_default || (self.is_keyword("namespace") && *self.peek_at(2) == Token::Symbol("=")) {
+                if is_default {
+                    self.next();
+                }
+                self.next();
+
+                let prefix = if self.is_symbol("=") { None } else { Some(self.identifier_or_keyword()?) };
+                self.expect_symbol("=")?;
+
+                let uri = if self.eat_keyword("inherit") { String::new() } else { self.literal()? };
+
+                if let Some(prefix) = prefix {
+                    self.namespaces.insert(prefix, uri.clone());
+                }
+                if is_default {
+                    self.default_namespace = uri;
+                }
+            }
+            else if self.is_keyword("datatypes") {
+                self.next();
+                let prefix = self.identifier_or_keyword()?;
+                self.expect_symbol("=")?;
+                let uri = self.literal()?;
+                self.datatypes.insert(prefix, uri);
+            }
+            else {
+                return Ok(());
+            }
+        }
+    }
+
+    //Whether the schema is a list of definitions rather than a single pattern
+    fn is_grammar(&self) -> bool {
+        match *self.peek() {
+            Token::Identifier(ref name) if name == "start" || name == "div" || name == "include" => true,
+            Token::Identifier(ref name) if is_keyword(name) => false,
+            Token::Identifier(_) | Token::Escaped(_) => matches!(*self.peek_at(1), Token::Symbol("=") | Token::Symbol("|=") | Token::Symbol("&=")),
+            _ => false,
+        }
+    }
+
+    //Reads definitions up to the end of the schema or a closing '}', returning the start pattern if one was given
+    fn grammar_content(&mut self, start: &mut Option<Rc<Pattern>>) -> Result<(), String> {
+        loop {
+            match self.peek().clone() {
+                Token::End | Token::Symbol("}") => return Ok(()),
+                Token::Identifier(ref name) if name == "div" => {
+                    self.next();
+                    self.expect_symbol("{")?;
+                    self.grammar_content(start)?;
+                    self.expect_symbol("}")?;
+                },
+                Token::Identifier(ref name) if name == "include" => return Err(self.error("Including other schemas is not supported")),
+                Token::Identifier(ref name) if name == "start" => {
+                    self.next();
+                    let combine = self.assignment()?;
+                    let pattern = self.pattern()?;
+                    *start = Some(combine_definitions(start.take(), pattern, combine));
+                },
+                Token::Identifier(ref name) | Token::Escaped(ref name) if !is_keyword(name) || self.peek() == &Token::Escaped(name.clone()) => {
+                    self.next();
+                    let combine = self.assignment()?;
+                    let pattern = self.pattern()?;
+                    let index = self.definition_index(name);
+
+                    if combine.is_none() && self.patterns[index].is_some() {
+                        return Err(self.error(&format!("{} is defined twice", name)));
+                    }
+
+                    let existing = self.patterns[index].take();
+                    self.patterns[index] = Some(combine_definitions(existing, pattern, combine));
+                },
+                _ => return Err(self.error("Expected a definition")),
+            }
+        }
+    }
+
+    //Reads '=', '|=' or '&=', returning the symbol which combines definitions of the same name
+    fn assignment(&mut self) -> Result<Option<&'static str>, String> {
+        if self.eat_symbol("=") {
+            Ok(None)
+        }
+        else if self.eat_symbol("|=") {
+            Ok(Some("|"))
+        }
+        else if self.eat_symbol("&=") {
+            Ok(Some("&"))
+        }
+        else {
+            Err(self.error("Expected '=', '|=' or '&='"))
+        }
+    }
+
+    //Reads particles joined by one of ',', '|' or '&'
+    fn pattern(&mut self) -> Result<Rc<Pattern>, String> {
+        let mut pattern = self.particle()?;
+        let mut operator: Option<&'static str> = None;
+
+        loop {
+            let next = [",", "|", "&"].iter().cloned().find(|symbol| self.is_symbol(symbol));
+
+            match next {
+                Some(symbol) => {
+                    if operator.is_some_and(|operator| operator != symbol) {
+                        return Err(self.error("Use brackets to mix ',', '|' and '&'"));
+                    }
+                    operator = Some(symbol);
+                    self.next();
+
+                    let particle = self.particle()?;
+                    pattern = match symbol {
+                        "," => group(pattern, particle),
+                        "|" => choice(pattern, particle),
+                        _ => interleave(pattern, particle),
+                    };
+                },
+                None => return Ok(pattern),
+            }
+        }
+    }
+
+    fn particle(&mut self) -> Result<Rc<Pattern>, String> {
+        let primary = self.primary()?;
+
+        Ok(if self.eat_symbol("?") {
+            choice(primary, empty())
+        }
+        else if self.eat_symbol("*") {
+            choice(one_or_more(primary), empty())
+        }
+        else if self.eat_symbol("+") {
+            one_or_more(primary)
+        }
+        else {
+            primary
+        })
+    }
+
+    fn primary(&mut self) -> Result<Rc<Pattern>, String> {
+        let token = self.peek().clone();
+
+        match token {
+            Token::Identifier(ref keyword) if keyword == "element" || keyword == "attribute" => {
+                self.next();
+                let is_element = keyword == "element";
+                let name_class = self.name_class(is_element)?;
+
+                self.expect_symbol("{")?;
+                let content = self.pattern()?;
+                self.expect_symbol("}")?;
+
+                Ok(Rc::new(if is_element { Pattern::Element(name_class, content) } else { Pattern::Attribute(name_class, content) }))
+            },
+            Token::Identifier(ref keyword) if keyword == "mixed" || keyword == "list" => {
+                self.next();
+                self.expect_symbol("{")?;
+                let content = self.pattern()?;
+                self.expect_symbol("}")?;
+
+                Ok(if keyword == "mixed" { interleave(content, Rc::new(Pattern::Text)) } else { Rc::new(Pattern::List(content)) })
+            },
+            Token::Identifier(ref keyword) if keyword == "empty" => { self.next(); Ok(empty()) },
+            Token::Identifier(ref keyword) if keyword == "notAllowed" => { self.next(); Ok(not_allowed()) },
+            Token::Identifier(ref keyword) if keyword == "text" => { self.next(); Ok(Rc::new(Pattern::Text)) },
+            Token::Identifier(ref keyword) if keyword == "string" || keyword == "token" => {
+                self.next();
+                let datatype = if keyword == "string" { Datatype::String } else { Datatype::Token };
+                self.data(datatype)
+            },
+            Token::Identifier(ref keyword) if keyword == "grammar" || keyword == "external" || keyword == "parent" => {
+                Err(self.error(&format!("'{}' is not supported", keyword)))
+            },
+            Token::Identifier(ref name) if !is_keyword(name) => {
+                self.next();
+                Ok(Rc::new(Pattern::Ref(self.definition_index(name))))
+            },
+            Token::Escaped(ref name) => {
+                self.next();
+                Ok(Rc::new(Pattern::Ref(self.definition_index(name))))
+            },
+            Token::PrefixedName(ref prefix, ref name) => {
+                self.next();
+
+                let library = match self.datatypes.get(prefix) {
+                    Some(library) => library.clone(),
+                    None => return Err(self.error(&format!("Datatype prefix {} is not declared", prefix))),
+                };
+                if library != XSD_DATATYPES {
+                    return Err(self.error(&format!("Unsupported datatype library {}", library)));
+                }
+                if let Err(e) = check_built_in(name, "") {
+                    return Err(self.error(&e));
+                }
+
+                self.data(Datatype::Xsd(name.clone(), Vec::new()))
+            },
+            Token::Literal(_) => {
+                let value = self.literal()?;
+                Ok(Rc::new(Pattern::Value(Datatype::Token, value)))
+            },
+            Token::Symbol("(") => {
+                self.next();
+                let pattern = self.pattern()?;
+                self.expect_symbol(")")?;
+                Ok(pattern)
+            },
+            _ => Err(self.error("Expected a pattern")),
+        }
+    }
+
+    //Reads what may follow a datatype name: a value, parameters, or an exception after '-'
+    fn data(&mut self, datatype: Datatype) -> Result<Rc<Pattern>, String> {
+        if let Token::Literal(_) = *self.peek() {
+            let value = self.literal()?;
+            return Ok(Rc::new(Pattern::Value(datatype, value)));
+        }
+
+        let mut datatype = datatype;
+
+        if self.eat_symbol("{") {
+            let mut parameters: Vec<Parameter> = Vec::new();
+
+            while !self.eat_symbol("}") {
+                let name = self.identifier_or_keyword()?;
+                self.expect_symbol("=")?;
+                let value = self.literal()?;
+
+                let regex = if name == "pattern" {
+                    match RegexPattern::parse(&value) {
+                        Ok(regex) => Some(regex),
+                        Err(e) => return Err(self.error(&e)),
+                    }
+                }
+                else {
+                    if let Err(e) = check_facet(&name, &value, "") {
+                        if e.starts_with("Unsupported") || e.starts_with("Invalid") {
+                            return Err(self.error(&e));
+                        }
+                    }
+                    None
+                };
+
+                parameters.push(Parameter {
+                    name,
+                    value,
+                    regex,
+                });
+            }
+
+            datatype = match datatype {
+                Datatype::Xsd(name, _) => Datatype::Xsd(name, parameters),
+                _ => return Err(self.error("Only XML Schema datatypes take parameters")),
+            };
+        }
+
+        let except = if self.eat_symbol("-") { Some(self.primary()?) } else { None };
+
+        Ok(Rc::new(Pattern::Data(datatype, except)))
+    }
+
+    fn name_class(&mut self, is_element: bool) -> Result<NameClass, String> {
+        let mut name_class = self.name_class_primary(is_element)?;
+
+        while self.eat_symbol("|") {
+            let other = self.name_class_primary(is_element)?;
+            name_class = NameClass::Choice(Box::new(name_class), Box::new(other));
+        }
+
+        Ok(name_class)
+    }
+
+    fn name_class_primary(&mut self, is_element: bool) -> Result<NameClass, String> {
+        match self.next() {
+            Token::Identifier(name) | Token::Escaped(name) => {
+                //Only elements take the default namespace
+                let namespace = if is_element { self.default_namespace.clone() } else { String::new() };
+                Ok(NameClass::Name(namespace, name))
+            },
+            Token::PrefixedName(prefix, name) => Ok(NameClass::Name(self.namespace_uri(&prefix)?, name)),
+            Token::NamespaceName(prefix) => {
+                let uri = self.namespace_uri(&prefix)?;
+                let except = if self.eat_symbol("-") { Some(Box::new(self.name_class_primary(is_element)?)) } else { None };
+                Ok(NameClass::NamespaceName(uri, except))
+            },
+            Token::Symbol("*") => {
+                let except = if self.eat_symbol("-") { Some(Box::new(self.name_class_primary(is_element)?)) } else { None };
+                Ok(NameClass::AnyName(except))
+            },
+            Token::Symbol("(") => {
+                let name_class = self.name_class(is_element)?;
+                self.expect_symbol(")")?;
+                Ok(name_class)
+            },
+            _ => {
+                self.index -= 1;
+                Err(self.error("Expected a name"))
+            },
+        }
+    }
+}
+
+//Joins a definition to an earlier one of the same name with '|=' or '&='
+fn combine_definitions(existing: Option<Rc<Pattern>>, pattern: Rc<Pattern>, combine: Option<&'static str>) -> Rc<Pattern> {
+    match (existing, combine) {
+        (Some(existing), Some("&")) => interleave(existing, pattern),
+        (Some(existing), Some(_)) => choice(existing, pattern),
+        _ => pattern,
+    }
+}
+
+//A compiled RELAX NG schema, ready to check documents against
+#[derive(Debug)]
+pub struct RelaxNG {
+    start: Rc<Pattern>,
+    definitions: Vec<Rc<Pattern>>,
+}
+
+impl RelaxNG {
+    //Reads a schema written in the compact syntax
+    pub fn parse(text: &str) -> Result<RelaxNG, String> {
+        let mut namespaces: HashMap<String, String> = HashMap::new();
+        namespaces.insert("xml".to_owned(), XML_NAMESPACE.to_owned());
+
+        let mut datatypes: HashMap<String, String> = HashMap::new();
+        datatypes.insert("xsd".to_owned(), XSD_DATATYPES.to_owned());
+
+        let mut parser = SchemaParser {
+            tokens: tokenize(text)?,
+            index: 0,
+            default_namespace: String::new(),
+            namespaces,
+            datatypes,
+            definitions: HashMap::new(),
+            patterns: Vec::new(),
+            names: Vec::new(),
+        };
+
+        parser.declarations()?;
+
+        let start = if parser.is_grammar() {
+            let mut start: Option<Rc<Pattern>> = None;
+            parser.grammar_content(&mut start)?;
+
+            match start {
+                Some(start) => start,
+                None => return Err("The grammar has no 'start' pattern".to_owned()),
+            }
+        }
+        else {
+            parser.pattern()?
+        };
+
+        if *parser.peek() != Token::End {
+            return Err(parser.error("Unexpected text after the schema"));
+        }
+
+        let mut definitions: Vec<Rc<Pattern>> = Vec::new();
+        for (index, pattern) in parser.patterns.into_iter().enumerate() {
+            match pattern {
+                Some(pattern) => definitions.push(pattern),
+                None => return Err(format!("{} is used but not defined", parser.names[index])),
+            }
+        }
+
+        let schema = RelaxNG {
+            start,
+            definitions,
+        };
+
+        //A definition may only refer back to itself from inside an element
+        for index in 0..schema.definitions.len() {
+            if schema.refers_without_element(&schema.definitions[index], index, 0) {
+                return Err(format!("{} refers to itself without an element between", parser.names[index]));
+            }
+        }
+
+        Ok(schema)
+    }
+
+    fn refers_without_element(&self, pattern: &Pattern, target: usize, depth: usize) -> bool {
+        if depth > self.definitions.len() {
+            return false;
+        }
+
+        match *pattern {
+            Pattern::Ref(index) => index == target || self.refers_without_element(&self.definitions[index], target, depth + 1),
+            Pattern::Choice(ref first, ref second) | Pattern::Interleave(ref first, ref second) | Pattern::Group(ref first, ref second) | Pattern::After(ref first, ref second) => {
+                self.refers_without_element(first, target, depth) || self.refers_without_element(second, target, depth)
+            },
+            Pattern::OneOrMore(ref inner) | Pattern::List(ref inner) | Pattern::Attribute(_, ref inner) => self.refers_without_element(inner, target, depth),
+            Pattern::Data(_, Some(ref except)) => self.refers_without_element(except, target, depth),
+            _ => false,
+        }
+    }
+
+    //The pattern a reference stands for, or the pattern itself
+    fn resolve<'a>(&'a self, pattern: &'a Rc<Pattern>) -> &'a Rc<Pattern> {
+        match **pattern {
+            Pattern::Ref(index) => self.resolve(&self.definitions[index]),
+            _ => pattern,
+        }
+    }
+
+    fn nullable(&self, pattern: &Rc<Pattern>) -> bool {
+        match **self.resolve(pattern) {
+            Pattern::Empty | Pattern::Text => true,
+            Pattern::Group(ref first, ref second) | Pattern::Interleave(ref first, ref second) => self.nullable(first) && self.nullable(second),
+            Pattern::Choice(ref first, ref second) => self.nullable(first) || self.nullable(second),
+            Pattern::OneOrMore(ref inner) => self.nullable(inner),
+            _ => false,
+        }
+    }
+
+    //What a pattern allows after some text
+    fn text_deriv(&self, pattern: &Rc<Pattern>, text: &str) -> Rc<Pattern> {
+        match **self.resolve(pattern) {
+            Pattern::Choice(ref first, ref second) => choice(self.text_deriv(first, text), self.text_deriv(second, text)),
+            Pattern::Interleave(ref first, ref second) => choice(
+                interleave(self.text_deriv(first, text), second.clone()),
+                interleave(first.clone(), self.text_deriv(second, text))),
+            Pattern::Group(ref first, ref second) => {
+                let derived = group(self.text_deriv(first, text), second.clone());
+                if self.nullable(first) { choice(derived, self.text_deriv(second, text)) } else { derived }
+            },
+            Pattern::After(ref first, ref second) => after(self.text_deriv(first, text), second.clone()),
+            Pattern::OneOrMore(ref inner) => group(self.text_deriv(inner, text), choice(pattern.clone(), empty())),
+            Pattern::Text => Rc::new(Pattern::Text),
+            Pattern::Value(ref datatype, ref value) => if datatype.equal(value, text) { empty() } else { not_allowed() },
+            Pattern::Data(ref datatype, ref except) => {
+                let excluded = except.as_ref().is_some_and(|except| self.nullable(&self.text_deriv(except, text)));
+                if datatype.allows(text) && !excluded { empty() } else { not_allowed() }
+            },
+            Pattern::List(ref inner) => {
+                let mut derived = inner.clone();
+                for token in text.split_whitespace() {
+                    derived = self.text_deriv(&derived, token);
+                }
+                if self.nullable(&derived) { empty() } else { not_allowed() }
+            },
+            _ => not_allowed(),
+        }
+    }
+
+    fn apply_after(&self, pattern: &Rc<Pattern>, continuation: &Continuation) -> Rc<Pattern> {
+        match **pattern {
+            Pattern::After(ref first, ref second) => after(first.clone(), match *continuation {
+                Continuation::InterleaveBefore(ref other) => interleave(second.clone(), other.clone()),
+                Continuation::InterleaveAfter(ref other) => interleave(other.clone(), second.clone()),
+                Continuation::GroupBefore(ref other) => group(second.clone(), other.clone()),
+                Continuation::After(ref other) => after(second.clone(), other.clone()),
+            }),
+            Pattern::Choice(ref first, ref second) => choice(self.apply_after(first, continuation), self.apply_after(second, continuation)),
+            _ => not_allowed(),
+        }
+    }
+
+    //What a pattern allows once an element with a name starts, as the element's content followed by what comes after it
+    fn start_tag_open_deriv(&self, pattern: &Rc<Pattern>, namespace: &str, local_name: &str) -> Rc<Pattern> {
+        match **self.resolve(pattern) {
+            Pattern::Choice(ref first, ref second) => choice(
+                self.start_tag_open_deriv(first, namespace, local_name),
+                self.start_tag_open_deriv(second, namespace, local_name)),
+            Pattern::Element(ref name_class, ref content) => {
+                if name_class.contains(namespace, local_name) { after(content.clone(), empty()) } else { not_allowed() }
+            },
+            Pattern::Interleave(ref first, ref second) => choice(
+                self.apply_after(&self.start_tag_open_deriv(first, namespace, local_name), &Continuation::InterleaveBefore(second.clone())),
+                self.apply_after(&self.start_tag_open_deriv(second, namespace, local_name), &Continuation::InterleaveAfter(first.clone()))),
+            Pattern::OneOrMore(ref inner) => self.apply_after(
+                &self.start_tag_open_deriv(inner, namespace, local_name),
+                &Continuation::GroupBefore(choice(pattern.clone(), empty()))),
+            Pattern::Group(ref first, ref second) => {
+                let derived = self.apply_after(&self.start_tag_open_deriv(first, namespace, local_name), &Continuation::GroupBefore(second.clone()));
+                if self.nullable(first) { choice(derived, self.start_tag_open_deriv(second, namespace, local_name)) } else { derived }
+            },
+            Pattern::After(ref first, ref second) => self.apply_after(
+                &self.start_tag_open_deriv(first, namespace, local_name),
+                &Continuation::After(second.clone())),
+            _ => not_allowed(),
+        }
+    }
+
+    //Whether some text is allowed by a pattern, where whitespace is allowed by anything which may be empty
+    fn value_matches(&self, pattern: &Rc<Pattern>, text: &str) -> bool {
+        (self.nullable(pattern) && text.trim().is_empty()) || self.nullable(&self.text_deriv(pattern, text))
+    }
+
+    //What a pattern allows after an attribute, where a value of None matches any value to carry on after a wrong one
+    fn attribute_deriv(&self, pattern: &Rc<Pattern>, namespace: &str, local_name: &str, value: Option<&str>) -> Rc<Pattern> {
+        match **self.resolve(pattern) {
+            Pattern::After(ref first, ref second) => after(self.attribute_deriv(first, namespace, local_name, value), second.clone()),
+            Pattern::Choice(ref first, ref second) => choice(
+                self.attribute_deriv(first, namespace, local_name, value),
+                self.attribute_deriv(second, namespace, local_name, value)),
+            Pattern::Group(ref first, ref second) => choice(
+                group(self.attribute_deriv(first, namespace, local_name, value), second.clone()),
+                group(first.clone(), self.attribute_deriv(second, namespace, local_name, value))),
+            Pattern::Interleave(ref first, ref second) => choice(
+                interleave(self.attribute_deriv(first, namespace, local_name, value), second.clone()),
+                interleave(first.clone(), self.attribute_deriv(second, namespace, local_name, value))),
+            Pattern::OneOrMore(ref inner) => group(self.attribute_deriv(inner, namespace, local_name, value), choice(pattern.clone(), empty())),
+            Pattern::Attribute(ref name_class, ref content) => {
+                if name_class.contains(namespace, local_name) && value.is_none_or(|value| self.value_matches(content, value)) { empty() } else { not_allowed() }
+            },
+            _ => not_allowed(),
+        }
+    }
+
+    //What a pattern allows once the attributes of an element have been read, where 'lenient' forgets missing attributes instead of failing
+    fn start_tag_close_deriv(&self, pattern: &Rc<Pattern>, lenient: bool) -> Rc<Pattern> {
+        match **self.resolve(pattern) {
+            Pattern::After(ref first, ref second) => after(self.start_tag_close_deriv(first, lenient), second.clone()),
+            Pattern::Choice(ref first, ref second) => choice(self.start_tag_close_deriv(first, lenient), self.start_tag_close_deriv(second, lenient)),
+            Pattern::Group(ref first, ref second) => group(self.start_tag_close_deriv(first, lenient), self.start_tag_close_deriv(second, lenient)),
+            Pattern::Interleave(ref first, ref second) => interleave(self.start_tag_close_deriv(first, lenient), self.start_tag_close_deriv(second, lenient)),
+            Pattern::OneOrMore(ref inner) => one_or_more(self.start_tag_close_deriv(inner, lenient)),
+            Pattern::Attribute(_, _) => if lenient { empty() } else { not_allowed() },
+            _ => pattern.clone(),
+        }
+    }
+
+    fn end_tag_deriv(&self, pattern: &Rc<Pattern>) -> Rc<Pattern> {
+        match **pattern {
+            Pattern::Choice(ref first, ref second) => choice(self.end_tag_deriv(first), self.end_tag_deriv(second)),
+            Pattern::After(ref first, ref second) => if self.nullable(first) { second.clone() } else { not_allowed() },
+            _ => not_allowed(),
+        }
+    }
+
+    //What follows the current element whatever its content, to carry on after a problem
+    fn after_element(&self, pattern: &Rc<Pattern>) -> Rc<Pattern> {
+        match **pattern {
+            Pattern::Choice(ref first, ref second) => choice(self.after_element(first), self.after_element(second)),
+            Pattern::After(_, ref second) => second.clone(),
+            _ => not_allowed(),
+        }
+    }
+
+    //The elements, text and values a pattern could accept next, for messages
+    fn expected(&self, pattern: &Rc<Pattern>, names: &mut Vec<String>, depth: usize) {
+        if depth > 64 {
+            return;
+        }
+
+        match **self.resolve(pattern) {
+            Pattern::Element(ref name_class, _) => name_class.describe(names),
+            Pattern::Text => names.push("text".to_owned()),
+            Pattern::Value(_, ref value) => names.push(format!("'{}'", value)),
+            Pattern::Data(ref datatype, _) => names.push(datatype.describe()),
+            Pattern::List(ref inner) | Pattern::OneOrMore(ref inner) => self.expected(inner, names, depth + 1),
+            Pattern::Choice(ref first, ref second) | Pattern::Interleave(ref first, ref second) => {
+                self.expected(first, names, depth + 1);
+                self.expected(second, names, depth + 1);
+            },
+            Pattern::Group(ref first, ref second) => {
+                self.expected(first, names, depth + 1);
+                if self.nullable(first) {
+                    self.expected(second, names, depth + 1);
+                }
+            },
+            Pattern::After(ref first, _) => self.expected(first, names, depth + 1),
+            _ => {},
+        }
+    }
+
+    fn expected_message(&self, pattern: &Rc<Pattern>) -> String {
+        let mut names: Vec<String> = Vec::new();
+        self.expected(pattern, &mut names, 0);
+
+        names.sort();
+        names.dedup();
+
+        if !names.is_empty() { format!("expected {}", names.join(" or ")) } else { "expected nothing more".to_owned() }
+    }
+
+    //The attributes a pattern must still be given before its element's start tag ends
+    fn required_attributes(&self, pattern: &Rc<Pattern>, depth: usize) -> Vec<String> {
+        if depth > 64 {
+            return Vec::new();
+        }
+
+        match **self.resolve(pattern) {
+            Pattern::Attribute(ref name_class, _) => {
+                let mut names: Vec<String> = Vec::new();
+                name_class.describe(&mut names);
+                names
+            },
+            Pattern::After(ref first, _) | Pattern::OneOrMore(ref first) => self.required_attributes(first, depth + 1),
+            Pattern::Group(ref first, ref second) | Pattern::Interleave(ref first, ref second) => {
+                let mut names = self.required_attributes(first, depth + 1);
+                names.extend(self.required_attributes(second, depth + 1));
+                names
+            },
+            //Only attributes needed whichever choice is taken
+            Pattern::Choice(ref first, ref second) => {
+                let other = self.required_attributes(second, depth + 1);
+                self.required_attributes(first, depth + 1).into_iter().filter(|name| other.contains(name)).collect()
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    //Whether any attribute the pattern allows could have a name, to tell an unknown attribute from a wrong value
+    fn allows_attribute_name(&self, pattern: &Rc<Pattern>, namespace: &str, local_name: &str, depth: usize) -> bool {
+        if depth > 64 {
+            return false;
+        }
+
+        match **self.resolve(pattern) {
+            Pattern::Attribute(ref name_class, _) => name_class.contains(namespace, local_name),
+            Pattern::After(ref first, _) | Pattern::OneOrMore(ref first) => self.allows_attribute_name(first, namespace, local_name, depth + 1),
+            Pattern::Group(ref first, ref second) | Pattern::Interleave(ref first, ref second) | Pattern::Choice(ref first, ref second) => {
+                self.allows_attribute_name(first, namespace, local_name, depth + 1) || self.allows_attribute_name(second, namespace, local_name, depth + 1)
+            },
+            _ => false,
+        }
+    }
+
+    //Whether a pattern allows text among elements at this level
+    fn allows_text(&self, pattern: &Rc<Pattern>, depth: usize) -> bool {
+        if depth > 64 {
+            return false;
+        }
+
+        match **self.resolve(pattern) {
+            Pattern::Text => true,
+            Pattern::After(ref first, _) | Pattern::OneOrMore(ref first) => self.allows_text(first, depth + 1),
+            Pattern::Group(ref first, ref second) | Pattern::Interleave(ref first, ref second) | Pattern::Choice(ref first, ref second) => {
+                self.allows_text(first, depth + 1) || self.allows_text(second, depth + 1)
+            },
+            _ => false,
+        }
+    }
+
+    //Checks an element which 'pattern' has just started, returning what the pattern allows after it
+    fn check_element(&self, document: &XMLDocument, node: NodeId, pattern: Rc<Pattern>, errors: &mut Vec<ValidationError>) -> Rc<Pattern> {
+        let element = document.name(node);
+        let mut pattern = pattern;
+
+        for (name, value) in document.attributes(node) {
+            //Namespace declarations are not attributes to RELAX NG
+            if declared_prefix(name).is_some() {
+                continue;
+            }
+
+            let namespace = document.attribute_namespace(node, name).unwrap_or("");
+            let local_name = split_name(name).1;
+            let derived = self.attribute_deriv(&pattern, namespace, local_name, Some(value));
+
+            if !is_not_allowed(&derived) {
+                pattern = derived;
+            }
+            else if self.allows_attribute_name(&pattern, namespace, local_name, 0) {
+                errors.push(ValidationError::new(document, node, format!("Invalid value '{}' of attribute '{}' of '{}'", value, name, element)));
+                pattern = self.attribute_deriv(&pattern, namespace, local_name, None);
+            }
+            else {
+                errors.push(ValidationError::new(document, node, format!("Attribute '{}' is not allowed on '{}'", name, element)));
+            }
+        }
+
+        let closed = self.start_tag_close_deriv(&pattern, false);
+        pattern = if is_not_allowed(&closed) {
+            let missing = self.required_attributes(&pattern, 0);
+            let message = if !missing.is_empty() {
+                format!("Missing required attribute {} on '{}'", missing.join(" and "), element)
+            }
+            else {
+                format!("Missing required attributes on '{}'", element)
+            };
+            errors.push(ValidationError::new(document, node, message));
+
+            self.start_tag_close_deriv(&pattern, true)
+        }
+        else {
+            closed
+        };
+
+        let children = document.child_elements(node);
+        let text = document.text(node);
+
+        if children.is_empty() {
+            let mut derived = self.text_deriv(&pattern, &text);
+
+            //Whitespace alone may also be left out
+            if text.trim().is_empty() {
+                derived = choice(pattern.clone(), derived);
+            }
+
+            if is_not_allowed(&derived) {
+                let message = format!("Invalid content of '{}': '{}' is not allowed, {}", element, text.trim(), self.expected_message(&pattern));
+                errors.push(ValidationError::new(document, node, message));
+
+                return self.after_element(&pattern);
+            }
+
+            pattern = derived;
+        }
+        else {
+            if !text.trim().is_empty() && !self.allows_text(&pattern, 0) {
+                errors.push(ValidationError::new(document, node, format!("Element '{}' cannot contain text", element)));
+            }
+
+            for child in children {
+                let namespace = document.namespace(child).unwrap_or("");
+                let derived = self.start_tag_open_deriv(&pattern, namespace, document.local_name(child));
+
+                if is_not_allowed(&derived) {
+                    let message = format!("Element '{}' is not expected here in '{}', {}", document.name(child), element, self.expected_message(&pattern));
+                    errors.push(ValidationError::new(document, child, message));
+                    continue;
+                }
+
+                pattern = self.check_element(document, child, derived, errors);
+            }
+        }
+
+        let ended = self.end_tag_deriv(&pattern);
+
+        if is_not_allowed(&ended) {
+            let message = format!("Element '{}' is incomplete, {}", element, self.expected_message(&pattern));
+            errors.push(ValidationError::new(document, node, message));
+
+            self.after_element(&pattern)
+        }
+        else {
+            ended
+        }
+    }
+
+    //Checks a document by following the derivatives of the start pattern through its elements, listing where they fail.
+    //No problems means the document is valid.
+    pub fn validate(&self, document: &XMLDocument) -> Vec<ValidationError> {
+        let mut errors: Vec<ValidationError> = Vec::new();
+
+        match document.child_elements(document.root()).first() {
+            Some(&node) => {
+                let namespace = document.namespace(node).unwrap_or("");
+                let derived = self.start_tag_open_deriv(&self.start, namespace, document.local_name(node));
+
+                if is_not_allowed(&derived) {
+                    let message = format!("Element '{}' is not allowed as the root element, {}", document.name(node), self.expected_message(&self.start));
+                    errors.push(ValidationError::new(document, node, message));
+                }
+                else {
+                    self.check_element(document, node, derived, &mut errors);
+                }
+            },
+            None => errors.push(ValidationError { position: None, message: "The document has no elements".to_owned() }),
+        }
+
+        sort_by_position(&mut errors);
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {lexer, parser};
+
+    const SCHEMA: &str = "
+default namespace = \"\"
+datatypes xsd = \"http://www.w3.org/2001/XMLSchema-datatypes\"
+
+start = catalog
+catalog = element catalog { cd+ }
+cd = element cd {
+    attribute format { \"CD\" | \"LP\" }?,
+    element title { text },
+    element year { xsd:integer { minInclusive = \"1900\" } }
+}
+";
+
+    //The messages for everything in 'xml' which breaks the schema
+    fn validate(xml: &str) -> Vec<String> {
+        let schema = RelaxNG::parse(SCHEMA).unwrap();
+        let document = parser(&lexer(xml.to_owned()).unwrap()).unwrap();
+
+        schema.validate(&document).into_iter().map(|error| error.message).collect()
+    }
+
+    #[test]
+    fn valid_documents_have_no_errors() {
+        assert!(validate("<catalog><cd format=\"LP\"><title>Hide</title><year>1988</year></cd><cd><title>Empire</title><year>1985</year></cd></catalog>").is_empty());
+    }
+
+    #[test]
+    fn reports_content_attributes_and_datatypes() {
+        let errors = validate("<catalog><cd format=\"MP3\"><title>Hide</title><year>1800</year></cd><cd><year>1985</year></cd></catalog>");
+        assert_eq!(errors, vec![
+            "Invalid value 'MP3' of attribute 'format' of 'cd'",
+            "Invalid content of 'year': '1800' is not allowed, expected xsd:integer",
+            "Element 'year' is not expected here in 'cd', expected 'title'",
+            "Element 'cd' is incomplete, expected 'title'",
+        ]);
+    }
+
+    #[test]
+    fn malformed_schemas_are_an_error() {
+        assert!(RelaxNG::parse("start = element a { text").is_err());
+        assert!(RelaxNG::parse("start = b").is_err());
+        assert!(RelaxNG::parse("start = element a { xsd:madeUp }").is_err());
+    }
+}
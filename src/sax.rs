@@ -0,0 +1,95 @@
+//
+//  Description  :    Event driven reading of XML files, reacting to each element as it is read
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use pull::{XMLEvent, EventReader};
+use XMLTerm;
+
+//Receives the parts of an XML file in the order they are read. Every event is ignored unless overridden.
+pub trait XMLHandler {
+    fn start_element(&mut self, _name: &str, _attributes: &[(String, String)]) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn end_element(&mut self, _name: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn text(&mut self, _text: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn comment(&mut self, _comment: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn processing_instruction(&mut self, _target: &str, _data: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    //An empty line between two pieces of markup
+    fn blank_line(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+//Passes a stream of 'XMLTerm's to a handler without building a tree, checking that tags are closed in order
+pub fn sax_parse<I: Iterator<Item = Result<XMLTerm, String>>, H: XMLHandler>(terms: I, handler: &mut H) -> Result<(), String> {
+    for event in EventReader::new(terms) {
+        match event? {
+            XMLEvent::StartElement(name, attributes) => handler.start_element(&name, &attributes)?,
+            XMLEvent::EndElement(name) => handler.end_element(&name)?,
+            XMLEvent::Text(s) => handler.text(&s)?,
+            XMLEvent::Comment(s) => handler.comment(&s)?,
+            XMLEvent::ProcessingInstruction(target, data) => handler.processing_instruction(&target, &data)?,
+            XMLEvent::BlankLine => handler.blank_line()?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer;
+
+    //Records the events it is given
+    struct EventLog(Vec<String>);
+
+    impl XMLHandler for EventLog {
+        fn start_element(&mut self, name: &str, attributes: &[(String, String)]) -> Result<(), String> {
+            self.0.push(format!("start {} {}", name, attributes.len()));
+            Ok(())
+        }
+
+        fn end_element(&mut self, name: &str) -> Result<(), String> {
+            self.0.push(format!("end {}", name));
+            Ok(())
+        }
+
+        fn text(&mut self, text: &str) -> Result<(), String> {
+            self.0.push(format!("text {}", text));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn passes_events_in_order_and_ignores_the_rest() {
+        let terms = lexer("<a x=\"1\"><!--note--><b>hi</b></a>".to_owned()).unwrap();
+        let mut log = EventLog(Vec::new());
+
+        sax_parse(terms.into_iter().map(Ok), &mut log).unwrap();
+
+        assert_eq!(log.0, vec!["start a 1", "start b 0", "text hi", "end b", "end a"]);
+    }
+
+    #[test]
+    fn mismatched_tags_are_an_error() {
+        let terms = lexer("<a><b></a></b>".to_owned()).unwrap();
+
+        assert!(sax_parse(terms.into_iter().map(Ok), &mut EventLog(Vec::new())).is_err());
+    }
+}
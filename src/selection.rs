@@ -0,0 +1,46 @@
+//
+//  Description  :    Selection of parts of a parsed XML tree by XPath or CSS selector
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use document::{XMLDocument, NodeId};
+use xpath::{XPath, XPathItem};
+use css_selector::CSSSelector;
+use namespaces::NamespaceMap;
+
+//Expressions starting with this are CSS selectors, anything else is XPath
+pub const CSS_PREFIX: &str = "css:";
+
+//A compiled expression choosing records or column values
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum Selection {
+    XPath(XPath),
+    CSS(CSSSelector),
+}
+
+impl Selection {
+    //Compiles an XPath expression such as '//CD/TITLE', or a CSS selector such as 'css:CD > TITLE'
+    pub fn parse(expression: &str) -> Result<Selection, String> {
+        Selection::parse_with_namespaces(expression, &NamespaceMap::new())
+    }
+
+    //Compiles an expression whose prefixed names, such as 'atom:entry' or 'css:atom|entry', are resolved with the namespace map
+    pub fn parse_with_namespaces(expression: &str, namespaces: &NamespaceMap) -> Result<Selection, String> {
+        let expression = expression.trim();
+
+        match expression.strip_prefix(CSS_PREFIX) {
+            Some(selector) => Ok(Selection::CSS(CSSSelector::parse_with_namespaces(selector, namespaces)?)),
+            None => Ok(Selection::XPath(XPath::parse_with_namespaces(expression, namespaces)?)),
+        }
+    }
+
+    //Finds the selected items, relative to 'context'. CSS selectors search the elements below it.
+    pub fn evaluate(&self, document: &XMLDocument, context: NodeId) -> Vec<XPathItem> {
+        match *self {
+            Selection::XPath(ref path) => path.evaluate(document, context),
+            Selection::CSS(ref selector) => selector.select(document, context).into_iter().map(XPathItem::Node).collect(),
+        }
+    }
+}
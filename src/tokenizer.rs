@@ -0,0 +1,340 @@
+//
+//  Description  :    Zero-copy lexing of XML held in memory, yielding slices of the input
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use {XMLTerm, decode_entities, is_blank_line, declaration_depth};
+
+//A term of an XML file borrowed from the input. Text and attribute values are only copied when they contain entities.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum Token<'a> {
+    OpeningTag(&'a str),
+    ClosingTag(&'a str),
+    Text(Cow<'a, str>),
+    Attribute(&'a str, Cow<'a, str>),
+    Comment(&'a str),
+    ProcessingInstruction(&'a str, &'a str),
+    BlankLine,
+}
+
+impl<'a> Token<'a> {
+    //Copies the token into an owned 'XMLTerm'
+    pub fn to_term(&self) -> XMLTerm {
+        match *self {
+            Token::OpeningTag(name) => XMLTerm::OpeningTag(name.to_owned()),
+            Token::ClosingTag(name) => XMLTerm::ClosingTag(name.to_owned()),
+            Token::Text(ref text) => XMLTerm::Text(text.clone().into_owned()),
+            Token::Attribute(name, ref value) => XMLTerm::Attribute(name.to_owned(), value.clone().into_owned()),
+            Token::Comment(comment) => XMLTerm::Comment(comment.to_owned()),
+            Token::ProcessingInstruction(target, data) => XMLTerm::ProcessingInstruction(target.to_owned(), data.to_owned()),
+            Token::BlankLine => XMLTerm::BlankLine,
+        }
+    }
+}
+
+//Decodes entity references only if there are any
+fn decode<'a>(text: &'a str) -> Cow<'a, str> {
+    if text.contains('&') {
+        Cow::Owned(decode_entities(text))
+    }
+    else {
+        Cow::Borrowed(text)
+    }
+}
+
+//Splits off the text up to the first whitespace or 'stops' character
+fn take_name<'a>(text: &'a str, stops: &[char]) -> (&'a str, &'a str) {
+    match text.find(|c: char| c.is_whitespace() || stops.contains(&c)) {
+        Some(end) => (&text[..end], &text[end..]),
+        None => (text, ""),
+    }
+}
+
+//Decodes an attribute value, with each whitespace character written in it read as a space as the XML specification
+//requires. Whitespace written as a character reference is kept.
+fn normalize_value<'a>(value: &'a str) -> Cow<'a, str> {
+    if value.contains(['\t', '\n', '\r']) {
+        Cow::Owned(decode_entities(&value.replace(['\t', '\n', '\r'], " ")))
+    }
+    else {
+        decode(value)
+    }
+}
+
+//Converts the inside of an opening tag, such as 'game platform="PC"', into tokens
+fn tokenize_tag<'a>(markup: &'a str, keep_whitespace: bool, tokens: &mut VecDeque<Token<'a>>) -> Result<(), String> {
+    //A tag such as '<br/>' opens and closes an element
+    let self_closing = markup.ends_with('/');
+    let markup = if self_closing { &markup[..markup.len() - 1] } else { markup };
+
+    let (name, mut rest) = take_name(markup.trim(), &[]);
+    if name.is_empty() {
+        return Err(format!("Missing tag name in <{}>", markup));
+    }
+
+    tokens.push_back(Token::OpeningTag(name));
+
+    loop {
+        rest = rest.trim_start();
+
+        if rest.is_empty() {
+            break;
+        }
+
+        let (attribute_name, after_name) = take_name(rest, &['=']);
+
+        rest = after_name.trim_start();
+        if !rest.starts_with('=') {
+            return Err(format!("Expected '=' after attribute {} of tag {}", attribute_name, name));
+        }
+
+        rest = rest[1..].trim_start();
+        let quote = match rest.chars().next() {
+            Some(c) if c == '"' || c == '\'' => c,
+            _ => return Err(format!("Expected a quoted value for attribute {} of tag {}", attribute_name, name)),
+        };
+
+        rest = &rest[1..];
+        let end = match rest.find(quote) {
+            Some(end) => end,
+            None => return Err(format!("Unterminated value for attribute {} of tag {}", attribute_name, name)),
+        };
+
+        let value = if keep_whitespace { normalize_value(&rest[..end]) } else { decode(&rest[..end]) };
+        tokens.push_back(Token::Attribute(attribute_name, value));
+        rest = &rest[end + 1..];
+    }
+
+    if self_closing {
+        tokens.push_back(Token::ClosingTag(name));
+    }
+
+    Ok(())
+}
+
+//Converts everything between a '<' and its '>' into tokens
+fn tokenize_markup<'a>(markup: &'a str, keep_whitespace: bool, tokens: &mut VecDeque<Token<'a>>) -> Result<(), String> {
+    if markup.starts_with("!--") {
+        tokens.push_back(Token::Comment(&markup[3..markup.len() - 2]));
+    }
+    else if markup.starts_with("![CDATA[") {
+        //Character data is kept exactly as written
+        let data = &markup[8..markup.len() - 2];
+        if !data.is_empty() {
+            tokens.push_back(Token::Text(Cow::Borrowed(data)));
+        }
+    }
+    else if markup.starts_with('?') {
+        let instruction = &markup[1..markup.len() - 1];
+        let (target, data) = take_name(instruction, &[]);
+        tokens.push_back(Token::ProcessingInstruction(target, data.trim()));
+    }
+    else if markup.starts_with('!') {
+        //Ignore document type declarations
+    }
+    else if let Some(closing) = markup.strip_prefix('/') {
+        let name = closing.trim();
+
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("Invalid closing tag </{}>", closing));
+        }
+
+        tokens.push_back(Token::ClosingTag(name));
+    }
+    else {
+        tokenize_tag(markup, keep_whitespace, tokens)?;
+    }
+
+    Ok(())
+}
+
+//Finds the end of a tag or declaration starting after its '<', skipping quoted values and nested brackets
+fn find_tag_end(markup: &str) -> Result<Option<usize>, String> {
+    let declaration = markup.starts_with('!');
+    let mut quote: Option<char> = None;
+    let mut bracket_depth: usize = 0;
+
+    for (index, c) in markup.char_indices() {
+        if let Some(q) = quote {
+            //Nothing ends a tag inside a quoted attribute value
+            if c == q {
+                quote = None;
+            }
+            continue;
+        }
+
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '[' | ']' if declaration => bracket_depth = declaration_depth(bracket_depth, c)?,
+            '<' if !declaration => return Err("Unexpected '<'".to_owned()),
+            '>' if bracket_depth == 0 => return Ok(Some(index)),
+            _ => {},
+        }
+    }
+
+    Ok(None)
+}
+
+//Reads the tokens of an XML file held in memory, without copying any text that has no entities
+pub struct Tokenizer<'a> {
+    input: &'a str,
+    position: usize,
+    tokens: VecDeque<Token<'a>>,
+    failed: bool,
+    //Whether text is kept whole, whitespace and all, rather than trimmed
+    keep_whitespace: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(input: &'a str) -> Tokenizer<'a> {
+        Tokenizer {
+            input,
+            position: 0,
+            tokens: VecDeque::new(),
+            failed: false,
+            keep_whitespace: false,
+        }
+    }
+
+    //Reads the tokens of an XML file keeping all of its text, such as the whitespace between elements, and reading
+    //attribute values as the XML specification does. Line endings should already be '\n'.
+    pub fn keeping_whitespace(input: &'a str) -> Tokenizer<'a> {
+        let mut tokenizer = Tokenizer::new(input);
+        tokenizer.keep_whitespace = true;
+        tokenizer
+    }
+
+    //The byte offset of the input which has been read up to
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    //Reads the next piece of text or markup, queueing any tokens it completes
+    fn read(&mut self) -> Result<(), String> {
+        let rest = &self.input[self.position..];
+
+        if !rest.starts_with('<') {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = rest[..end].trim();
+
+            if self.keep_whitespace {
+                self.tokens.push_back(Token::Text(decode(&rest[..end])));
+            }
+            else if !text.is_empty() {
+                self.tokens.push_back(Token::Text(decode(text)));
+            }
+            else if is_blank_line(&rest[..end]) {
+                self.tokens.push_back(Token::BlankLine);
+            }
+
+            self.position += end;
+            return Ok(());
+        }
+
+        let markup = &rest[1..];
+
+        //Comments, character data and processing instructions end with their own delimiters
+        let end = if let Some(comment) = markup.strip_prefix("!--") {
+            comment.find("-->").map(|end| end + 3 + 2)
+        }
+        else if let Some(data) = markup.strip_prefix("![CDATA[") {
+            data.find("]]>").map(|end| end + 8 + 2)
+        }
+        else if let Some(instruction) = markup.strip_prefix('?') {
+            instruction.find("?>").map(|end| end + 1 + 1)
+        }
+        else {
+            find_tag_end(markup)?
+        };
+
+        let end = match end {
+            Some(end) => end,
+            None => return Err("Unexpected end of file inside a tag".to_owned()),
+        };
+
+        tokenize_markup(&markup[..end], self.keep_whitespace, &mut self.tokens)?;
+        self.position += end + 2;
+
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, String>;
+
+    fn next(&mut self) -> Option<Result<Token<'a>, String>> {
+        while self.tokens.is_empty() {
+            if self.failed || self.position >= self.input.len() {
+                return None;
+            }
+
+            if let Err(e) = self.read() {
+                self.failed = true;
+                return Some(Err(e));
+            }
+        }
+
+        self.tokens.pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer;
+
+    fn terms(xml: &str) -> Result<Vec<XMLTerm>, String> {
+        Tokenizer::new(xml).map(|token| token.map(|token| token.to_term())).collect()
+    }
+
+    #[test]
+    fn text_without_entities_is_borrowed() {
+        let tokens: Vec<Token> = Tokenizer::new("<a b='x &amp; y'>plain</a>").collect::<Result<Vec<Token>, String>>().unwrap();
+
+        assert_eq!(tokens[1], Token::Attribute("b", Cow::Owned("x & y".to_owned())));
+        match tokens[2] {
+            Token::Text(Cow::Borrowed("plain")) => {},
+            ref other => panic!("Expected borrowed text, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn agrees_with_the_character_lexer() {
+        let xml = "<?xml version=\"1.0\"?>\n<!DOCTYPE a [ <!ENTITY e \"x\"> ]>\n<a x=\"1&lt;2\"><!-- c --><b/>\n\n<![CDATA[<raw>]]> t &amp; u<?pi d?></a>";
+
+        assert_eq!(terms(xml).unwrap(), lexer(xml.to_owned()).unwrap());
+    }
+
+    #[test]
+    fn whitespace_can_be_kept() {
+        let tokens: Vec<XMLTerm> = Tokenizer::keeping_whitespace("<a x=\"p\tq&#10;r\">\n <b> t </b></a>").map(|token| token.unwrap().to_term()).collect();
+
+        assert_eq!(tokens, vec![
+            XMLTerm::OpeningTag("a".to_owned()),
+            XMLTerm::Attribute("x".to_owned(), "p q\nr".to_owned()),
+            XMLTerm::Text("\n ".to_owned()),
+            XMLTerm::OpeningTag("b".to_owned()),
+            XMLTerm::Text(" t ".to_owned()),
+            XMLTerm::ClosingTag("b".to_owned()),
+            XMLTerm::ClosingTag("a".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn unmatched_bracket_in_declaration_is_an_error() {
+        let xml = "<!DOCTYPE a ]><a>1</a>";
+
+        assert_eq!(terms(xml).err(), Some("Unmatched ']' in declaration".to_owned()));
+        assert_eq!(terms(xml).err(), lexer(xml.to_owned()).err());
+    }
+
+    #[test]
+    fn unterminated_markup_is_an_error() {
+        assert!(terms("<a><!-- never closed").is_err());
+    }
+}
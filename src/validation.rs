@@ -0,0 +1,158 @@
+//
+//  Description  :    Problems found when checking a parsed XML tree against a schema
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::fmt;
+use document::{XMLDocument, NodeId, Position};
+
+//A part of a document which breaks a rule of its schema, and where it was in the file
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub struct ValidationError {
+    pub position: Option<Position>,
+    pub message: String,
+}
+
+impl ValidationError {
+    //A problem with a node, placed at the node or, if it was not read from a file, at the nearest element around it which was
+    pub fn new(document: &XMLDocument, node: NodeId, message: String) -> ValidationError {
+        let mut current = Some(node);
+        let mut position = None;
+
+        while let Some(id) = current {
+            position = document.position(id);
+            if position.is_some() {
+                break;
+            }
+            current = document.parent(id);
+        }
+
+        ValidationError {
+            position,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{}: {}", position, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+//Puts problems in the order they appear in the file, with any which have no position first
+pub fn sort_by_position(errors: &mut [ValidationError]) {
+    errors.sort_by_key(|error| error.position.map(|position| (position.line, position.column)));
+}
+
+//Finds every position a part of a content model can end at when repeated between 'min_occurs' and 'max_occurs' times,
+//starting from any of 'starts'. 'match_once' finds the positions a single occurrence can end at.
+pub fn match_repeated<F: FnMut(&[usize]) -> Vec<usize>>(min_occurs: usize, max_occurs: Option<usize>, starts: &[usize], mut match_once: F) -> Vec<usize> {
+    let mut ends: Vec<usize> = if min_occurs == 0 { starts.to_vec() } else { Vec::new() };
+    let mut current = starts.to_vec();
+    let mut count = 0;
+
+    //Repeat until the limit, or until no new positions are reached
+    while max_occurs.is_none_or(|max| count < max) && !current.is_empty() {
+        let mut next = match_once(&current);
+        next.sort();
+        next.dedup();
+        count += 1;
+
+        //Repeats which reach the same positions again always will, so the minimum is as good as reached
+        let settled = next == current;
+        current = next;
+
+        if count >= min_occurs || settled {
+            let before = ends.len();
+            ends.extend(current.iter().cloned());
+            ends.sort();
+            ends.dedup();
+
+            //Repeating from positions already reached cannot reach any others, whatever the limit
+            if ends.len() == before || settled {
+                break;
+            }
+        }
+    }
+
+    ends
+}
+
+//Where the children of an element stop matching its content model
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum ContentProblem {
+    //The children run out before the content is complete, with what was expected next
+    Incomplete(String),
+    //The child at this index cannot come where it does, with what was expected instead
+    Unexpected(usize, String),
+}
+
+//Checks the names of an element's children against its content model. 'match_content' finds the positions the content
+//can end at within the first names, adding the names of elements which could have come next when those ran out to 'expected'.
+pub fn check_content_model<F: FnMut(usize, &mut Vec<String>) -> Vec<usize>>(names: usize, mut match_content: F) -> Option<ContentProblem> {
+    if match_content(names, &mut Vec::new()).contains(&names) {
+        return None;
+    }
+
+    //Find the longest run of children which could start valid content, to say where it goes wrong
+    for length in (0..names + 1).rev() {
+        let mut expected: Vec<String> = Vec::new();
+        let ends = match_content(length, &mut expected);
+
+        if !ends.contains(&length) && expected.is_empty() {
+            continue;
+        }
+
+        expected.sort();
+        expected.dedup();
+        let expected = if !expected.is_empty() { format!("expected {}", expected.join(" or ")) } else { "expected no more elements".to_owned() };
+
+        return Some(if length == names { ContentProblem::Incomplete(expected) } else { ContentProblem::Unexpected(length, expected) });
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Matches one 'a' at each of 'starts' in 'names'
+    fn match_a(names: &[&str], starts: &[usize], expected: &mut Vec<String>) -> Vec<usize> {
+        starts.iter().filter_map(|&start| match names.get(start) {
+            Some(&"a") => Some(start + 1),
+            Some(_) => None,
+            None => {
+                expected.push("'a'".to_owned());
+                None
+            },
+        }).collect()
+    }
+
+    #[test]
+    fn repeats_between_the_limits() {
+        let names = ["a", "a", "a"];
+
+        assert_eq!(match_repeated(2, Some(3), &[0], |current| match_a(&names, current, &mut Vec::new())), vec![2, 3]);
+        assert_eq!(match_repeated(0, None, &[0], |current| match_a(&names, current, &mut Vec::new())), vec![0, 1, 2, 3]);
+        assert_eq!(match_repeated(50000000, Some(50000000), &[0], |current| current.to_vec()), vec![0]);
+    }
+
+    #[test]
+    fn finds_where_content_goes_wrong() {
+        let check = |names: &[&str]| check_content_model(names.len(), |length, expected| match_repeated(2, Some(2), &[0], |current| match_a(&names[..length], current, expected)));
+
+        assert_eq!(check(&["a", "a"]), None);
+        assert_eq!(check(&["a"]), Some(ContentProblem::Incomplete("expected 'a'".to_owned())));
+        assert_eq!(check(&["a", "b"]), Some(ContentProblem::Unexpected(1, "expected 'a'".to_owned())));
+        assert_eq!(check(&["a", "a", "a"]), Some(ContentProblem::Unexpected(2, "expected no more elements".to_owned())));
+    }
+}
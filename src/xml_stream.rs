@@ -0,0 +1,250 @@
+//
+//  Description  :    Streaming conversion of XML files to CSV, one record at a time
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::io::Write;
+use sax::{XMLHandler, sax_parse};
+use {XMLTerm, XMLLexer, EncodedWriter, csv_escape};
+
+//Reads 'XMLTerm's one at a time from the characters of a file
+pub struct TermReader<I: Iterator<Item = Result<char, String>>> {
+    chars: I,
+    xml_lexer: XMLLexer,
+    terms: Vec<XMLTerm>,
+    finished: bool,
+}
+
+impl<I: Iterator<Item = Result<char, String>>> TermReader<I> {
+    pub fn new(chars: I) -> TermReader<I> {
+        TermReader {
+            chars,
+            xml_lexer: XMLLexer::new(),
+            terms: Vec::new(),
+            finished: false,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<char, String>>> Iterator for TermReader<I> {
+    type Item = Result<XMLTerm, String>;
+
+    fn next(&mut self) -> Option<Result<XMLTerm, String>> {
+        //Read characters until the lexer completes a term
+        while self.terms.is_empty() {
+            let c = match self.chars.next() {
+                Some(Ok(c)) => c,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    //Complete any text left at the end of the file
+                    if self.finished {
+                        return None;
+                    }
+
+                    self.finished = true;
+                    match self.xml_lexer.finish(&mut self.terms) {
+                        Ok(_) => continue,
+                        Err(e) => return Some(Err(e)),
+                    }
+                },
+            };
+
+            if let Err(e) = self.xml_lexer.lex_char(c, &mut self.terms) {
+                return Some(Err(e));
+            }
+        }
+
+        Some(Ok(self.terms.remove(0)))
+    }
+}
+
+//An element which has been opened but not yet closed
+struct OpenElement {
+    name: String,
+    data: String,
+    has_children: bool,
+}
+
+//Writes one row of a record, starting with the column titles if this is the first record
+fn write_record<W: Write>(writer: &mut EncodedWriter<W>, record: &[(String, String)], columns: &mut Option<Vec<String>>, row: usize) -> Result<(), String> {
+    //The first record decides the columns of the file
+    if columns.is_none() {
+        let mut keys: Vec<String> = Vec::new();
+        for (key, _) in record {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+
+        let titles: Vec<String> = keys.iter()
+            .map(|key| csv_escape(key.split('/').next_back().unwrap_or("")))
+            .collect();
+        writer.write_str(&titles.join(","))?;
+        writer.write_str("\n")?;
+
+        *columns = Some(keys);
+    }
+
+    let keys = columns.as_ref().unwrap();
+
+    //Later records cannot add columns once the titles have been written, and their data must not be lost
+    for (index, (key, _)) in record.iter().enumerate() {
+        if !keys.contains(key) {
+            return Err(format!("Record {} has {}, which was not in the first record. Convert without streaming to take the columns from every record", row, key));
+        }
+
+        //A row has one entry for each column, so a repeated end node would lose all but one of its values
+        if record[..index].iter().any(|(earlier_key, _)| earlier_key == key) {
+            return Err(format!("Record {} has more than one {}, which cannot all be written in its column", row, key));
+        }
+    }
+
+    let entries: Vec<String> = keys.iter()
+        .map(|key| match record.iter().find(|&(record_key, _)| record_key == key) {
+            Some((_, data)) => csv_escape(data),
+            None => String::new(),
+        })
+        .collect();
+    writer.write_str(&entries.join(","))?;
+    writer.write_str("\n")
+}
+
+//Collects the 'end nodes' of each record, writing a CSV row as soon as the record closes
+struct CSVRecordWriter<'a, W: Write + 'a> {
+    writer: &'a mut EncodedWriter<W>,
+    record_name: Option<&'a str>,
+    open_elements: Vec<OpenElement>,
+    record_depth: Option<usize>,
+    record: Vec<(String, String)>,
+    columns: Option<Vec<String>>,
+    rows: usize,
+}
+
+impl<'a, W: Write> XMLHandler for CSVRecordWriter<'a, W> {
+    fn start_element(&mut self, name: &str, _attributes: &[(String, String)]) -> Result<(), String> {
+        if let Some(parent) = self.open_elements.last_mut() {
+            parent.has_children = true;
+        }
+
+        //Check if this element starts a new record
+        if self.record_depth.is_none() {
+            let is_record = match self.record_name {
+                Some(record_name) => name == record_name,
+                None => self.open_elements.len() == 1,
+            };
+
+            if is_record {
+                self.record_depth = Some(self.open_elements.len());
+            }
+        }
+
+        self.open_elements.push(OpenElement {
+            name: name.to_owned(),
+            data: String::new(),
+            has_children: false,
+        });
+
+        Ok(())
+    }
+
+    fn end_element(&mut self, _name: &str) -> Result<(), String> {
+        //The tags have already been checked to match
+        let element = match self.open_elements.pop() {
+            Some(element) => element,
+            None => return Ok(()),
+        };
+
+        let depth = match self.record_depth {
+            Some(depth) => depth,
+            None => return Ok(()),
+        };
+
+        //Keep the data of 'end nodes', keyed by their path within the record
+        if !element.has_children && !element.data.is_empty() {
+            let mut path: Vec<&str> = self.open_elements.iter().skip(depth + 1).map(|open| open.name.as_str()).collect();
+            path.push(&element.name);
+            self.record.push((path.join("/"), element.data.clone()));
+        }
+
+        //Write the record as soon as it is closed
+        if self.open_elements.len() == depth {
+            write_record(self.writer, &self.record, &mut self.columns, self.rows + 1)?;
+            self.record.clear();
+            self.record_depth = None;
+            self.rows += 1;
+        }
+
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), String> {
+        if let Some(element) = self.open_elements.last_mut() {
+            element.data.push_str(text);
+        }
+
+        Ok(())
+    }
+}
+
+//Converts a stream of 'XMLTerm's into CSV rows, writing each record as soon as it closes.
+//Records are elements named 'record_name', or the children of the outermost element if no name is given.
+pub fn xml_to_csv_stream<I: Iterator<Item = Result<XMLTerm, String>>, W: Write>(terms: I, writer: &mut EncodedWriter<W>, record_name: Option<&str>) -> Result<usize, String> {
+    let mut handler = CSVRecordWriter {
+        writer,
+        record_name,
+        open_elements: Vec::new(),
+        record_depth: None,
+        record: Vec::new(),
+        columns: None,
+        rows: 0,
+    };
+
+    sax_parse(terms, &mut handler)?;
+
+    Ok(handler.rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use OutputOptions;
+
+    //Streams XML text to CSV text
+    fn stream_to_csv(xml: &str, record_name: Option<&str>) -> Result<String, String> {
+        let mut output: Vec<u8> = Vec::new();
+        {
+            let mut writer = EncodedWriter::new(&mut output, &OutputOptions::new())?;
+            xml_to_csv_stream(TermReader::new(xml.chars().map(Ok)), &mut writer, record_name)?;
+        }
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn writes_a_row_for_each_record() {
+        let csv = stream_to_csv("<games><game><name>Halo</name><year>2001</year></game><game><year>1993</year></game></games>", None);
+
+        assert_eq!(csv.unwrap(), "name,year\nHalo,2001\n,1993\n");
+    }
+
+    #[test]
+    fn records_can_be_named_at_any_depth() {
+        let csv = stream_to_csv("<a><b><r><x>1</x></r></b><b><r><x>2</x></r></b></a>", Some("r"));
+
+        assert_eq!(csv.unwrap(), "x\n1\n2\n");
+    }
+
+    #[test]
+    fn a_column_missing_from_the_first_record_is_an_error() {
+        let csv = stream_to_csv("<games><game><name>Halo</name></game><game><name>Myst</name><year>1993</year></game></games>", None);
+
+        assert!(csv.unwrap_err().contains("Record 2 has year"));
+    }
+
+    #[test]
+    fn a_repeated_end_node_is_an_error() {
+        let csv = stream_to_csv("<games><game><tag>a</tag><tag>b</tag></game></games>", None);
+
+        assert_eq!(csv.unwrap_err(), "Record 1 has more than one tag, which cannot all be written in its column");
+    }
+}
@@ -0,0 +1,318 @@
+//
+//  Description  :    Writes a parsed XML tree, or any part of it, straight to a file
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::io::Write;
+use document::{XMLDocument, NodeId, NodeKind};
+use {EncodedWriter, OutputOptions, Indent, xml_declaration, escape_text, escape_attribute};
+
+//The whitespace before a node at 'depth'
+pub fn indentation(depth: usize, options: &OutputOptions) -> String {
+    if options.compact {
+        return String::new();
+    }
+
+    match options.indent {
+        Indent::Spaces(width) => " ".repeat(width * depth),
+        Indent::Tabs => "\t".repeat(depth),
+    }
+}
+
+//What follows a node at 'depth'. Compact files only break lines after the outermost nodes.
+pub fn line_end(depth: usize, options: &OutputOptions) -> &'static str {
+    if options.compact && depth > 0 {
+        ""
+    }
+    else {
+        "\n"
+    }
+}
+
+//What is written before a node which had a blank line before it when it was read
+pub fn blank_line(options: &OutputOptions) -> &'static str {
+    if options.preserve_blank_lines && !options.compact {
+        "\n"
+    }
+    else {
+        ""
+    }
+}
+
+//Writes the start of an element and its text. An element without children is closed on the same line,
+//otherwise its children follow and it is closed by 'element_end'.
+pub fn element_start(name: &str, attributes: &[(&str, &str)], text: &str, depth: usize, has_children: bool, options: &OutputOptions) -> String {
+    let indent = indentation(depth, options);
+
+    //What comes after the attributes depends on what is inside the element
+    let ending = if has_children {
+        format!(">{}{}", escape_text(text), line_end(depth + 1, options))
+    }
+    else if text.is_empty() && options.self_closing {
+        format!("/>{}", line_end(depth, options))
+    }
+    else {
+        format!(">{}</{}>{}", escape_text(text), name, line_end(depth, options))
+    };
+
+    let attributes: Vec<String> = attributes.iter()
+        .map(|&(name, value)| format!("{}=\"{}\"", name, escape_attribute(value)))
+        .collect();
+
+    let mut tag = format!("{}<{}", indent, name);
+    for attribute in &attributes {
+        tag.push(' ');
+        tag.push_str(attribute);
+    }
+
+    //Check the width of the line the tag starts, up to the first line break
+    let line_width = tag.chars().count() + ending.lines().next().unwrap_or("").chars().count();
+    let wrap = match options.max_line_width {
+        Some(width) => !options.compact && !attributes.is_empty() && line_width > width,
+        None => false,
+    };
+
+    if wrap {
+        //Put each attribute on its own line, one level further in than the tag
+        let attribute_indent = indentation(depth + 1, options);
+
+        tag = format!("{}<{}", indent, name);
+        for attribute in &attributes {
+            tag.push('\n');
+            tag.push_str(&attribute_indent);
+            tag.push_str(attribute);
+        }
+    }
+
+    tag.push_str(&ending);
+    tag
+}
+
+//Writes the closing tag of an element with children
+pub fn element_end(name: &str, depth: usize, options: &OutputOptions) -> String {
+    format!("{}</{}>{}", indentation(depth, options), name, line_end(depth, options))
+}
+
+pub fn comment(text: &str, depth: usize, options: &OutputOptions) -> String {
+    format!("{}<!--{}-->{}", indentation(depth, options), text, line_end(depth, options))
+}
+
+pub fn processing_instruction(target: &str, data: &str, depth: usize, options: &OutputOptions) -> String {
+    if data.is_empty() {
+        format!("{}<?{}?>{}", indentation(depth, options), target, line_end(depth, options))
+    }
+    else {
+        format!("{}<?{} {}?>{}", indentation(depth, options), target, data, line_end(depth, options))
+    }
+}
+
+//Finds the namespace declarations made above 'node' which are not made again on it, so a subtree can be written on its own
+fn inherited_namespaces(document: &XMLDocument, node: NodeId) -> Vec<(&str, &str)> {
+    let mut ancestors: Vec<NodeId> = Vec::new();
+    let mut ancestor = document.parent(node);
+
+    while let Some(current) = ancestor {
+        ancestors.push(current);
+        ancestor = document.parent(current);
+    }
+
+    //Read from the outermost ancestor in, so the closest declaration of each prefix is the one kept
+    let mut namespaces: Vec<(&str, &str)> = Vec::new();
+
+    for &current in ancestors.iter().rev() {
+        for (name, value) in document.attributes(current) {
+            if name != "xmlns" && !name.starts_with("xmlns:") {
+                continue;
+            }
+
+            match namespaces.iter().position(|&(declared, _)| declared == name) {
+                Some(index) => namespaces[index] = (name, value),
+                None => namespaces.push((name, value)),
+            }
+        }
+    }
+
+    namespaces.retain(|&(name, _)| document.attribute(node, name).is_none());
+    namespaces
+}
+
+//Writes a node and everything below it on one line, as for a node inside a compact element
+fn inline_node(document: &XMLDocument, node: NodeId, attributes: &[(&str, &str)], options: &OutputOptions) -> String {
+    match document.kind(node) {
+        NodeKind::Element => {
+            let children = document.children(node);
+            let mut text = element_start(document.name(node), attributes, "", 1, !children.is_empty(), options);
+
+            for &child in children {
+                text.push_str(&inline_node(document, child, &document.attributes(child), options));
+            }
+
+            if !children.is_empty() {
+                text.push_str(&element_end(document.name(node), 1, options));
+            }
+
+            text
+        },
+        NodeKind::Text => escape_text(document.data(node)),
+        NodeKind::Comment => comment(document.data(node), 1, options),
+        NodeKind::ProcessingInstruction => processing_instruction(document.name(node), document.data(node), 1, options),
+    }
+}
+
+fn write_element<W: Write>(document: &XMLDocument, node: NodeId, writer: &mut EncodedWriter<W>, depth: usize, extra_attributes: &[(&str, &str)]) -> Result<(), String> {
+    let mut children = document.children(node);
+    let mut text = String::new();
+
+    if document.blank_line_before(node) {
+        text.push_str(blank_line(writer.options()));
+    }
+
+    match document.kind(node) {
+        NodeKind::Element => {
+            let mut attributes = extra_attributes.to_vec();
+            attributes.extend(document.attributes(node));
+
+            //Text alone stays on the line of its tags
+            if children.iter().all(|&child| document.kind(child) == NodeKind::Text) {
+                text.push_str(&element_start(document.name(node), &attributes, &document.text(node), depth, false, writer.options()));
+                children = &[];
+            }
+            //Text mixed with other nodes keeps them all on its line, as breaking the line would change the text
+            else if document.has_text(node) {
+                let mut options = writer.options().clone();
+                options.compact = true;
+
+                text.push_str(&indentation(depth, writer.options()));
+                text.push_str(&inline_node(document, node, &attributes, &options));
+                text.push_str(line_end(depth, writer.options()));
+                children = &[];
+            }
+            else {
+                text.push_str(&element_start(document.name(node), &attributes, "", depth, true, writer.options()));
+            }
+        },
+        NodeKind::Text => text.push_str(&format!("{}{}{}", indentation(depth, writer.options()), escape_text(document.data(node)), line_end(depth, writer.options()))),
+        NodeKind::Comment => text.push_str(&comment(document.data(node), depth, writer.options())),
+        NodeKind::ProcessingInstruction => text.push_str(&processing_instruction(document.name(node), document.data(node), depth, writer.options())),
+    }
+
+    writer.write_xml(&text)?;
+
+    //If we span multiple lines, write the children and close the element after them
+    if !children.is_empty() {
+        for &child in children {
+            write_element(document, child, writer, depth + 1, &[])?;
+        }
+
+        let end = element_end(document.name(node), depth, writer.options());
+        writer.write_xml(&end)?;
+    }
+
+    Ok(())
+}
+
+//Writes a node and everything below it. The root of a document writes every node at the top of the document.
+pub fn write_node<W: Write>(document: &XMLDocument, node: NodeId, writer: &mut EncodedWriter<W>) -> Result<(), String> {
+    if node == document.root() {
+        for &child in document.children(node) {
+            write_element(document, child, writer, 0, &[])?;
+        }
+
+        return Ok(());
+    }
+
+    write_element(document, node, writer, 0, &inherited_namespaces(document, node))
+}
+
+//Writes a node and everything below it as a complete XML file, with the chosen encoding, line endings and layout
+pub fn write_xml<W: Write>(document: &XMLDocument, node: NodeId, writer: W, options: &OutputOptions) -> Result<(), String> {
+    let mut writer = EncodedWriter::new(writer, options)?;
+
+    writer.write_xml(&xml_declaration(options))?;
+    write_node(document, node, &mut writer)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {lexer, parser};
+
+    //Parses 'xml' and writes the node found by 'find' back out with 'options'
+    fn rewrite<F: Fn(&XMLDocument) -> NodeId>(xml: &str, find: F, options: &OutputOptions) -> String {
+        let document = parser(&lexer(xml.to_owned()).unwrap()).unwrap();
+        let mut output = Vec::new();
+
+        write_xml(&document, find(&document), &mut output, options).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn writes_attributes_comments_and_instructions() {
+        let output = rewrite("<a x=\"1 &amp; 2\"><!--note--><?style sheet?><b>t&lt;</b></a>", |document| document.root(), &OutputOptions::new());
+
+        assert!(output.starts_with("<?xml "), "{}", output);
+        assert!(output.ends_with("<a x=\"1 &amp; 2\">\n  <!--note-->\n  <?style sheet?>\n  <b>t&lt;</b>\n</a>\n"), "{}", output);
+    }
+
+    #[test]
+    fn a_subtree_keeps_the_namespaces_declared_above_it() {
+        let output = rewrite("<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:m=\"urn:m\"><entry m:id=\"1\"/></feed>", |document| {
+            let feed = document.child_elements(document.root())[0];
+            document.child_elements(feed)[0]
+        }, &OutputOptions::new());
+
+        assert!(output.contains("<entry xmlns=\"http://www.w3.org/2005/Atom\" xmlns:m=\"urn:m\" m:id=\"1\"></entry>"), "{}", output);
+    }
+
+    #[test]
+    fn written_files_read_back_the_same() {
+        let xml = "<CATALOG><CD id=\"1\"><TITLE>Empire</TITLE></CD><!--end--></CATALOG>";
+        let once = rewrite(xml, |document| document.root(), &OutputOptions::new());
+        let twice = rewrite(&once, |document| document.root(), &OutputOptions::new());
+
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn mixed_content_stays_on_one_line() {
+        let mut document = XMLDocument::new();
+        let body = document.append_element(document.root(), "body");
+        let p = document.append_element(body, "p");
+        document.append_text(p, "Hello ");
+        let b = document.append_element(p, "b");
+        document.append_text(b, "world");
+        document.append_text(p, " again");
+
+        let mut output = Vec::new();
+        write_xml(&document, body, &mut output, &OutputOptions::new()).unwrap();
+
+        assert!(String::from_utf8(output).unwrap().ends_with("<body>\n  <p>Hello <b>world</b> again</p>\n</body>\n"));
+    }
+
+    #[test]
+    fn instructions_without_data_have_no_space() {
+        let options = OutputOptions::new();
+
+        assert_eq!(processing_instruction("page-break", "", 0, &options), "<?page-break?>\n");
+        assert_eq!(processing_instruction("style", "sheet", 1, &options), "  <?style sheet?>\n");
+    }
+
+    #[test]
+    fn follows_the_layout_options() {
+        let xml = "<a><b x=\"first value\" y=\"second value\"/><c/></a>";
+        let root = |document: &XMLDocument| document.root();
+
+        let mut options = OutputOptions::new();
+        options.indent = Indent::Tabs;
+        options.self_closing = true;
+        assert!(rewrite(xml, root, &options).ends_with("<a>\n\t<b x=\"first value\" y=\"second value\"/>\n\t<c/>\n</a>\n"));
+
+        options.max_line_width = Some(20);
+        assert!(rewrite(xml, root, &options).ends_with("<a>\n\t<b\n\t\tx=\"first value\"\n\t\ty=\"second value\"/>\n\t<c/>\n</a>\n"));
+
+        options.compact = true;
+        assert!(rewrite(xml, root, &options).ends_with("<a><b x=\"first value\" y=\"second value\"/><c/></a>\n"));
+    }
+}
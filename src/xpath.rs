@@ -0,0 +1,493 @@
+//
+//  Description  :    A subset of XPath 1.0 for selecting parts of a parsed XML tree
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::collections::HashSet;
+use document::{XMLDocument, NodeId, NodeKind};
+use namespaces::{NamespaceMap, NameTest};
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+enum Axis {
+    Child,
+    //'//', every node below the context node and the node itself
+    DescendantOrSelf,
+    SelfNode,
+    Parent,
+    Attribute,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum NodeTest {
+    Name(NameTest),
+    Wildcard,
+    Text,
+    Comment,
+    Node,
+}
+
+//The value a predicate compares against, such as '@platform' or 'TITLE'
+#[derive(Debug)]
+#[derive(Clone)]
+enum Operand {
+    Attribute(NameTest),
+    Child(NameTest),
+    Text,
+    SelfNode,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum Predicate {
+    Position(usize),
+    Last,
+    Exists(Operand),
+    Equals(Operand, String),
+    NotEquals(Operand, String),
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+struct Step {
+    axis: Axis,
+    test: NodeTest,
+    predicates: Vec<Predicate>,
+}
+
+//A compiled XPath expression
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct XPath {
+    absolute: bool,
+    steps: Vec<Step>,
+}
+
+//A selected part of a document
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum XPathItem {
+    Node(NodeId),
+    Attribute(NodeId, String),
+    Text(NodeId),
+}
+
+//Reads an XPath expression one character at a time
+struct ExpressionReader<'a> {
+    expression: &'a str,
+    position: usize,
+    namespaces: &'a NamespaceMap,
+}
+
+impl<'a> ExpressionReader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.expression[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    //Consumes 'token' if the expression continues with it
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        }
+        else {
+            Err(format!("Expected '{}' at position {} of {}", token, self.position, self.expression))
+        }
+    }
+
+    //Reads a name, which may be written '{uri}local', or end in '*' to match every name in a namespace
+    fn name(&mut self) -> Result<NameTest, String> {
+        self.skip_whitespace();
+
+        let rest = self.rest();
+        let uri_length = if rest.starts_with("*:") {
+            2
+        }
+        else if rest.starts_with('{') {
+            match rest.find('}') {
+                Some(end) => end + 1,
+                None => return Err(format!("Unterminated namespace at position {} of {}", self.position, self.expression)),
+            }
+        }
+        else {
+            0
+        };
+
+        let local_name = &rest[uri_length..];
+        let mut end = local_name.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '.' || c == ':')).unwrap_or(local_name.len());
+
+        if local_name[end..].starts_with('*') && (local_name[..end].ends_with(':') || (end == 0 && uri_length > 0)) {
+            end += 1;
+        }
+
+        if end == 0 {
+            return Err(format!("Expected a name at position {} of {}", self.position, self.expression));
+        }
+
+        self.position += uri_length + end;
+        NameTest::parse(&rest[..uri_length + end], self.namespaces)
+    }
+
+    //Reads a quoted string or a number to compare against
+    fn literal(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+
+        let rest = self.rest();
+        match rest.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => {
+                match rest[1..].find(quote) {
+                    Some(end) => {
+                        self.position += end + 2;
+                        Ok(rest[1..end + 1].to_owned())
+                    },
+                    None => Err(format!("Unterminated string in {}", self.expression)),
+                }
+            },
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let end = rest[1..].find(|c: char| !(c.is_ascii_digit() || c == '.')).map_or(rest.len(), |end| end + 1);
+                self.position += end;
+                Ok(rest[..end].to_owned())
+            },
+            _ => Err(format!("Expected a string or number at position {} of {}", self.position, self.expression)),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        if self.eat("@") {
+            Ok(Operand::Attribute(self.name()?))
+        }
+        else if self.eat("text()") {
+            Ok(Operand::Text)
+        }
+        else if self.eat(".") {
+            Ok(Operand::SelfNode)
+        }
+        else {
+            Ok(Operand::Child(self.name()?))
+        }
+    }
+
+    fn predicate(&mut self) -> Result<Predicate, String> {
+        self.skip_whitespace();
+
+        let predicate = if self.rest().starts_with(|c: char| c.is_ascii_digit()) {
+            match self.literal()?.parse::<usize>() {
+                Ok(position) if position > 0 => Predicate::Position(position),
+                _ => return Err(format!("Expected a position of at least 1 in {}", self.expression)),
+            }
+        }
+        else if self.eat("last()") {
+            Predicate::Last
+        }
+        else {
+            let operand = self.operand()?;
+
+            if self.eat("!=") {
+                Predicate::NotEquals(operand, self.literal()?)
+            }
+            else if self.eat("=") {
+                Predicate::Equals(operand, self.literal()?)
+            }
+            else {
+                Predicate::Exists(operand)
+            }
+        };
+
+        self.expect("]")?;
+
+        Ok(predicate)
+    }
+
+    fn step(&mut self, axis: Axis) -> Result<Step, String> {
+        self.skip_whitespace();
+
+        let (axis, test) = if self.eat("..") {
+            (Axis::Parent, NodeTest::Node)
+        }
+        else if self.eat(".") {
+            (Axis::SelfNode, NodeTest::Node)
+        }
+        else if self.eat("@") {
+            if self.rest().starts_with("*:") {
+                (Axis::Attribute, NodeTest::Name(self.name()?))
+            }
+            else if self.eat("*") {
+                (Axis::Attribute, NodeTest::Wildcard)
+            }
+            else {
+                (Axis::Attribute, NodeTest::Name(self.name()?))
+            }
+        }
+        else if self.eat("text()") {
+            (axis, NodeTest::Text)
+        }
+        else if self.eat("comment()") {
+            (axis, NodeTest::Comment)
+        }
+        else if self.eat("node()") {
+            (axis, NodeTest::Node)
+        }
+        else if self.rest().starts_with("*:") {
+            (axis, NodeTest::Name(self.name()?))
+        }
+        else if self.eat("*") {
+            (axis, NodeTest::Wildcard)
+        }
+        else {
+            (axis, NodeTest::Name(self.name()?))
+        };
+
+        let mut predicates: Vec<Predicate> = Vec::new();
+        while self.eat("[") {
+            predicates.push(self.predicate()?);
+        }
+
+        Ok(Step {
+            axis,
+            test,
+            predicates,
+        })
+    }
+}
+
+impl XPath {
+    //Compiles an expression such as '/CATALOG/CD[@id="1"]/TITLE' or '//game[platform="PC"][1]'
+    pub fn parse(expression: &str) -> Result<XPath, String> {
+        XPath::parse_with_namespaces(expression, &NamespaceMap::new())
+    }
+
+    //Compiles an expression whose names may use the prefixes of a namespace map, such as '//atom:entry/atom:title',
+    //or be written with their namespace URI, such as '//{http://www.w3.org/2005/Atom}entry'
+    pub fn parse_with_namespaces(expression: &str, namespaces: &NamespaceMap) -> Result<XPath, String> {
+        let mut reader = ExpressionReader {
+            expression,
+            position: 0,
+            namespaces,
+        };
+
+        let mut steps: Vec<Step> = Vec::new();
+        reader.skip_whitespace();
+        let absolute = reader.rest().starts_with('/');
+
+        loop {
+            //'//' selects from every node below, so is read as an extra step
+            if reader.eat("//") {
+                steps.push(Step { axis: Axis::DescendantOrSelf, test: NodeTest::Node, predicates: Vec::new() });
+            }
+            else if !reader.eat("/") && !steps.is_empty() {
+                break;
+            }
+
+            reader.skip_whitespace();
+            if reader.rest().is_empty() {
+                //A lone '/' selects the document itself
+                if steps.is_empty() && absolute {
+                    break;
+                }
+                return Err(format!("Expected a step at the end of {}", expression));
+            }
+
+            steps.push(reader.step(Axis::Child)?);
+        }
+
+        reader.skip_whitespace();
+        if !reader.rest().is_empty() {
+            return Err(format!("Unexpected '{}' in {}", reader.rest(), expression));
+        }
+
+        Ok(XPath {
+            absolute,
+            steps,
+        })
+    }
+
+    //Finds the items selected by the expression, starting from 'context' unless it is absolute
+    pub fn evaluate(&self, document: &XMLDocument, context: NodeId) -> Vec<XPathItem> {
+        let mut items: Vec<XPathItem> = vec![XPathItem::Node(if self.absolute { document.root() } else { context })];
+
+        for step in &self.steps {
+            let mut next_items: Vec<XPathItem> = Vec::new();
+            let mut seen: HashSet<(NodeId, u8, String)> = HashSet::new();
+
+            for item in &items {
+                //Only elements have anything below them
+                let node = match *item {
+                    XPathItem::Node(node) => node,
+                    _ => continue,
+                };
+
+                for selected in select_step(document, node, step) {
+                    let key = match selected {
+                        XPathItem::Node(node) => (node, 0, String::new()),
+                        XPathItem::Attribute(node, ref name) => (node, 1, name.clone()),
+                        XPathItem::Text(node) => (node, 2, String::new()),
+                    };
+
+                    if seen.insert(key) {
+                        next_items.push(selected);
+                    }
+                }
+            }
+
+            items = next_items;
+        }
+
+        items
+    }
+}
+
+//Finds the items a single step selects from one node, applying its predicates in order
+fn select_step(document: &XMLDocument, node: NodeId, step: &Step) -> Vec<XPathItem> {
+    let candidates: Vec<NodeId> = match step.axis {
+        Axis::Child => document.children(node).to_vec(),
+        Axis::DescendantOrSelf => {
+            let mut nodes = vec![node];
+            nodes.extend(document.descendants(node));
+            nodes
+        },
+        Axis::SelfNode => vec![node],
+        Axis::Parent => document.parent(node).into_iter().collect(),
+        Axis::Attribute => {
+            return document.attributes(node).iter()
+                .filter(|&&(name, _)| match step.test {
+                    NodeTest::Name(ref test) => test.matches_attribute(document, node, name),
+                    _ => true,
+                })
+                .map(|&(name, _)| XPathItem::Attribute(node, name.to_owned()))
+                .collect();
+        },
+    };
+
+    let mut items: Vec<XPathItem> = match step.test {
+        NodeTest::Name(ref test) => candidates.into_iter().filter(|&child| test.matches(document, child)).map(XPathItem::Node).collect(),
+        NodeTest::Wildcard => candidates.into_iter().filter(|&child| document.is_element(child)).map(XPathItem::Node).collect(),
+        NodeTest::Comment => candidates.into_iter().filter(|&child| document.kind(child) == NodeKind::Comment).map(XPathItem::Node).collect(),
+        NodeTest::Node => candidates.into_iter().map(XPathItem::Node).collect(),
+        NodeTest::Text => candidates.into_iter().filter(|&child| document.kind(child) == NodeKind::Text).map(XPathItem::Text).collect(),
+    };
+
+    for predicate in &step.predicates {
+        let count = items.len();
+
+        items = items.into_iter().enumerate()
+            .filter(|&(index, ref item)| match *predicate {
+                Predicate::Position(position) => index + 1 == position,
+                Predicate::Last => index + 1 == count,
+                Predicate::Exists(ref operand) => !operand_values(document, item, operand).is_empty(),
+                Predicate::Equals(ref operand, ref value) => operand_values(document, item, operand).iter().any(|v| v == value),
+                Predicate::NotEquals(ref operand, ref value) => operand_values(document, item, operand).iter().any(|v| v != value),
+            })
+            .map(|(_, item)| item)
+            .collect();
+    }
+
+    items
+}
+
+//Finds the values of an operand of a predicate, which may be several if it names repeated children
+fn operand_values(document: &XMLDocument, item: &XPathItem, operand: &Operand) -> Vec<String> {
+    let node = match *item {
+        XPathItem::Node(node) => node,
+        _ => return match *operand {
+            Operand::SelfNode | Operand::Text => vec![item_string(document, item)],
+            _ => Vec::new(),
+        },
+    };
+
+    match *operand {
+        Operand::Attribute(ref test) => document.attributes(node).iter()
+            .filter(|&&(name, _)| test.matches_attribute(document, node, name))
+            .map(|&(_, value)| value.to_owned())
+            .collect(),
+        Operand::Child(ref test) => document.children(node).iter()
+            .filter(|&&child| test.matches(document, child))
+            .map(|&child| item_string(document, &XPathItem::Node(child)))
+            .collect(),
+        Operand::Text => document.children(node).iter()
+            .filter(|&&child| document.kind(child) == NodeKind::Text)
+            .map(|&child| document.data(child).to_owned())
+            .collect(),
+        Operand::SelfNode => vec![item_string(document, item)],
+    }
+}
+
+//The text of a selected item. Elements give all of the text inside them in document order, and comments their own text.
+pub fn item_string(document: &XMLDocument, item: &XPathItem) -> String {
+    match *item {
+        XPathItem::Node(node) if !document.is_element(node) => document.data(node).to_owned(),
+        XPathItem::Node(node) => document.descendants(node)
+            .filter(|&descendant| document.kind(descendant) == NodeKind::Text)
+            .map(|descendant| document.data(descendant))
+            .collect(),
+        XPathItem::Attribute(node, ref name) => document.attribute(node, name).unwrap_or("").to_owned(),
+        XPathItem::Text(node) => document.data(node).to_owned(),
+    }
+}
+
+//Evaluates an XPath expression against a document, starting from 'context' for relative expressions
+pub fn xpath_query(document: &XMLDocument, context: NodeId, expression: &str) -> Result<Vec<XPathItem>, String> {
+    Ok(XPath::parse(expression)?.evaluate(document, context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {lexer, parser};
+
+    const CATALOG: &str = "<CATALOG><CD id=\"1\"><TITLE>Empire</TITLE><YEAR>1985</YEAR></CD><CD id=\"2\"><TITLE>Hide</TITLE><YEAR>1988</YEAR><!--live--></CD></CATALOG>";
+
+    //The text of everything an expression selects from the catalog
+    fn query(expression: &str) -> Result<Vec<String>, String> {
+        let document = parser(&lexer(CATALOG.to_owned()).unwrap()).unwrap();
+        let items = xpath_query(&document, document.root(), expression)?;
+
+        Ok(items.iter().map(|item| item_string(&document, item)).collect())
+    }
+
+    #[test]
+    fn selects_by_absolute_and_descendant_paths() {
+        assert_eq!(query("/CATALOG/CD/TITLE").unwrap(), vec!["Empire", "Hide"]);
+        assert_eq!(query("//YEAR/text()").unwrap(), vec!["1985", "1988"]);
+        assert_eq!(query("//comment()").unwrap(), vec!["live"]);
+    }
+
+    #[test]
+    fn filters_with_predicates() {
+        assert_eq!(query("//CD[2]/TITLE").unwrap(), vec!["Hide"]);
+        assert_eq!(query("//CD[last()]/@id").unwrap(), vec!["2"]);
+        assert_eq!(query("//CD[@id='1']/YEAR").unwrap(), vec!["1985"]);
+        assert_eq!(query("//CD[YEAR!='1985']/TITLE").unwrap(), vec!["Hide"]);
+        assert_eq!(query("//TITLE[.='Empire']/../YEAR").unwrap(), vec!["1985"]);
+    }
+
+    #[test]
+    fn an_element_gives_all_of_its_text() {
+        assert_eq!(query("/CATALOG/CD[1]").unwrap(), vec!["Empire1985"]);
+    }
+
+    #[test]
+    fn unsupported_expressions_are_an_error() {
+        assert!(query("//CD[").is_err());
+        assert!(query("count(//CD)").is_err());
+    }
+}
@@ -0,0 +1,1589 @@
+//
+//  Description  :    Validation of parsed XML trees against a subset of XML Schema (XSD)
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use std::collections::HashMap;
+use document::{XMLDocument, NodeId, NodeKind};
+use namespaces::{split_name, declared_prefix, XML_NAMESPACE};
+use pattern::Pattern;
+use validation::{ValidationError, ContentProblem, sort_by_position, match_repeated, check_content_model};
+
+pub const XSD_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema";
+pub const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+//A namespace URI, empty for none, and a local name
+type QName = (String, String);
+
+//The local name of a qualified name, for messages
+fn display_name(name: &QName) -> &str {
+    &name.1
+}
+
+//A restriction on the values of a simple type
+#[derive(Debug)]
+#[derive(Clone)]
+enum Facet {
+    Length(usize),
+    MinLength(usize),
+    MaxLength(usize),
+    //A value must match at least one of the patterns
+    Pattern(Vec<Pattern>),
+    Enumeration(Vec<String>),
+    MinInclusive(String),
+    MaxInclusive(String),
+    MinExclusive(String),
+    MaxExclusive(String),
+    TotalDigits(usize),
+    FractionDigits(usize),
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum SimpleType {
+    //A type of the XSD namespace, such as 'integer'
+    BuiltIn(String),
+    Named(QName),
+    Restriction(Box<SimpleType>, Vec<Facet>),
+    //Values separated by whitespace
+    List(Box<SimpleType>),
+    Union(Vec<SimpleType>),
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum TypeDefinition {
+    Simple(SimpleType),
+    Complex(Box<ComplexType>),
+    //A named type, which may be simple or complex
+    Named(QName),
+    //Anything at all, for elements declared without a type
+    AnyType,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+struct ElementDeclaration {
+    name: QName,
+    definition: TypeDefinition,
+    nillable: bool,
+    fixed: Option<String>,
+}
+
+//Which namespaces a wildcard accepts
+#[derive(Debug)]
+#[derive(Clone)]
+enum NamespaceConstraint {
+    Any,
+    //Any namespace other than the target namespace, and not no namespace
+    Other(String),
+    List(Vec<String>),
+}
+
+impl NamespaceConstraint {
+    fn allows(&self, namespace: &str) -> bool {
+        match *self {
+            NamespaceConstraint::Any => true,
+            NamespaceConstraint::Other(ref target) => namespace != target && !namespace.is_empty(),
+            NamespaceConstraint::List(ref namespaces) => namespaces.iter().any(|allowed| allowed == namespace),
+        }
+    }
+}
+
+//How the contents of elements matched by a wildcard are checked
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+enum ProcessContents {
+    Strict,
+    Lax,
+    Skip,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum Term {
+    Element(ElementDeclaration),
+    ElementRef(QName),
+    Sequence(Vec<Particle>),
+    Choice(Vec<Particle>),
+    //Each member at most once, in any order
+    All(Vec<Particle>),
+    GroupRef(QName),
+    Any(NamespaceConstraint, ProcessContents),
+}
+
+//A part of a content model and how many times it may occur, where a maximum of 'None' is unbounded
+#[derive(Debug)]
+#[derive(Clone)]
+struct Particle {
+    term: Term,
+    min_occurs: usize,
+    max_occurs: Option<usize>,
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+enum AttributeUse {
+    Required,
+    Optional,
+    Prohibited,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+struct AttributeDeclaration {
+    name: QName,
+    simple_type: SimpleType,
+    attribute_use: AttributeUse,
+    fixed: Option<String>,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+enum AttributeItem {
+    Attribute(AttributeDeclaration),
+    Ref(QName, AttributeUse),
+    GroupRef(QName),
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+struct ComplexType {
+    mixed: bool,
+    content: Option<Particle>,
+    //The type of the text of an element with simple content and attributes
+    simple_content: Option<SimpleType>,
+    attributes: Vec<AttributeItem>,
+    any_attribute: Option<NamespaceConstraint>,
+    //A type this one extends, whose content comes before its own, or restricts
+    base: Option<QName>,
+    extends: bool,
+}
+
+impl ComplexType {
+    fn new() -> ComplexType {
+        ComplexType {
+            mixed: false,
+            content: None,
+            simple_content: None,
+            attributes: Vec::new(),
+            any_attribute: None,
+            base: None,
+            extends: false,
+        }
+    }
+}
+
+//A compiled schema, ready to check documents against
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Schema {
+    target_namespace: String,
+    elements: HashMap<QName, ElementDeclaration>,
+    attributes: HashMap<QName, AttributeDeclaration>,
+    complex_types: HashMap<QName, ComplexType>,
+    simple_types: HashMap<QName, SimpleType>,
+    groups: HashMap<QName, Particle>,
+    attribute_groups: HashMap<QName, Vec<AttributeItem>>,
+}
+
+//Reads the declarations of a schema document
+struct SchemaReader<'a> {
+    document: &'a XMLDocument,
+    target_namespace: String,
+    qualified_elements: bool,
+    qualified_attributes: bool,
+}
+
+fn occurs(text: &str) -> Result<Option<usize>, String> {
+    if text.trim() == "unbounded" {
+        return Ok(None);
+    }
+
+    match text.trim().parse::<usize>() {
+        Ok(count) => Ok(Some(count)),
+        Err(_) => Err(format!("Invalid number of occurrences: {}", text)),
+    }
+}
+
+fn facet_number(node_name: &str, value: &str) -> Result<usize, String> {
+    match value.trim().parse::<usize>() {
+        Ok(number) => Ok(number),
+        Err(_) => Err(format!("Invalid value {} for facet {}", value, node_name)),
+    }
+}
+
+impl<'a> SchemaReader<'a> {
+    fn is_xsd(&self, node: NodeId, local_name: &str) -> bool {
+        self.document.namespace(node) == Some(XSD_NAMESPACE) && self.document.local_name(node) == local_name
+    }
+
+    //The schema elements inside a node, without annotations
+    fn xsd_children(&self, node: NodeId) -> Vec<NodeId> {
+        self.document.child_elements(node).into_iter()
+            .filter(|&child| self.document.namespace(child) == Some(XSD_NAMESPACE) && self.document.local_name(child) != "annotation")
+            .collect()
+    }
+
+    fn unsupported(&self, node: NodeId) -> String {
+        match self.document.position(node) {
+            Some(position) => format!("Unsupported schema element {} at {}", self.document.name(node), position),
+            None => format!("Unsupported schema element {}", self.document.name(node)),
+        }
+    }
+
+    //Resolves a name such as 'xs:string' or 'tns:Order' written in an attribute of 'node'
+    fn qname(&self, node: NodeId, name: &str) -> Result<QName, String> {
+        let (prefix, local_name) = split_name(name.trim());
+
+        match self.document.lookup_namespace(node, prefix) {
+            Some(uri) => Ok((uri.to_owned(), local_name.to_owned())),
+            None if prefix.is_empty() => Ok((String::new(), local_name.to_owned())),
+            None => Err(format!("Unknown namespace prefix in {}", name)),
+        }
+    }
+
+    fn required_attribute(&self, node: NodeId, name: &str) -> Result<&'a str, String> {
+        match self.document.attribute(node, name) {
+            Some(value) => Ok(value),
+            None => Err(format!("Missing '{}' on {}", name, self.document.name(node))),
+        }
+    }
+
+    //A reference to a type, where types of the XSD namespace are built in
+    fn type_reference(&self, node: NodeId, name: &str) -> Result<TypeDefinition, String> {
+        let name = self.qname(node, name)?;
+
+        if name.0 == XSD_NAMESPACE {
+            if name.1 == "anyType" {
+                Ok(TypeDefinition::AnyType)
+            }
+            else {
+                Ok(TypeDefinition::Simple(SimpleType::BuiltIn(name.1)))
+            }
+        }
+        else {
+            Ok(TypeDefinition::Named(name))
+        }
+    }
+
+    fn simple_type_reference(&self, node: NodeId, name: &str) -> Result<SimpleType, String> {
+        let name = self.qname(node, name)?;
+
+        if name.0 == XSD_NAMESPACE {
+            Ok(SimpleType::BuiltIn(name.1))
+        }
+        else {
+            Ok(SimpleType::Named(name))
+        }
+    }
+
+    fn particle(&self, node: NodeId, term: Term) -> Result<Particle, String> {
+        Ok(Particle {
+            term,
+            min_occurs: occurs(self.document.attribute(node, "minOccurs").unwrap_or("1"))?.unwrap_or(0),
+            max_occurs: occurs(self.document.attribute(node, "maxOccurs").unwrap_or("1"))?,
+        })
+    }
+
+    fn element(&self, node: NodeId, global: bool) -> Result<ElementDeclaration, String> {
+        let local_name = self.required_attribute(node, "name")?;
+
+        let qualified = global || match self.document.attribute(node, "form") {
+            Some(form) => form == "qualified",
+            None => self.qualified_elements,
+        };
+        let namespace = if qualified { self.target_namespace.clone() } else { String::new() };
+
+        let mut definition = match self.document.attribute(node, "type") {
+            Some(name) => Some(self.type_reference(node, name)?),
+            None => None,
+        };
+
+        for child in self.xsd_children(node) {
+            if self.is_xsd(child, "complexType") {
+                definition = Some(TypeDefinition::Complex(Box::new(self.complex_type(child)?)));
+            }
+            else if self.is_xsd(child, "simpleType") {
+                definition = Some(TypeDefinition::Simple(self.simple_type(child)?));
+            }
+            //Identity constraints are not checked
+            else if !self.is_xsd(child, "key") && !self.is_xsd(child, "keyref") && !self.is_xsd(child, "unique") {
+                return Err(self.unsupported(child));
+            }
+        }
+
+        Ok(ElementDeclaration {
+            name: (namespace, local_name.to_owned()),
+            definition: definition.unwrap_or(TypeDefinition::AnyType),
+            nillable: self.document.attribute(node, "nillable") == Some("true"),
+            fixed: self.document.attribute(node, "fixed").map(|value| value.to_owned()),
+        })
+    }
+
+    //Reads a particle inside a content model
+    fn content_particle(&self, node: NodeId) -> Result<Particle, String> {
+        let document = self.document;
+
+        let term = if self.is_xsd(node, "element") {
+            match document.attribute(node, "ref") {
+                Some(name) => Term::ElementRef(self.qname(node, name)?),
+                None => Term::Element(self.element(node, false)?),
+            }
+        }
+        else if self.is_xsd(node, "sequence") || self.is_xsd(node, "choice") || self.is_xsd(node, "all") {
+            let mut particles: Vec<Particle> = Vec::new();
+            for child in self.xsd_children(node) {
+                particles.push(self.content_particle(child)?);
+            }
+
+            match document.local_name(node) {
+                "sequence" => Term::Sequence(particles),
+                "choice" => Term::Choice(particles),
+                _ => Term::All(particles),
+            }
+        }
+        else if self.is_xsd(node, "group") {
+            Term::GroupRef(self.qname(node, self.required_attribute(node, "ref")?)?)
+        }
+        else if self.is_xsd(node, "any") {
+            Term::Any(self.namespace_constraint(node)?, self.process_contents(node)?)
+        }
+        else {
+            return Err(self.unsupported(node));
+        };
+
+        self.particle(node, term)
+    }
+
+    fn namespace_constraint(&self, node: NodeId) -> Result<NamespaceConstraint, String> {
+        let namespaces = self.document.attribute(node, "namespace").unwrap_or("##any").trim();
+
+        Ok(match namespaces {
+            "##any" => NamespaceConstraint::Any,
+            "##other" => NamespaceConstraint::Other(self.target_namespace.clone()),
+            namespaces => NamespaceConstraint::List(namespaces.split_whitespace()
+                .map(|namespace| match namespace {
+                    "##targetNamespace" => self.target_namespace.clone(),
+                    "##local" => String::new(),
+                    uri => uri.to_owned(),
+                })
+                .collect()),
+        })
+    }
+
+    fn process_contents(&self, node: NodeId) -> Result<ProcessContents, String> {
+        match self.document.attribute(node, "processContents").unwrap_or("strict") {
+            "strict" => Ok(ProcessContents::Strict),
+            "lax" => Ok(ProcessContents::Lax),
+            "skip" => Ok(ProcessContents::Skip),
+            other => Err(format!("Invalid processContents: {}", other)),
+        }
+    }
+
+    fn attribute(&self, node: NodeId, global: bool) -> Result<AttributeItem, String> {
+        let document = self.document;
+
+        let attribute_use = match document.attribute(node, "use").unwrap_or("optional") {
+            "required" => AttributeUse::Required,
+            "optional" => AttributeUse::Optional,
+            "prohibited" => AttributeUse::Prohibited,
+            other => return Err(format!("Invalid attribute use: {}", other)),
+        };
+
+        if let Some(name) = document.attribute(node, "ref") {
+            return Ok(AttributeItem::Ref(self.qname(node, name)?, attribute_use));
+        }
+
+        let local_name = self.required_attribute(node, "name")?;
+        let qualified = global || match document.attribute(node, "form") {
+            Some(form) => form == "qualified",
+            None => self.qualified_attributes,
+        };
+        let namespace = if qualified { self.target_namespace.clone() } else { String::new() };
+
+        let mut simple_type = match document.attribute(node, "type") {
+            Some(name) => self.simple_type_reference(node, name)?,
+            None => SimpleType::BuiltIn("anySimpleType".to_owned()),
+        };
+
+        for child in self.xsd_children(node) {
+            if self.is_xsd(child, "simpleType") {
+                simple_type = self.simple_type(child)?;
+            }
+            else {
+                return Err(self.unsupported(child));
+            }
+        }
+
+        Ok(AttributeItem::Attribute(AttributeDeclaration {
+            name: (namespace, local_name.to_owned()),
+            simple_type,
+            attribute_use,
+            fixed: document.attribute(node, "fixed").map(|value| value.to_owned()),
+        }))
+    }
+
+    //Reads the attributes declared by a type or attribute group, returning false for anything else
+    fn attribute_item(&self, node: NodeId, attributes: &mut Vec<AttributeItem>, any_attribute: &mut Option<NamespaceConstraint>) -> Result<bool, String> {
+        if self.is_xsd(node, "attribute") {
+            attributes.push(self.attribute(node, false)?);
+        }
+        else if self.is_xsd(node, "attributeGroup") {
+            attributes.push(AttributeItem::GroupRef(self.qname(node, self.required_attribute(node, "ref")?)?));
+        }
+        else if self.is_xsd(node, "anyAttribute") {
+            *any_attribute = Some(self.namespace_constraint(node)?);
+        }
+        else {
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    fn complex_type(&self, node: NodeId) -> Result<ComplexType, String> {
+        let mut complex_type = ComplexType::new();
+        complex_type.mixed = self.document.attribute(node, "mixed") == Some("true");
+
+        for child in self.xsd_children(node) {
+            if self.attribute_item(child, &mut complex_type.attributes, &mut complex_type.any_attribute)? {
+                continue;
+            }
+
+            if self.is_xsd(child, "simpleContent") || self.is_xsd(child, "complexContent") {
+                let simple = self.is_xsd(child, "simpleContent");
+
+                if self.document.attribute(child, "mixed") == Some("true") {
+                    complex_type.mixed = true;
+                }
+
+                let derivation = match self.xsd_children(child).into_iter().next() {
+                    Some(derivation) if self.is_xsd(derivation, "extension") || self.is_xsd(derivation, "restriction") => derivation,
+                    Some(other) => return Err(self.unsupported(other)),
+                    None => return Err(format!("Missing extension or restriction in {}", self.document.name(child))),
+                };
+
+                let base = self.qname(derivation, self.required_attribute(derivation, "base")?)?;
+                let mut facets: Vec<Facet> = Vec::new();
+
+                for item in self.xsd_children(derivation) {
+                    if self.attribute_item(item, &mut complex_type.attributes, &mut complex_type.any_attribute)? {
+                        continue;
+                    }
+
+                    if simple {
+                        if !self.facet(item, &mut facets)? {
+                            return Err(self.unsupported(item));
+                        }
+                    }
+                    else {
+                        complex_type.content = Some(self.content_particle(item)?);
+                    }
+                }
+
+                if simple {
+                    let base_type = if base.0 == XSD_NAMESPACE { SimpleType::BuiltIn(base.1.clone()) } else { SimpleType::Named(base.clone()) };
+
+                    complex_type.simple_content = Some(if !facets.is_empty() {
+                        SimpleType::Restriction(Box::new(base_type), facets)
+                    }
+                    else {
+                        base_type
+                    });
+                }
+
+                //Types derived from the built in 'anyType' have nothing to inherit
+                if !(base.0 == XSD_NAMESPACE && base.1 == "anyType") {
+                    complex_type.base = Some(base);
+                    complex_type.extends = self.is_xsd(derivation, "extension");
+                }
+            }
+            else {
+                complex_type.content = Some(self.content_particle(child)?);
+            }
+        }
+
+        Ok(complex_type)
+    }
+
+    //Reads a facet of a restriction, returning false if the node is not one
+    fn facet(&self, node: NodeId, facets: &mut Vec<Facet>) -> Result<bool, String> {
+        let document = self.document;
+        let name = document.local_name(node);
+
+        if document.namespace(node) != Some(XSD_NAMESPACE) {
+            return Ok(false);
+        }
+
+        let value = match document.attribute(node, "value") {
+            Some(value) => value,
+            None if ["length", "minLength", "maxLength", "pattern", "enumeration", "minInclusive", "maxInclusive",
+                "minExclusive", "maxExclusive", "totalDigits", "fractionDigits", "whiteSpace"].contains(&name) => {
+                return Err(format!("Missing 'value' on {}", document.name(node)));
+            },
+            None => return Ok(false),
+        };
+
+        match name {
+            "length" => facets.push(Facet::Length(facet_number(name, value)?)),
+            "minLength" => facets.push(Facet::MinLength(facet_number(name, value)?)),
+            "maxLength" => facets.push(Facet::MaxLength(facet_number(name, value)?)),
+            "totalDigits" => facets.push(Facet::TotalDigits(facet_number(name, value)?)),
+            "fractionDigits" => facets.push(Facet::FractionDigits(facet_number(name, value)?)),
+            "minInclusive" => facets.push(Facet::MinInclusive(value.trim().to_owned())),
+            "maxInclusive" => facets.push(Facet::MaxInclusive(value.trim().to_owned())),
+            "minExclusive" => facets.push(Facet::MinExclusive(value.trim().to_owned())),
+            "maxExclusive" => facets.push(Facet::MaxExclusive(value.trim().to_owned())),
+            "pattern" => {
+                let pattern = Pattern::parse(value)?;
+
+                //Patterns of the same restriction are alternatives
+                for facet in facets.iter_mut() {
+                    if let Facet::Pattern(ref mut patterns) = *facet {
+                        patterns.push(pattern);
+                        return Ok(true);
+                    }
+                }
+                facets.push(Facet::Pattern(vec![pattern]));
+            },
+            "enumeration" => {
+                for facet in facets.iter_mut() {
+                    if let Facet::Enumeration(ref mut values) = *facet {
+                        values.push(value.to_owned());
+                        return Ok(true);
+                    }
+                }
+                facets.push(Facet::Enumeration(vec![value.to_owned()]));
+            },
+            //Values are compared with their surrounding whitespace removed
+            "whiteSpace" => {},
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    fn simple_type(&self, node: NodeId) -> Result<SimpleType, String> {
+        let document = self.document;
+
+        let definition = match self.xsd_children(node).into_iter().next() {
+            Some(definition) => definition,
+            None => return Err(format!("Missing restriction, list or union in {}", document.name(node))),
+        };
+
+        if self.is_xsd(definition, "restriction") {
+            let mut base = match document.attribute(definition, "base") {
+                Some(name) => Some(self.simple_type_reference(definition, name)?),
+                None => None,
+            };
+            let mut facets: Vec<Facet> = Vec::new();
+
+            for child in self.xsd_children(definition) {
+                if self.is_xsd(child, "simpleType") {
+                    base = Some(self.simple_type(child)?);
+                }
+                else if !self.facet(child, &mut facets)? {
+                    return Err(self.unsupported(child));
+                }
+            }
+
+            match base {
+                Some(base) => Ok(SimpleType::Restriction(Box::new(base), facets)),
+                None => Err(format!("Missing base type of {}", document.name(definition))),
+            }
+        }
+        else if self.is_xsd(definition, "list") {
+            let item_type = match document.attribute(definition, "itemType") {
+                Some(name) => self.simple_type_reference(definition, name)?,
+                None => match self.xsd_children(definition).into_iter().next() {
+                    Some(child) if self.is_xsd(child, "simpleType") => self.simple_type(child)?,
+                    _ => return Err(format!("Missing item type of {}", document.name(definition))),
+                },
+            };
+
+            Ok(SimpleType::List(Box::new(item_type)))
+        }
+        else if self.is_xsd(definition, "union") {
+            let mut members: Vec<SimpleType> = Vec::new();
+
+            for name in document.attribute(definition, "memberTypes").unwrap_or("").split_whitespace() {
+                members.push(self.simple_type_reference(definition, name)?);
+            }
+            for child in self.xsd_children(definition) {
+                members.push(self.simple_type(child)?);
+            }
+
+            Ok(SimpleType::Union(members))
+        }
+        else {
+            Err(self.unsupported(definition))
+        }
+    }
+}
+
+//Checks that some text is a valid name, optionally without a prefix
+fn is_name(value: &str, allow_colon: bool) -> bool {
+    let mut chars = value.chars();
+
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' || (allow_colon && c == ':') => {},
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_alphanumeric() || c == '.' || c == '-' || c == '_' || (allow_colon && c == ':'))
+}
+
+fn is_integer(value: &str) -> bool {
+    let digits = value.trim_start_matches(['+', '-']);
+    value.len() - digits.len() <= 1 && !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_decimal(value: &str) -> bool {
+    let digits = value.trim_start_matches(['+', '-']);
+    let mut parts = digits.splitn(2, '.');
+    let whole = parts.next().unwrap_or("");
+    let fraction = parts.next().unwrap_or("");
+
+    value.len() - digits.len() <= 1 && whole.len() + fraction.len() > 0 &&
+        whole.chars().all(|c| c.is_ascii_digit()) && fraction.chars().all(|c| c.is_ascii_digit())
+}
+
+//Reads a number of exactly 'length' digits
+fn fixed_digits(text: &str, length: usize) -> Option<u32> {
+    if text.len() == length && text.chars().all(|c| c.is_ascii_digit()) {
+        text.parse::<u32>().ok()
+    }
+    else {
+        None
+    }
+}
+
+//Removes a time zone such as 'Z' or '+01:00' from the end of a date or time
+fn without_time_zone(value: &str) -> Option<&str> {
+    if let Some(value) = value.strip_suffix('Z') {
+        return Some(value);
+    }
+
+    if value.len() > 6 && value.is_char_boundary(value.len() - 6) {
+        let zone = &value[value.len() - 6..];
+        let bytes = zone.as_bytes();
+
+        if (bytes[0] == b'+' || bytes[0] == b'-') && bytes[3] == b':' {
+            return match (fixed_digits(&zone[1..3], 2), fixed_digits(&zone[4..], 2)) {
+                (Some(hours), Some(minutes)) if hours <= 14 && minutes < 60 => Some(&value[..value.len() - 6]),
+                _ => None,
+            };
+        }
+    }
+
+    Some(value)
+}
+
+//Checks a date written 'YYYY-MM-DD'
+fn is_date_part(value: &str) -> bool {
+    let negative = value.starts_with('-');
+    let value = if negative { &value[1..] } else { value };
+    let parts: Vec<&str> = value.split('-').collect();
+
+    if parts.len() != 3 || parts[0].len() < 4 {
+        return false;
+    }
+
+    let (year, month, day) = match (fixed_digits(parts[0], parts[0].len()), fixed_digits(parts[1], 2), fixed_digits(parts[2], 2)) {
+        (Some(year), Some(month), Some(day)) => (year, month, day),
+        _ => return false,
+    };
+
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+
+    day >= 1 && day <= days_in_month
+}
+
+//Checks a time written 'hh:mm:ss' with optional fractions of a second
+fn is_time_part(value: &str) -> bool {
+    let parts: Vec<&str> = value.split(':').collect();
+
+    if parts.len() != 3 {
+        return false;
+    }
+
+    let mut seconds = parts[2].splitn(2, '.');
+    let whole_seconds = seconds.next().unwrap_or("");
+    let fraction_valid = match seconds.next() {
+        Some(fraction) => !fraction.is_empty() && fraction.chars().all(|c| c.is_ascii_digit()),
+        None => true,
+    };
+
+    match (fixed_digits(parts[0], 2), fixed_digits(parts[1], 2), fixed_digits(whole_seconds, 2)) {
+        (Some(24), Some(0), Some(0)) => fraction_valid,
+        (Some(hours), Some(minutes), Some(seconds)) => hours < 24 && minutes < 60 && seconds < 60 && fraction_valid,
+        _ => false,
+    }
+}
+
+//Checks whether a value is in the lexical space of a built in type, with whitespace already removed from its ends.
+//Returns an error for types which are not supported.
+pub fn check_built_in(type_name: &str, value: &str) -> Result<bool, String> {
+    let integer_range = |min: i128, max: i128| is_integer(value) && value.parse::<i128>().map(|number| number >= min && number <= max).unwrap_or(false);
+
+    Ok(match type_name {
+        "anySimpleType" | "string" | "normalizedString" | "anyURI" => true,
+        "token" => !value.contains("  ") && !value.contains(['\t', '\n', '\r']),
+        "boolean" => ["true", "false", "1", "0"].contains(&value),
+        "decimal" => is_decimal(value),
+        "float" | "double" => ["INF", "-INF", "NaN"].contains(&value) ||
+            (value.parse::<f64>().is_ok() && value.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c))),
+        "integer" => is_integer(value),
+        "long" => integer_range(i64::MIN as i128, i64::MAX as i128),
+        "int" => integer_range(i32::MIN as i128, i32::MAX as i128),
+        "short" => integer_range(i16::MIN as i128, i16::MAX as i128),
+        "byte" => integer_range(i8::MIN as i128, i8::MAX as i128),
+        "nonNegativeInteger" => is_integer(value) && !value.starts_with('-') || value == "-0",
+        "positiveInteger" => is_integer(value) && !value.starts_with('-') && !value.trim_start_matches('+').trim_start_matches('0').is_empty(),
+        "nonPositiveInteger" => is_integer(value) && (value.starts_with('-') || value.trim_start_matches('+').trim_start_matches('0').is_empty()),
+        "negativeInteger" => is_integer(value) && value.starts_with('-') && !value[1..].trim_start_matches('0').is_empty(),
+        "unsignedLong" => integer_range(0, u64::MAX as i128),
+        "unsignedInt" => integer_range(0, u32::MAX as i128),
+        "unsignedShort" => integer_range(0, u16::MAX as i128),
+        "unsignedByte" => integer_range(0, u8::MAX as i128),
+        "date" => without_time_zone(value).is_some_and(is_date_part),
+        "time" => without_time_zone(value).is_some_and(is_time_part),
+        "dateTime" => without_time_zone(value).is_some_and(|value| {
+            let mut parts = value.splitn(2, 'T');
+            is_date_part(parts.next().unwrap_or("")) && parts.next().is_some_and(is_time_part)
+        }),
+        "gYear" => without_time_zone(value).is_some_and(|value| value.len() >= 4 && fixed_digits(value, value.len()).is_some()),
+        "gYearMonth" => without_time_zone(value).is_some_and(|value| is_date_part(&format!("{}-01", value))),
+        "duration" => {
+            let pattern = Pattern::parse(r"-?P([0-9]+Y)?([0-9]+M)?([0-9]+D)?(T([0-9]+H)?([0-9]+M)?([0-9]+(\.[0-9]+)?S)?)?")?;
+            pattern.matches(value) && !value.ends_with('P') && !value.ends_with('T')
+        },
+        "hexBinary" => value.len().is_multiple_of(2) && value.chars().all(|c| c.is_ascii_hexdigit()),
+        "base64Binary" => value.chars().all(|c| c.is_alphanumeric() || c == '+' || c == '/' || c == '=' || c.is_whitespace()),
+        "language" => Pattern::parse("[a-zA-Z]{1,8}(-[a-zA-Z0-9]{1,8})*")?.matches(value),
+        "Name" => is_name(value, true),
+        "NCName" | "ID" | "IDREF" | "ENTITY" => is_name(value, false),
+        "QName" => {
+            let (prefix, local_name) = split_name(value);
+            (prefix.is_empty() || is_name(prefix, false)) && is_name(local_name, false)
+        },
+        "NMTOKEN" => !value.is_empty() && value.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '-' || c == '_' || c == ':'),
+        "IDREFS" | "ENTITIES" => value.split_whitespace().count() > 0 && value.split_whitespace().all(|name| is_name(name, false)),
+        "NMTOKENS" => value.split_whitespace().count() > 0,
+        other => return Err(format!("Unsupported built in type xs:{}", other)),
+    })
+}
+
+//Compares two values of an ordered type, as numbers if both are numbers, otherwise as written, which orders dates and times
+fn compare_values(value: &str, limit: &str) -> ::std::cmp::Ordering {
+    match (value.parse::<f64>(), limit.parse::<f64>()) {
+        (Ok(value), Ok(limit)) => value.partial_cmp(&limit).unwrap_or(::std::cmp::Ordering::Equal),
+        _ => value.cmp(limit),
+    }
+}
+
+//The digits of a decimal, without leading zeros of its whole part or trailing zeros of its fraction
+fn significant_digits(value: &str) -> (usize, usize) {
+    let digits = value.trim_start_matches(['+', '-']);
+    let mut parts = digits.splitn(2, '.');
+    let whole = parts.next().unwrap_or("").trim_start_matches('0');
+    let fraction = parts.next().unwrap_or("").trim_end_matches('0');
+
+    (whole.len() + fraction.len(), fraction.len())
+}
+
+//Checks a value against a facet, describing how it breaks it. The length of a list is its number of items.
+fn check_facet_value(facet: &Facet, value: &str, length: usize) -> Result<(), String> {
+    use std::cmp::Ordering;
+
+    let valid = match *facet {
+        Facet::Length(expected) => length == expected,
+        Facet::MinLength(min) => length >= min,
+        Facet::MaxLength(max) => length <= max,
+        Facet::Pattern(ref patterns) => patterns.iter().any(|pattern| pattern.matches(value)),
+        Facet::Enumeration(ref values) => values.iter().any(|allowed| allowed == value),
+        Facet::MinInclusive(ref min) => compare_values(value, min) != Ordering::Less,
+        Facet::MaxInclusive(ref max) => compare_values(value, max) != Ordering::Greater,
+        Facet::MinExclusive(ref min) => compare_values(value, min) == Ordering::Greater,
+        Facet::MaxExclusive(ref max) => compare_values(value, max) == Ordering::Less,
+        Facet::TotalDigits(max) => significant_digits(value).0 <= max,
+        Facet::FractionDigits(max) => significant_digits(value).1 <= max,
+    };
+
+    if valid {
+        return Ok(());
+    }
+
+    Err(match *facet {
+        Facet::Length(expected) => format!("'{}' has length {}, but must have length {}", value, length, expected),
+        Facet::MinLength(min) => format!("'{}' has length {}, but must be at least {}", value, length, min),
+        Facet::MaxLength(max) => format!("'{}' has length {}, but must be at most {}", value, length, max),
+        Facet::Pattern(ref patterns) => {
+            let expressions: Vec<&str> = patterns.iter().map(|pattern| pattern.expression()).collect();
+            format!("'{}' does not match the pattern {}", value, expressions.join(" | "))
+        },
+        Facet::Enumeration(ref values) => format!("'{}' is not one of {}", value, values.join(", ")),
+        Facet::MinInclusive(ref min) => format!("'{}' is less than {}", value, min),
+        Facet::MaxInclusive(ref max) => format!("'{}' is greater than {}", value, max),
+        Facet::MinExclusive(ref min) => format!("'{}' must be greater than {}", value, min),
+        Facet::MaxExclusive(ref max) => format!("'{}' must be less than {}", value, max),
+        Facet::TotalDigits(max) => format!("'{}' has more than {} digits", value, max),
+        Facet::FractionDigits(max) => format!("'{}' has more than {} fraction digits", value, max),
+    })
+}
+
+//Checks a value against a facet given by name, such as 'maxLength' with '10', for schema languages which borrow the XSD datatypes
+pub fn check_facet(name: &str, limit: &str, value: &str) -> Result<(), String> {
+    let facet = match name {
+        "length" => Facet::Length(facet_number(name, limit)?),
+        "minLength" => Facet::MinLength(facet_number(name, limit)?),
+        "maxLength" => Facet::MaxLength(facet_number(name, limit)?),
+        "totalDigits" => Facet::TotalDigits(facet_number(name, limit)?),
+        "fractionDigits" => Facet::FractionDigits(facet_number(name, limit)?),
+        "minInclusive" => Facet::MinInclusive(limit.trim().to_owned()),
+        "maxInclusive" => Facet::MaxInclusive(limit.trim().to_owned()),
+        "minExclusive" => Facet::MinExclusive(limit.trim().to_owned()),
+        "maxExclusive" => Facet::MaxExclusive(limit.trim().to_owned()),
+        "pattern" => Facet::Pattern(vec![Pattern::parse(limit)?]),
+        other => return Err(format!("Unsupported facet {}", other)),
+    };
+
+    check_facet_value(&facet, value, value.chars().count())
+}
+
+//Checks a parsed document against a schema, collecting every problem
+struct Validator<'a> {
+    schema: &'a Schema,
+    document: &'a XMLDocument,
+    errors: Vec<ValidationError>,
+}
+
+impl<'a> Validator<'a> {
+    fn error(&mut self, node: NodeId, message: String) {
+        let error = ValidationError::new(self.document, node, message);
+        self.errors.push(error);
+    }
+
+    fn element_name(&self, node: NodeId) -> QName {
+        (self.document.namespace(node).unwrap_or("").to_owned(), self.document.local_name(node).to_owned())
+    }
+
+    //Checks a value against a simple type, describing every rule it breaks
+    fn check_simple(&self, simple_type: &SimpleType, value: &str) -> Result<(), Vec<String>> {
+        match *simple_type {
+            SimpleType::BuiltIn(ref name) => match check_built_in(name, value) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(vec![format!("'{}' is not a valid {}", value, name)]),
+                Err(e) => Err(vec![e]),
+            },
+            SimpleType::Named(ref name) => match self.schema.simple_types.get(name) {
+                Some(simple_type) => self.check_simple(simple_type, value),
+                None => Err(vec![format!("Type {} is not declared in the schema", display_name(name))]),
+            },
+            SimpleType::List(ref item_type) => {
+                let errors: Vec<String> = value.split_whitespace()
+                    .filter_map(|item| self.check_simple(item_type, item).err())
+                    .flatten()
+                    .collect();
+
+                if errors.is_empty() { Ok(()) } else { Err(errors) }
+            },
+            SimpleType::Union(ref members) => {
+                if members.iter().any(|member| self.check_simple(member, value).is_ok()) {
+                    Ok(())
+                }
+                else {
+                    Err(vec![format!("'{}' is not valid for any of the member types of a union", value)])
+                }
+            },
+            SimpleType::Restriction(ref base, ref facets) => {
+                //The facets compare values of the base type, so mean nothing for a value which is not one
+                self.check_simple(base, value)?;
+
+                //The length of a list is the number of items in it
+                let length = if self.is_list(base) { value.split_whitespace().count() } else { value.chars().count() };
+
+                let errors: Vec<String> = facets.iter()
+                    .filter_map(|facet| check_facet_value(facet, value, length).err())
+                    .collect();
+
+                if errors.is_empty() { Ok(()) } else { Err(errors) }
+            },
+        }
+    }
+
+    fn is_list(&self, simple_type: &SimpleType) -> bool {
+        match *simple_type {
+            SimpleType::List(_) => true,
+            SimpleType::Restriction(ref base, _) => self.is_list(base),
+            SimpleType::Named(ref name) => self.schema.simple_types.get(name).is_some_and(|simple_type| self.is_list(simple_type)),
+            _ => false,
+        }
+    }
+
+    //Gathers the attributes a complex type declares, including those of its base types and attribute groups
+    fn collect_attributes(&self, items: &[AttributeItem], attributes: &mut Vec<AttributeDeclaration>, depth: usize) -> Result<(), String> {
+        if depth > 32 {
+            return Err("Attribute groups refer to each other in a loop".to_owned());
+        }
+
+        for item in items {
+            match *item {
+                AttributeItem::Attribute(ref declaration) => {
+                    attributes.retain(|existing| existing.name != declaration.name);
+                    attributes.push(declaration.clone());
+                },
+                AttributeItem::Ref(ref name, attribute_use) => {
+                    let mut declaration = match self.schema.attributes.get(name) {
+                        Some(declaration) => declaration.clone(),
+                        //Attributes such as 'xml:lang' need no declaration
+                        None if name.0 == XML_NAMESPACE => AttributeDeclaration {
+                            name: name.clone(),
+                            simple_type: SimpleType::BuiltIn("string".to_owned()),
+                            attribute_use,
+                            fixed: None,
+                        },
+                        None => return Err(format!("Attribute {} is not declared in the schema", display_name(name))),
+                    };
+                    declaration.attribute_use = attribute_use;
+
+                    attributes.retain(|existing| existing.name != declaration.name);
+                    attributes.push(declaration);
+                },
+                AttributeItem::GroupRef(ref name) => match self.schema.attribute_groups.get(name) {
+                    Some(items) => self.collect_attributes(items, attributes, depth + 1)?,
+                    None => return Err(format!("Attribute group {} is not declared in the schema", display_name(name))),
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn type_attributes(&self, complex_type: &ComplexType, attributes: &mut Vec<AttributeDeclaration>, any_attribute: &mut Option<NamespaceConstraint>, depth: usize) -> Result<(), String> {
+        if depth > 32 {
+            return Err("Types are derived from each other in a loop".to_owned());
+        }
+
+        if let Some(ref base) = complex_type.base {
+            if let Some(base_type) = self.schema.complex_types.get(base) {
+                self.type_attributes(base_type, attributes, any_attribute, depth + 1)?;
+            }
+        }
+
+        self.collect_attributes(&complex_type.attributes, attributes, 0)?;
+
+        if complex_type.any_attribute.is_some() {
+            *any_attribute = complex_type.any_attribute.clone();
+        }
+
+        Ok(())
+    }
+
+    //The content model of a complex type, which for an extension is the content of its base type followed by its own
+    fn type_content(&self, complex_type: &ComplexType, depth: usize) -> Result<Option<Particle>, String> {
+        if depth > 32 {
+            return Err("Types are derived from each other in a loop".to_owned());
+        }
+
+        let base_content = match complex_type.base {
+            Some(ref base) if complex_type.extends => match self.schema.complex_types.get(base) {
+                Some(base_type) => self.type_content(base_type, depth + 1)?,
+                None => None,
+            },
+            _ => None,
+        };
+
+        Ok(match (base_content, complex_type.content.clone()) {
+            (Some(base_content), Some(content)) => Some(Particle {
+                term: Term::Sequence(vec![base_content, content]),
+                min_occurs: 1,
+                max_occurs: Some(1),
+            }),
+            (Some(content), None) | (None, Some(content)) => Some(content),
+            (None, None) => None,
+        })
+    }
+
+    //The simple type of the text of a complex type with simple content
+    fn type_simple_content(&self, complex_type: &ComplexType, depth: usize) -> Option<SimpleType> {
+        if depth > 32 {
+            return None;
+        }
+
+        match complex_type.simple_content {
+            //A base which is a complex type with simple content gives its text type
+            Some(SimpleType::Named(ref name)) if self.schema.complex_types.contains_key(name) => {
+                self.type_simple_content(&self.schema.complex_types[name], depth + 1)
+            },
+            Some(SimpleType::Restriction(ref base, ref facets)) => match **base {
+                SimpleType::Named(ref name) if self.schema.complex_types.contains_key(name) => {
+                    self.type_simple_content(&self.schema.complex_types[name], depth + 1)
+                        .map(|base| SimpleType::Restriction(Box::new(base), facets.clone()))
+                },
+                _ => complex_type.simple_content.clone(),
+            },
+            Some(ref simple_type) => Some(simple_type.clone()),
+            None => match complex_type.base {
+                Some(ref base) => self.schema.complex_types.get(base).and_then(|base_type| self.type_simple_content(base_type, depth + 1)),
+                None => None,
+            },
+        }
+    }
+
+    //Finds every position in 'names' the particle can end at, starting from any of 'starts'.
+    //The names of elements which could have come next when the names ran out are added to 'expected'.
+    fn match_particle(&self, particle: &Particle, names: &[QName], starts: &[usize], expected: &mut Vec<String>, depth: usize) -> Vec<usize> {
+        match_repeated(particle.min_occurs, particle.max_occurs, starts, |current| self.match_term(&particle.term, names, current, expected, depth))
+    }
+
+    fn match_term(&self, term: &Term, names: &[QName], starts: &[usize], expected: &mut Vec<String>, depth: usize) -> Vec<usize> {
+        if depth > 64 {
+            return Vec::new();
+        }
+
+        let mut ends: Vec<usize> = match *term {
+            Term::Element(ElementDeclaration { ref name, .. }) | Term::ElementRef(ref name) => {
+                let mut ends: Vec<usize> = Vec::new();
+
+                for &start in starts {
+                    if start == names.len() {
+                        expected.push(format!("'{}'", display_name(name)));
+                    }
+                    else if names[start] == *name {
+                        ends.push(start + 1);
+                    }
+                }
+
+                ends
+            },
+            Term::Any(ref namespaces, _) => {
+                let mut ends: Vec<usize> = Vec::new();
+
+                for &start in starts {
+                    if start == names.len() {
+                        expected.push("any element".to_owned());
+                    }
+                    else if namespaces.allows(&names[start].0) {
+                        ends.push(start + 1);
+                    }
+                }
+
+                ends
+            },
+            Term::Sequence(ref particles) => {
+                let mut positions = starts.to_vec();
+
+                for particle in particles {
+                    if positions.is_empty() {
+                        break;
+                    }
+                    positions = self.match_particle(particle, names, &positions, expected, depth + 1);
+                }
+
+                positions
+            },
+            Term::Choice(ref particles) => particles.iter()
+                .flat_map(|particle| self.match_particle(particle, names, starts, expected, depth + 1))
+                .collect(),
+            Term::All(ref particles) => {
+                let mut ends: Vec<usize> = Vec::new();
+
+                //Members of 'all' have different names, so each element can only be one of them
+                for &start in starts {
+                    let mut used = vec![false; particles.len()];
+                    let mut position = start;
+
+                    loop {
+                        let mut next = None;
+
+                        for (index, particle) in particles.iter().enumerate() {
+                            if used[index] {
+                                continue;
+                            }
+
+                            if self.match_particle(particle, names, &[position], &mut Vec::new(), depth + 1).iter().any(|&end| end > position) {
+                                next = Some(index);
+                                break;
+                            }
+                        }
+
+                        match next {
+                            Some(index) => {
+                                used[index] = true;
+                                position += 1;
+                            },
+                            None => break,
+                        }
+                    }
+
+                    let missing: Vec<&Particle> = particles.iter().enumerate()
+                        .filter(|&(index, particle)| !used[index] && particle.min_occurs > 0)
+                        .map(|(_, particle)| particle)
+                        .collect();
+
+                    if position == names.len() {
+                        for (index, particle) in particles.iter().enumerate() {
+                            if !used[index] {
+                                self.match_particle(particle, names, &[position], expected, depth + 1);
+                            }
+                        }
+                    }
+
+                    if missing.is_empty() {
+                        ends.push(position);
+                    }
+                }
+
+                ends
+            },
+            Term::GroupRef(ref name) => match self.schema.groups.get(name) {
+                Some(particle) => self.match_particle(particle, names, starts, expected, depth + 1),
+                None => Vec::new(),
+            },
+        };
+
+        ends.sort();
+        ends.dedup();
+        ends
+    }
+
+    //Finds the declaration for a child element in a content model, or the wildcard which allows it
+    fn find_declaration(&self, particle: &Particle, name: &QName, depth: usize) -> Option<Result<ElementDeclaration, ProcessContents>> {
+        if depth > 64 {
+            return None;
+        }
+
+        match particle.term {
+            Term::Element(ref declaration) if declaration.name == *name => Some(Ok(declaration.clone())),
+            Term::ElementRef(ref reference) if reference == name => self.schema.elements.get(name).map(|declaration| Ok(declaration.clone())),
+            Term::Sequence(ref particles) | Term::Choice(ref particles) | Term::All(ref particles) => {
+                let mut wildcard = None;
+
+                //A declaration wins over a wildcard which also allows the name
+                for particle in particles {
+                    match self.find_declaration(particle, name, depth + 1) {
+                        Some(Ok(declaration)) => return Some(Ok(declaration)),
+                        Some(Err(process_contents)) if wildcard.is_none() => wildcard = Some(Err(process_contents)),
+                        _ => {},
+                    }
+                }
+
+                wildcard
+            },
+            Term::GroupRef(ref group) => self.schema.groups.get(group).and_then(|particle| self.find_declaration(particle, name, depth + 1)),
+            Term::Any(ref namespaces, process_contents) if namespaces.allows(&name.0) => Some(Err(process_contents)),
+            _ => None,
+        }
+    }
+
+    fn check_attributes(&mut self, node: NodeId, complex_type: Option<&ComplexType>) {
+        let mut declarations: Vec<AttributeDeclaration> = Vec::new();
+        let mut any_attribute: Option<NamespaceConstraint> = None;
+
+        if let Some(complex_type) = complex_type {
+            if let Err(e) = self.type_attributes(complex_type, &mut declarations, &mut any_attribute, 0) {
+                self.error(node, e);
+                return;
+            }
+        }
+
+        let element = self.document.name(node).to_owned();
+
+        for (name, value) in self.document.attributes(node) {
+            let namespace = self.document.attribute_namespace(node, name).unwrap_or("");
+
+            //Namespace declarations and schema instance attributes are allowed everywhere
+            if declared_prefix(name).is_some() || namespace == XSI_NAMESPACE {
+                continue;
+            }
+
+            let attribute_name = (namespace.to_owned(), split_name(name).1.to_owned());
+
+            let result = match declarations.iter().find(|declaration| declaration.name == attribute_name) {
+                Some(declaration) if declaration.attribute_use == AttributeUse::Prohibited => {
+                    Err(vec![format!("Attribute '{}' is not allowed on '{}'", name, element)])
+                },
+                Some(declaration) => {
+                    let value = value.trim();
+
+                    match self.check_simple(&declaration.simple_type, value) {
+                        Ok(_) => match declaration.fixed {
+                            Some(ref fixed) if fixed != value => Err(vec![format!("Attribute '{}' of '{}' must be '{}'", name, element, fixed)]),
+                            _ => Ok(()),
+                        },
+                        Err(errors) => Err(errors.iter().map(|e| format!("Invalid value of attribute '{}' of '{}': {}", name, element, e)).collect()),
+                    }
+                },
+                None if any_attribute.as_ref().is_some_and(|namespaces| namespaces.allows(namespace)) => Ok(()),
+                None => Err(vec![format!("Attribute '{}' is not allowed on '{}'", name, element)]),
+            };
+
+            if let Err(errors) = result {
+                for e in errors {
+                    self.error(node, e);
+                }
+            }
+        }
+
+        for declaration in &declarations {
+            if declaration.attribute_use != AttributeUse::Required {
+                continue;
+            }
+
+            let present = self.document.attributes(node).iter().any(|&(name, _)| {
+                let namespace = self.document.attribute_namespace(node, name).unwrap_or("");
+                declaration.name.0 == namespace && declaration.name.1 == split_name(name).1
+            });
+
+            if !present {
+                let message = format!("Missing required attribute '{}' on '{}'", display_name(&declaration.name), element);
+                self.error(node, message);
+            }
+        }
+    }
+
+    fn check_text(&mut self, node: NodeId, simple_type: &SimpleType, fixed: &Option<String>) {
+        let element = self.document.name(node).to_owned();
+
+        if !self.document.child_elements(node).is_empty() {
+            self.error(node, format!("Element '{}' cannot contain elements", element));
+            return;
+        }
+
+        let value = self.document.text(node);
+        let value = value.trim();
+
+        let result = match self.check_simple(simple_type, value) {
+            Ok(_) => match *fixed {
+                Some(ref fixed) if fixed != value => Err(vec![format!("Element '{}' must contain '{}'", element, fixed)]),
+                _ => Ok(()),
+            },
+            Err(errors) => Err(errors.iter().map(|e| format!("Invalid content of '{}': {}", element, e)).collect()),
+        };
+
+        if let Err(errors) = result {
+            for e in errors {
+                self.error(node, e);
+            }
+        }
+    }
+
+    fn check_content(&mut self, node: NodeId, particle: Option<Particle>) {
+        let element = self.document.name(node).to_owned();
+        let children = self.document.child_elements(node);
+        let names: Vec<QName> = children.iter().map(|&child| self.element_name(child)).collect();
+
+        let particle = match particle {
+            Some(particle) => particle,
+            None => {
+                if let Some(&child) = children.first() {
+                    self.error(child, format!("Element '{}' is not allowed in '{}', which must be empty", self.document.name(child), element));
+                }
+                return;
+            },
+        };
+
+        let problem = check_content_model(names.len(), |length, expected| self.match_particle(&particle, &names[..length], &[0], expected, 0));
+
+        match problem {
+            Some(ContentProblem::Incomplete(expected)) => self.error(node, format!("Element '{}' is incomplete, {}", element, expected)),
+            Some(ContentProblem::Unexpected(index, expected)) => {
+                let child = children[index];
+                let message = format!("Element '{}' is not expected here in '{}', {}", self.document.name(child), element, expected);
+                self.error(child, message);
+            },
+            None => {},
+        }
+
+        //Check each child which the content model has a declaration or strict wildcard for
+        for (&child, name) in children.iter().zip(&names) {
+            match self.find_declaration(&particle, name, 0) {
+                Some(Ok(declaration)) => self.check_element(child, &declaration),
+                Some(Err(ProcessContents::Skip)) | None => {},
+                Some(Err(process_contents)) => match self.schema.elements.get(name) {
+                    Some(declaration) => self.check_element(child, declaration),
+                    None if process_contents == ProcessContents::Strict => {
+                        self.error(child, format!("Element '{}' is not declared in the schema", self.document.name(child)));
+                    },
+                    None => {},
+                },
+            }
+        }
+    }
+
+    fn check_element(&mut self, node: NodeId, declaration: &ElementDeclaration) {
+        let element = self.document.name(node).to_owned();
+
+        //'xsi:nil' marks an element of a nillable declaration as deliberately empty
+        let nil = self.document.attributes(node).iter()
+            .any(|&(name, value)| self.document.attribute_namespace(node, name) == Some(XSI_NAMESPACE) && split_name(name).1 == "nil" && value.trim() == "true");
+
+        if nil {
+            if !declaration.nillable {
+                self.error(node, format!("Element '{}' cannot be nil", element));
+            }
+            else if self.document.children(node).iter().any(|&child| self.document.kind(child) != NodeKind::Text) || self.document.has_text(node) {
+                self.error(node, format!("Element '{}' is nil, so must be empty", element));
+            }
+        }
+
+        let definition = match declaration.definition {
+            TypeDefinition::Named(ref name) => {
+                if let Some(complex_type) = self.schema.complex_types.get(name) {
+                    TypeDefinition::Complex(Box::new(complex_type.clone()))
+                }
+                else if self.schema.simple_types.contains_key(name) {
+                    TypeDefinition::Simple(SimpleType::Named(name.clone()))
+                }
+                else {
+                    self.error(node, format!("Type {} of '{}' is not declared in the schema", display_name(name), element));
+                    return;
+                }
+            },
+            ref definition => definition.clone(),
+        };
+
+        match definition {
+            TypeDefinition::AnyType | TypeDefinition::Named(_) => {},
+            TypeDefinition::Simple(simple_type) => {
+                self.check_attributes(node, None);
+                if !nil {
+                    self.check_text(node, &simple_type, &declaration.fixed);
+                }
+            },
+            TypeDefinition::Complex(complex_type) => {
+                self.check_attributes(node, Some(&complex_type));
+
+                if nil {
+                    return;
+                }
+
+                if let Some(simple_type) = self.type_simple_content(&complex_type, 0) {
+                    self.check_text(node, &simple_type, &declaration.fixed);
+                    return;
+                }
+
+                if !complex_type.mixed && self.document.has_text(node) {
+                    self.error(node, format!("Element '{}' cannot contain text", element));
+                }
+
+                match self.type_content(&complex_type, 0) {
+                    Ok(particle) => self.check_content(node, particle),
+                    Err(e) => self.error(node, e),
+                }
+            },
+        }
+    }
+}
+
+impl Schema {
+    //Reads a schema from a parsed XSD file
+    pub fn parse(document: &XMLDocument) -> Result<Schema, String> {
+        let schema_node = match document.child_elements(document.root()).into_iter().next() {
+            Some(node) if document.namespace(node) == Some(XSD_NAMESPACE) && document.local_name(node) == "schema" => node,
+            _ => return Err(format!("A schema must start with an element 'schema' in the namespace {}", XSD_NAMESPACE)),
+        };
+
+        let reader = SchemaReader {
+            document,
+            target_namespace: document.attribute(schema_node, "targetNamespace").unwrap_or("").to_owned(),
+            qualified_elements: document.attribute(schema_node, "elementFormDefault") == Some("qualified"),
+            qualified_attributes: document.attribute(schema_node, "attributeFormDefault") == Some("qualified"),
+        };
+
+        let mut schema = Schema {
+            target_namespace: reader.target_namespace.clone(),
+            elements: HashMap::new(),
+            attributes: HashMap::new(),
+            complex_types: HashMap::new(),
+            simple_types: HashMap::new(),
+            groups: HashMap::new(),
+            attribute_groups: HashMap::new(),
+        };
+
+        for node in reader.xsd_children(schema_node) {
+            let name = || -> Result<QName, String> {
+                Ok((reader.target_namespace.clone(), reader.required_attribute(node, "name")?.to_owned()))
+            };
+
+            match document.local_name(node) {
+                "element" => {
+                    let declaration = reader.element(node, true)?;
+                    schema.elements.insert(declaration.name.clone(), declaration);
+                },
+                "attribute" => {
+                    if let AttributeItem::Attribute(declaration) = reader.attribute(node, true)? {
+                        schema.attributes.insert(declaration.name.clone(), declaration);
+                    }
+                },
+                "complexType" => {
+                    schema.complex_types.insert(name()?, reader.complex_type(node)?);
+                },
+                "simpleType" => {
+                    schema.simple_types.insert(name()?, reader.simple_type(node)?);
+                },
+                "group" => {
+                    let particle = match reader.xsd_children(node).into_iter().next() {
+                        Some(child) => reader.content_particle(child)?,
+                        None => return Err(format!("Group {} is empty", name()?.1)),
+                    };
+                    schema.groups.insert(name()?, particle);
+                },
+                "attributeGroup" => {
+                    let mut attributes: Vec<AttributeItem> = Vec::new();
+                    let mut any_attribute: Option<NamespaceConstraint> = None;
+
+                    for child in reader.xsd_children(node) {
+                        if !reader.attribute_item(child, &mut attributes, &mut any_attribute)? {
+                            return Err(reader.unsupported(child));
+                        }
+                    }
+                    schema.attribute_groups.insert(name()?, attributes);
+                },
+                "notation" => {},
+                _ => return Err(reader.unsupported(node)),
+            }
+        }
+
+        Ok(schema)
+    }
+
+    //The namespace the schema declares elements in, empty for none
+    pub fn target_namespace(&self) -> &str {
+        &self.target_namespace
+    }
+
+    //Checks a document against the schema, returning every problem found. The document is valid if there are none.
+    pub fn validate(&self, document: &XMLDocument) -> Vec<ValidationError> {
+        let mut validator = Validator {
+            schema: self,
+            document,
+            errors: Vec::new(),
+        };
+
+        let elements = document.child_elements(document.root());
+
+        match elements.first() {
+            Some(&node) => {
+                let name = validator.element_name(node);
+
+                match self.elements.get(&name) {
+                    Some(declaration) => validator.check_element(node, declaration),
+                    None => validator.error(node, format!("Element '{}' is not declared as a top level element of the schema", document.name(node))),
+                }
+            },
+            None => validator.errors.push(ValidationError { position: None, message: "The document has no elements".to_owned() }),
+        }
+
+        sort_by_position(&mut validator.errors);
+        validator.errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {lexer, parser};
+
+    const SCHEMA: &str = "<xs:schema xmlns:xs=\"http://www.w3.org/2001/XMLSchema\">
+  <xs:element name=\"prices\">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:element name=\"price\" minOccurs=\"1\" maxOccurs=\"2\">
+          <xs:complexType>
+            <xs:simpleContent>
+              <xs:extension base=\"amount\">
+                <xs:attribute name=\"currency\" type=\"xs:string\" use=\"required\"/>
+              </xs:extension>
+            </xs:simpleContent>
+          </xs:complexType>
+        </xs:element>
+        <xs:choice>
+          <xs:element name=\"note\" type=\"xs:string\"/>
+          <xs:element name=\"code\" type=\"xs:int\"/>
+        </xs:choice>
+      </xs:sequence>
+    </xs:complexType>
+  </xs:element>
+  <xs:simpleType name=\"amount\">
+    <xs:restriction base=\"xs:decimal\">
+      <xs:minInclusive value=\"0\"/>
+      <xs:pattern value=\"\\d+\\.\\d\\d\"/>
+    </xs:restriction>
+  </xs:simpleType>
+</xs:schema>";
+
+    //The messages for everything in 'xml' which breaks the schema
+    fn validate(xml: &str) -> Vec<String> {
+        let schema = Schema::parse(&parser(&lexer(SCHEMA.to_owned()).unwrap()).unwrap()).unwrap();
+        let document = parser(&lexer(xml.to_owned()).unwrap()).unwrap();
+
+        schema.validate(&document).into_iter().map(|error| error.message).collect()
+    }
+
+    #[test]
+    fn valid_documents_have_no_errors() {
+        assert!(validate("<prices><price currency=\"EUR\">1.50</price><code>7</code></prices>").is_empty());
+        assert!(validate("<prices><price currency=\"EUR\">1.50</price><price currency=\"GBP\">0.00</price><note>n</note></prices>").is_empty());
+    }
+
+    #[test]
+    fn every_broken_facet_is_reported() {
+        let errors = validate("<prices><price currency=\"EUR\">-2.0</price><code>7</code></prices>");
+
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].contains("less than 0"), "{:?}", errors);
+        assert!(errors[1].contains("does not match the pattern"), "{:?}", errors);
+    }
+
+    #[test]
+    fn reports_content_attributes_and_types() {
+        let errors = validate("<prices><price>1.00</price><price currency=\"EUR\">1.00</price><price currency=\"EUR\">1.00</price><code>x</code></prices>");
+
+        assert_eq!(errors, vec![
+            "Element 'price' is not expected here in 'prices', expected 'code' or 'note'",
+            "Missing required attribute 'currency' on 'price'",
+            "Invalid content of 'code': 'x' is not a valid int",
+        ]);
+        assert_eq!(validate("<prices><price currency=\"EUR\">1.00</price></prices>").len(), 1);
+    }
+
+    #[test]
+    fn large_repeats_stop_once_nothing_new_matches() {
+        let xsd = "<xs:schema xmlns:xs=\"http://www.w3.org/2001/XMLSchema\"><xs:element name=\"a\"><xs:complexType>\
+          <xs:sequence maxOccurs=\"50000000\"><xs:element name=\"b\" minOccurs=\"0\"/></xs:sequence>\
+        </xs:complexType></xs:element></xs:schema>";
+        let schema = Schema::parse(&parser(&lexer(xsd.to_owned()).unwrap()).unwrap()).unwrap();
+
+        assert!(schema.validate(&parser(&lexer("<a><b/></a>".to_owned()).unwrap()).unwrap()).is_empty());
+        assert_eq!(schema.validate(&parser(&lexer("<a><c/></a>".to_owned()).unwrap()).unwrap()).len(), 1);
+    }
+
+    #[test]
+    fn built_in_types_check_their_ranges() {
+        assert_eq!(check_built_in("byte", "127"), Ok(true));
+        assert_eq!(check_built_in("byte", "128"), Ok(false));
+        assert_eq!(check_built_in("unsignedLong", "-1"), Ok(false));
+        assert_eq!(check_built_in("date", "2024-02-29Z"), Ok(true));
+        assert_eq!(check_built_in("date", "2023-02-29"), Ok(false));
+        assert!(check_built_in("madeUp", "1").is_err());
+    }
+
+    #[test]
+    fn non_ascii_dates_are_invalid() {
+        assert_eq!(check_built_in("date", "€€x"), Ok(false));
+        assert_eq!(check_built_in("dateTime", "2024-01-01T10:00:0€"), Ok(false));
+    }
+}
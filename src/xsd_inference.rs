@@ -0,0 +1,641 @@
+//
+//  Description  :    Inference of an XSD describing the structure and value types of sample XML documents
+//  Author       :    Jack Wilson
+//  Mail         :    jack.wilson3311@gmail.com
+//
+
+use document::{XMLDocument, NodeId};
+use namespaces::declared_prefix;
+use xsd::{XSD_NAMESPACE, XSI_NAMESPACE, check_built_in};
+
+//The simple types a value can be inferred to have, from the most to the least specific
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum SimpleType {
+    Boolean,
+    Integer,
+    Decimal,
+    Date,
+    DateTime,
+    String,
+}
+
+impl SimpleType {
+    //The most specific type of a single value, ignoring whitespace around it
+    pub fn of(value: &str) -> SimpleType {
+        let value = value.trim();
+
+        if value == "true" || value == "false" {
+            SimpleType::Boolean
+        }
+        else if check_built_in("integer", value) == Ok(true) {
+            SimpleType::Integer
+        }
+        else if check_built_in("decimal", value) == Ok(true) {
+            SimpleType::Decimal
+        }
+        else if check_built_in("date", value) == Ok(true) {
+            SimpleType::Date
+        }
+        else if check_built_in("dateTime", value) == Ok(true) {
+            SimpleType::DateTime
+        }
+        else {
+            SimpleType::String
+        }
+    }
+
+    //The most specific type allowing the values of both types
+    pub fn merge(self, other: SimpleType) -> SimpleType {
+        match (self, other) {
+            _ if self == other => self,
+            (SimpleType::Integer, SimpleType::Decimal) | (SimpleType::Decimal, SimpleType::Integer) => SimpleType::Decimal,
+            _ => SimpleType::String,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SimpleType::Boolean => "boolean",
+            SimpleType::Integer => "integer",
+            SimpleType::Decimal => "decimal",
+            SimpleType::Date => "date",
+            SimpleType::DateTime => "datetime",
+            SimpleType::String => "string",
+        }
+    }
+
+    //Whether a value of this type is also a value of another, as integers are decimals
+    pub fn is_within(&self, other: SimpleType) -> bool {
+        *self == other || (*self == SimpleType::Integer && other == SimpleType::Decimal) || other == SimpleType::String
+    }
+
+    //The name of a type defined by the schema which also allows blank values
+    fn nullable_name(&self) -> &'static str {
+        match *self {
+            SimpleType::Boolean => "nullableBoolean",
+            SimpleType::Integer => "nullableInteger",
+            SimpleType::Decimal => "nullableDecimal",
+            SimpleType::Date => "nullableDate",
+            SimpleType::DateTime => "nullableDateTime",
+            SimpleType::String => "xs:string",
+        }
+    }
+
+    pub fn xsd_name(&self) -> &'static str {
+        match *self {
+            SimpleType::Boolean => "xs:boolean",
+            SimpleType::Integer => "xs:integer",
+            SimpleType::Decimal => "xs:decimal",
+            SimpleType::Date => "xs:date",
+            SimpleType::DateTime => "xs:dateTime",
+            SimpleType::String => "xs:string",
+        }
+    }
+}
+
+//The values seen for an attribute or the text of an element
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct ValueStatistics {
+    //The type of every value which was not blank, if there were any
+    pub value_type: Option<SimpleType>,
+    pub has_blank: bool,
+}
+
+impl Default for ValueStatistics {
+    fn default() -> ValueStatistics {
+        ValueStatistics::new()
+    }
+}
+
+impl ValueStatistics {
+    pub fn new() -> ValueStatistics {
+        ValueStatistics {
+            value_type: None,
+            has_blank: false,
+        }
+    }
+
+    pub fn add(&mut self, value: &str) {
+        if value.trim().is_empty() {
+            self.has_blank = true;
+            return;
+        }
+
+        let value_type = SimpleType::of(value);
+        self.value_type = Some(self.value_type.map_or(value_type, |seen| seen.merge(value_type)));
+    }
+
+    //The type of the values which were not blank, or string if all of them were
+    pub fn simple_type(&self) -> SimpleType {
+        self.value_type.unwrap_or(SimpleType::String)
+    }
+
+    //Whether blank values were seen among values of a type which does not allow them
+    pub fn nullable(&self) -> bool {
+        self.has_blank && self.simple_type() != SimpleType::String
+    }
+
+    //The name of the schema type for the values, noting any type which must be defined to also allow blanks
+    fn type_name(&self, nullable_types: &mut Vec<SimpleType>) -> &'static str {
+        let simple_type = self.simple_type();
+
+        if !self.nullable() {
+            return simple_type.xsd_name();
+        }
+
+        if !nullable_types.contains(&simple_type) {
+            nullable_types.push(simple_type);
+        }
+        simple_type.nullable_name()
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+struct AttributeStatistics {
+    name: String,
+    count: usize,
+    values: ValueStatistics,
+}
+
+//An element which appeared inside another, with how many times it appeared in each of them
+#[derive(Debug)]
+#[derive(Clone)]
+struct ChildStatistics {
+    element: ElementStatistics,
+    //The number of parents it appeared in, and the fewest and most times it appeared in one of them
+    parents: usize,
+    min_occurs: usize,
+    max_occurs: usize,
+}
+
+impl ChildStatistics {
+    //Whether every parent had it, as a minimum of 1, or not, as 0. Samples showing it at least twice do not make it required twice.
+    fn min_occurs(&self, parent: &ElementStatistics) -> usize {
+        if self.parents < parent.instances { 0 } else { self.min_occurs.min(1) }
+    }
+}
+
+//Everything seen of the elements with one name at one place in the samples
+#[derive(Debug)]
+#[derive(Clone)]
+struct ElementStatistics {
+    namespace: Option<String>,
+    local_name: String,
+    instances: usize,
+    //The text of the instances without child elements
+    text: ValueStatistics,
+    //Whether text was found beside child elements
+    mixed: bool,
+    attributes: Vec<AttributeStatistics>,
+    //Whether attributes in other namespaces were found, such as 'xml:lang'
+    other_attributes: bool,
+    //Children in the order they were found, which is the order of every instance if 'ordered'
+    children: Vec<ChildStatistics>,
+    ordered: bool,
+}
+
+impl ElementStatistics {
+    fn new(namespace: Option<String>, local_name: &str) -> ElementStatistics {
+        ElementStatistics {
+            namespace,
+            local_name: local_name.to_owned(),
+            instances: 0,
+            text: ValueStatistics::new(),
+            mixed: false,
+            attributes: Vec::new(),
+            other_attributes: false,
+            children: Vec::new(),
+            ordered: true,
+        }
+    }
+
+    fn add(&mut self, document: &XMLDocument, node: NodeId) {
+        self.instances += 1;
+
+        for (name, value) in document.attributes(node) {
+            if declared_prefix(name).is_some() || document.attribute_namespace(node, name) == Some(XSI_NAMESPACE) {
+                continue;
+            }
+            if name.contains(':') {
+                self.other_attributes = true;
+                continue;
+            }
+
+            let index = match self.attributes.iter().position(|attribute| attribute.name == name) {
+                Some(index) => index,
+                None => {
+                    self.attributes.push(AttributeStatistics {
+                        name: name.to_owned(),
+                        count: 0,
+                        values: ValueStatistics::new(),
+                    });
+                    self.attributes.len() - 1
+                },
+            };
+
+            self.attributes[index].count += 1;
+            self.attributes[index].values.add(value);
+        }
+
+        let children = document.child_elements(node);
+        let text = document.text(node);
+
+        if children.is_empty() {
+            self.text.add(&text);
+            return;
+        }
+
+        if !text.trim().is_empty() {
+            self.mixed = true;
+        }
+
+        //The children's positions in the merged order, and how many times each appears here
+        let mut order: Vec<usize> = Vec::new();
+        let mut counts: Vec<usize> = vec![0; self.children.len()];
+
+        for child in children {
+            let namespace = document.namespace(child).map(|uri| uri.to_owned());
+            let local_name = document.local_name(child);
+            let index = match self.children.iter().position(|statistics| statistics.element.local_name == local_name && statistics.element.namespace == namespace) {
+                Some(index) => index,
+                None => {
+                    //A new child goes straight after the one before it, or first, so the order seen so far still holds
+                    let index = order.last().map_or(0, |&previous| previous + 1);
+
+                    self.children.insert(index, ChildStatistics {
+                        element: ElementStatistics::new(namespace, local_name),
+                        parents: 0,
+                        min_occurs: usize::MAX,
+                        max_occurs: 0,
+                    });
+                    counts.insert(index, 0);
+                    for position in order.iter_mut().filter(|position| **position >= index) {
+                        *position += 1;
+                    }
+                    index
+                },
+            };
+
+            if order.last() != Some(&index) {
+                order.push(index);
+            }
+            counts[index] += 1;
+
+            self.children[index].element.add(document, child);
+        }
+
+        //The children are in order if each name appears in one run, in the merged order
+        if order.windows(2).any(|pair| pair[0] >= pair[1]) {
+            self.ordered = false;
+        }
+
+        for (statistics, &count) in self.children.iter_mut().zip(counts.iter()) {
+            if count > 0 {
+                statistics.parents += 1;
+                statistics.min_occurs = statistics.min_occurs.min(count);
+                statistics.max_occurs = statistics.max_occurs.max(count);
+            }
+        }
+    }
+
+    //Adds a declaration of a child to the schema being written. Children from other namespaces are allowed as any element in theirs.
+    fn write_child(&self, child: &ChildStatistics, schema: &mut XMLDocument, parent: NodeId, min_occurs: Option<usize>, repeats: bool, nullable_types: &mut Vec<SimpleType>) {
+        let particle = if child.element.namespace == self.namespace {
+            child.element.write(schema, parent, nullable_types)
+        }
+        else {
+            let any = schema.append_element(parent, "xs:any");
+            schema.set_attribute(any, "namespace", child.element.namespace.as_ref().map_or("##local", |uri| uri.as_str()));
+            schema.set_attribute(any, "processContents", "skip");
+            any
+        };
+
+        if let Some(min_occurs) = min_occurs {
+            if min_occurs != 1 {
+                schema.set_attribute(particle, "minOccurs", &min_occurs.to_string());
+            }
+        }
+        if repeats {
+            schema.set_attribute(particle, "maxOccurs", "unbounded");
+        }
+    }
+
+    //Adds an element declaration for this element to the schema being written, returning it
+    fn write(&self, schema: &mut XMLDocument, parent: NodeId, nullable_types: &mut Vec<SimpleType>) -> NodeId {
+        let element = schema.append_element(parent, "xs:element");
+        schema.set_attribute(element, "name", &self.local_name);
+
+        let has_attributes = !self.attributes.is_empty() || self.other_attributes;
+
+        if self.children.is_empty() {
+            //An element which only ever had blank text is left empty
+            let type_name = self.text.value_type.map(|_| self.text.type_name(nullable_types));
+
+            match type_name {
+                Some(type_name) if !has_attributes => schema.set_attribute(element, "type", type_name),
+                Some(type_name) => {
+                    let complex_type = schema.append_element(element, "xs:complexType");
+                    let content = schema.append_element(complex_type, "xs:simpleContent");
+                    let extension = schema.append_element(content, "xs:extension");
+                    schema.set_attribute(extension, "base", type_name);
+                    self.write_attributes(schema, extension, nullable_types);
+                },
+                None => {
+                    let complex_type = schema.append_element(element, "xs:complexType");
+                    self.write_attributes(schema, complex_type, nullable_types);
+                },
+            }
+
+            return element;
+        }
+
+        let complex_type = schema.append_element(element, "xs:complexType");
+        if self.mixed || self.text.value_type.is_some() {
+            schema.set_attribute(complex_type, "mixed", "true");
+        }
+
+        if self.ordered {
+            let sequence = schema.append_element(complex_type, "xs:sequence");
+            for child in &self.children {
+                self.write_child(child, schema, sequence, Some(child.min_occurs(self)), child.max_occurs > 1, nullable_types);
+            }
+        }
+        else if self.children.iter().all(|child| child.max_occurs <= 1 && child.element.namespace == self.namespace) {
+            let all = schema.append_element(complex_type, "xs:all");
+            for child in &self.children {
+                self.write_child(child, schema, all, Some(child.min_occurs(self)), false, nullable_types);
+            }
+        }
+        else {
+            //Children repeated in no fixed order can only be described as any number of each
+            let choice = schema.append_element(complex_type, "xs:choice");
+            schema.set_attribute(choice, "minOccurs", "0");
+            schema.set_attribute(choice, "maxOccurs", "unbounded");
+            for child in &self.children {
+                self.write_child(child, schema, choice, None, false, nullable_types);
+            }
+        }
+
+        self.write_attributes(schema, complex_type, nullable_types);
+
+        element
+    }
+
+    fn write_attributes(&self, schema: &mut XMLDocument, parent: NodeId, nullable_types: &mut Vec<SimpleType>) {
+        for attribute in &self.attributes {
+            let declaration = schema.append_element(parent, "xs:attribute");
+            schema.set_attribute(declaration, "name", &attribute.name);
+            schema.set_attribute(declaration, "type", attribute.values.type_name(nullable_types));
+
+            if attribute.count == self.instances {
+                schema.set_attribute(declaration, "use", "required");
+            }
+        }
+
+        if self.other_attributes {
+            let any_attribute = schema.append_element(parent, "xs:anyAttribute");
+            schema.set_attribute(any_attribute, "namespace", "##other");
+            schema.set_attribute(any_attribute, "processContents", "skip");
+        }
+    }
+}
+
+//Starts a schema document with a comment saying where it came from, returning it and its 'xs:schema' element
+fn new_schema(comment: &str, namespace: Option<&str>) -> (XMLDocument, NodeId) {
+    let mut schema = XMLDocument::new();
+    let root = schema.root();
+
+    schema.append_comment(root, &format!(" {} ", comment));
+
+    let schema_node = schema.append_element(root, "xs:schema");
+    schema.set_attribute(schema_node, "xmlns:xs", XSD_NAMESPACE);
+
+    if let Some(namespace) = namespace {
+        schema.set_attribute(schema_node, "xmlns", namespace);
+        schema.set_attribute(schema_node, "targetNamespace", namespace);
+        schema.set_attribute(schema_node, "elementFormDefault", "qualified");
+    }
+
+    (schema, schema_node)
+}
+
+//Defines the types the schema uses for values which may also be blank, as unions with a blank string
+fn write_nullable_types(schema: &mut XMLDocument, schema_node: NodeId, nullable_types: &[SimpleType]) {
+    if nullable_types.is_empty() {
+        return;
+    }
+
+    let blank = schema.append_element(schema_node, "xs:simpleType");
+    schema.set_attribute(blank, "name", "blank");
+    let restriction = schema.append_element(blank, "xs:restriction");
+    schema.set_attribute(restriction, "base", "xs:string");
+    let pattern = schema.append_element(restriction, "xs:pattern");
+    schema.set_attribute(pattern, "value", "\\s*");
+
+    for simple_type in nullable_types {
+        let definition = schema.append_element(schema_node, "xs:simpleType");
+        schema.set_attribute(definition, "name", simple_type.nullable_name());
+        let union = schema.append_element(definition, "xs:union");
+        schema.set_attribute(union, "memberTypes", &format!("{} blank", simple_type.xsd_name()));
+    }
+}
+
+//Collects the structure of sample documents, to write a schema which all of them match
+#[derive(Debug)]
+pub struct SchemaInference {
+    //The namespace of the outermost elements, set by the first sample
+    namespace: Option<Option<String>>,
+    roots: Vec<ElementStatistics>,
+    samples: usize,
+}
+
+impl Default for SchemaInference {
+    fn default() -> SchemaInference {
+        SchemaInference::new()
+    }
+}
+
+impl SchemaInference {
+    pub fn new() -> SchemaInference {
+        SchemaInference {
+            namespace: None,
+            roots: Vec::new(),
+            samples: 0,
+        }
+    }
+
+    //Adds the elements of a sample to what the schema must allow
+    pub fn add_document(&mut self, document: &XMLDocument) -> Result<(), String> {
+        let root = match document.child_elements(document.root()).first() {
+            Some(&root) => root,
+            None => return Err("The sample has no elements".to_owned()),
+        };
+
+        //One schema describes one namespace, which the samples' outermost elements must share
+        let namespace = document.namespace(root).map(|uri| uri.to_owned());
+        match self.namespace {
+            Some(ref expected) if *expected != namespace => {
+                return Err(format!("Element '{}' is in a different namespace from the other samples, which one schema cannot describe", document.name(root)));
+            },
+            _ => self.namespace = Some(namespace.clone()),
+        }
+
+        let local_name = document.local_name(root);
+        let index = match self.roots.iter().position(|statistics| statistics.local_name == local_name) {
+            Some(index) => index,
+            None => {
+                self.roots.push(ElementStatistics::new(namespace, local_name));
+                self.roots.len() - 1
+            },
+        };
+
+        self.roots[index].add(document, root);
+        self.samples += 1;
+
+        Ok(())
+    }
+
+    //Writes the schema as an XSD document
+    pub fn to_document(&self) -> XMLDocument {
+        let comment = format!("Inferred from {} sample{}", self.samples, if self.samples == 1 { "" } else { "s" });
+        let namespace = match self.namespace {
+            Some(Some(ref namespace)) => Some(namespace.as_str()),
+            _ => None,
+        };
+        let (mut schema, schema_node) = new_schema(&comment, namespace);
+        let mut nullable_types: Vec<SimpleType> = Vec::new();
+
+        for element in &self.roots {
+            element.write(&mut schema, schema_node, &mut nullable_types);
+        }
+
+        write_nullable_types(&mut schema, schema_node, &nullable_types);
+
+        schema
+    }
+}
+
+//Writes a schema for XML converted from a CSV file, whose outermost element holds an element for each row with a child for
+//each column. Each column is typed from its values, and may be blank if any of its cells were.
+pub fn csv_schema(document: &XMLDocument) -> Result<XMLDocument, String> {
+    let table = match document.child_elements(document.root()).first() {
+        Some(&table) => table,
+        None => return Err("The document has no elements".to_owned()),
+    };
+
+    let rows = document.child_elements(table);
+    let columns = match rows.first() {
+        Some(&row) => document.child_elements(row),
+        None => return Err("The document has no rows".to_owned()),
+    };
+
+    let mut values: Vec<ValueStatistics> = vec![ValueStatistics::new(); columns.len()];
+    for &row in &rows {
+        for (statistics, field) in values.iter_mut().zip(document.child_elements(row)) {
+            statistics.add(&document.text(field));
+        }
+    }
+
+    let comment = format!("Describes XML converted from CSV, inferred from {} row{}", rows.len(), if rows.len() == 1 { "" } else { "s" });
+    let (mut schema, schema_node) = new_schema(&comment, None);
+    let mut nullable_types: Vec<SimpleType> = Vec::new();
+
+    let table_element = schema.append_element(schema_node, "xs:element");
+    schema.set_attribute(table_element, "name", document.name(table));
+    let table_type = schema.append_element(table_element, "xs:complexType");
+    let table_sequence = schema.append_element(table_type, "xs:sequence");
+
+    let row_element = schema.append_element(table_sequence, "xs:element");
+    schema.set_attribute(row_element, "name", document.name(rows[0]));
+    schema.set_attribute(row_element, "maxOccurs", "unbounded");
+    let row_type = schema.append_element(row_element, "xs:complexType");
+    let row_sequence = schema.append_element(row_type, "xs:sequence");
+
+    for (&column, statistics) in columns.iter().zip(values.iter()) {
+        match document.namespace(column) {
+            //Columns in a namespace would need a schema of their own, so any element in it is allowed
+            Some(uri) => {
+                let any = schema.append_element(row_sequence, "xs:any");
+                schema.set_attribute(any, "namespace", uri);
+                schema.set_attribute(any, "processContents", "skip");
+            },
+            None => {
+                let field = schema.append_element(row_sequence, "xs:element");
+                schema.set_attribute(field, "name", document.local_name(column));
+                schema.set_attribute(field, "type", statistics.type_name(&mut nullable_types));
+            },
+        }
+    }
+
+    write_nullable_types(&mut schema, schema_node, &nullable_types);
+
+    Ok(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xpath::{xpath_query, item_string};
+    use xsd::Schema;
+    use {lexer, parser};
+
+    const SAMPLES: [&str; 2] = [
+        "<feed><item id=\"1\" sale=\"true\"><price>1.50</price><added>2024-01-31</added><tag>a</tag><tag>b</tag></item></feed>",
+        "<feed><item id=\"2\"><price>3</price><added>2024-02-01</added></item><item id=\"3\"><price>4</price><added>2024-02-02</added><note/></item></feed>",
+    ];
+
+    fn parse(xml: &str) -> XMLDocument {
+        parser(&lexer(xml.to_owned()).unwrap()).unwrap()
+    }
+
+    //The schema inferred from the samples
+    fn infer() -> XMLDocument {
+        let mut inference = SchemaInference::new();
+
+        for sample in SAMPLES.iter() {
+            inference.add_document(&parse(sample)).unwrap();
+        }
+
+        inference.to_document()
+    }
+
+    #[test]
+    fn values_take_the_narrowest_type_seen() {
+        assert_eq!(SimpleType::of("12").merge(SimpleType::of("1.5")).xsd_name(), "xs:decimal");
+        assert_eq!(SimpleType::of("2024-01-31").xsd_name(), "xs:date");
+        assert_eq!(SimpleType::of("true").merge(SimpleType::of("no")).xsd_name(), "xs:string");
+    }
+
+    #[test]
+    fn describes_nesting_cardinality_and_types() {
+        let schema = infer();
+        let query = |expression: &str| -> Vec<String> {
+            xpath_query(&schema, schema.root(), expression).unwrap().iter().map(|item| item_string(&schema, item)).collect()
+        };
+
+        assert_eq!(query("/xs:schema/xs:element/@name"), vec!["feed"]);
+        assert_eq!(query("//xs:element[@name='item']/@maxOccurs"), vec!["unbounded"]);
+        assert_eq!(query("//xs:element[@name='item']//xs:element/@name"), vec!["price", "added", "note", "tag"]);
+        assert_eq!(query("//xs:element[@name='price']/@type"), vec!["xs:decimal"]);
+        assert_eq!(query("//xs:element[@name='added']/@type"), vec!["xs:date"]);
+        assert_eq!(query("//xs:element[@name='note']/@minOccurs"), vec!["0"]);
+        assert_eq!(query("//xs:element[@name='tag']/@maxOccurs"), vec!["unbounded"]);
+        assert_eq!(query("//xs:attribute[@name='id']/@use"), vec!["required"]);
+        assert_eq!(query("//xs:attribute[@name='sale']/@type"), vec!["xs:boolean"]);
+        assert!(query("//xs:attribute[@name='sale']/@use").is_empty());
+    }
+
+    #[test]
+    fn samples_match_the_inferred_schema() {
+        let schema = Schema::parse(&infer()).unwrap();
+
+        for sample in SAMPLES.iter() {
+            assert!(schema.validate(&parse(sample)).is_empty());
+        }
+        assert!(!schema.validate(&parse("<feed><item><price>x</price></item></feed>")).is_empty());
+    }
+}